@@ -0,0 +1,255 @@
+//! # Orzatty Conformance
+//!
+//! A battery of wire-level checks run against a live server, to validate
+//! alternate implementations of the protocol (and to regression-test the
+//! reference server).
+//!
+//! The target must accept the auth handshake and echo every application
+//! frame back unchanged on the stream it arrived on, as the reference
+//! server does. Each check uses a fresh connection.
+
+use std::fmt;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use orzatty_client::OrzattyClient;
+use orzatty_core::auth::AuthMessage;
+use orzatty_core::control::ControlMessage;
+use orzatty_core::frame::{FrameFlags, FrameHeader, FrameType};
+use orzatty_core::Framer;
+use quinn::{Connection, RecvStream, SendStream};
+
+/// How long a single read may take before the check counts as failed.
+pub const CHECK_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Where and how to reach the server under test.
+pub struct Target {
+    pub addr: SocketAddr,
+    pub server_name: String,
+    pub token: String,
+}
+
+/// Result of a single check.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    Pass,
+    Fail(String),
+    /// The check could not run (e.g. the protocol feature it covers is not defined yet).
+    Skip(String),
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Outcome::Pass => write!(f, "PASS"),
+            Outcome::Fail(why) => write!(f, "FAIL ({})", why),
+            Outcome::Skip(why) => write!(f, "SKIP ({})", why),
+        }
+    }
+}
+
+pub struct CheckResult {
+    pub name: &'static str,
+    pub outcome: Outcome,
+}
+
+/// Runs every check in order.
+pub async fn run_all(target: &Target) -> Result<Vec<CheckResult>> {
+    // Conformance targets usually run with dev certificates
+    let client = OrzattyClient::with_config(true).await?;
+
+    let mut results = Vec::new();
+    results.push(check("handshake_accepted", handshake_accepted(&client, target)).await);
+    results.push(check("malformed_handshake_rejected", malformed_handshake_rejected(&client, target)).await);
+    results.push(check("frame_types_echo", frame_types_echo(&client, target)).await);
+    results.push(check("varint_boundaries", varint_boundaries(&client, target)).await);
+    results.push(check("oversized_frame_rejected", oversized_frame_rejected(&client, target)).await);
+    results.push(check("ping_pong", ping_pong(&client, target)).await);
+    results.push(check("migration_survived", migration_survived(target)).await);
+    Ok(results)
+}
+
+async fn check(name: &'static str, f: impl std::future::Future<Output = Result<Outcome>>) -> CheckResult {
+    let outcome = f.await.unwrap_or_else(|e| Outcome::Fail(e.to_string()));
+    CheckResult { name, outcome }
+}
+
+async fn connect(client: &OrzattyClient, target: &Target) -> Result<Connection> {
+    Ok(client.connect(target.addr, &target.server_name, &target.token).await?)
+}
+
+async fn handshake_accepted(client: &OrzattyClient, target: &Target) -> Result<Outcome> {
+    let connection = connect(client, target).await?;
+    connection.close(0u32.into(), b"done");
+    Ok(Outcome::Pass)
+}
+
+async fn malformed_handshake_rejected(client: &OrzattyClient, target: &Target) -> Result<Outcome> {
+    let connection = client.establish(target.addr, &target.server_name, CHECK_TIMEOUT).await?;
+    let (mut send, mut recv) = connection.open_bi().await?;
+
+    // A well-formed frame whose body is not an archived AuthMessage
+    let garbage = [0xA5u8; 24];
+    write_frame(&mut send, header(FrameType::RkyvAligned, 0, 0, garbage.len() as u64), &garbage).await?;
+    let _ = send.finish().await;
+
+    let outcome = match read_frame(&mut recv).await {
+        Ok(Some((_, payload))) => match rkyv::from_bytes::<AuthMessage>(&aligned(&payload)) {
+            Ok(AuthMessage::Ok { .. }) => Outcome::Fail("server accepted a malformed Hello".into()),
+            _ => Outcome::Pass,
+        },
+        // Closing the stream or connection is a valid rejection
+        Ok(None) => Outcome::Pass,
+        Err(e) if e.is::<tokio::time::error::Elapsed>() => Outcome::Fail("no response".into()),
+        Err(_) => Outcome::Pass,
+    };
+    connection.close(0u32.into(), b"done");
+    Ok(outcome)
+}
+
+async fn frame_types_echo(client: &OrzattyClient, target: &Target) -> Result<Outcome> {
+    let connection = connect(client, target).await?;
+    let (mut send, mut recv) = connection.open_bi().await?;
+
+    for (frame_type, payload) in [
+        (FrameType::RawBinary, &b"\x00\x01\x02\xff"[..]),
+        (FrameType::RkyvAligned, &[0u8; 16][..]),
+        (FrameType::Utf8Text, "conformance ✓".as_bytes()),
+    ] {
+        let sent = header(frame_type, 1, 0, payload.len() as u64);
+        write_frame(&mut send, sent, payload).await?;
+        if let Some(why) = expect_echo(&mut recv, sent, payload).await? {
+            return Ok(Outcome::Fail(format!("{:?}: {}", frame_type, why)));
+        }
+    }
+    connection.close(0u32.into(), b"done");
+    Ok(Outcome::Pass)
+}
+
+async fn varint_boundaries(client: &OrzattyClient, target: &Target) -> Result<Outcome> {
+    const BOUNDARIES: [u64; 8] = [0, 63, 64, 16383, 16384, 1073741823, 1073741824, (1 << 62) - 1];
+
+    let connection = connect(client, target).await?;
+    let (mut send, mut recv) = connection.open_bi().await?;
+
+    for value in BOUNDARIES {
+        // channel_id is a u32 on the wire side of the API
+        let channel_id = value.min(u32::MAX as u64) as u32;
+        let sent = header(FrameType::RawBinary, channel_id, value, 1);
+        write_frame(&mut send, sent, b"v").await?;
+        if let Some(why) = expect_echo(&mut recv, sent, b"v").await? {
+            return Ok(Outcome::Fail(format!("value {}: {}", value, why)));
+        }
+    }
+    connection.close(0u32.into(), b"done");
+    Ok(Outcome::Pass)
+}
+
+async fn oversized_frame_rejected(client: &OrzattyClient, target: &Target) -> Result<Outcome> {
+    let connection = connect(client, target).await?;
+    let (mut send, mut recv) = connection.open_bi().await?;
+
+    // Claims the largest encodable payload; a conforming server refuses it
+    // instead of buffering until it arrives
+    write_frame(&mut send, header(FrameType::RawBinary, 1, 0, (1 << 62) - 1), b"x").await?;
+
+    let outcome = match read_frame(&mut recv).await {
+        Ok(Some(_)) => Outcome::Fail("server answered an oversized frame".into()),
+        Err(e) if e.is::<tokio::time::error::Elapsed>() => {
+            Outcome::Fail("server kept waiting for the payload".into())
+        }
+        Ok(None) | Err(_) => Outcome::Pass,
+    };
+    connection.close(0u32.into(), b"done");
+    Ok(outcome)
+}
+
+async fn ping_pong(client: &OrzattyClient, target: &Target) -> Result<Outcome> {
+    const TS: u64 = 0x0123_4567_89ab;
+
+    let connection = connect(client, target).await?;
+    let (mut send, mut recv) = connection.open_bi().await?;
+
+    let body = rkyv::to_bytes::<_, 64>(&ControlMessage::Ping { ts: TS })
+        .map_err(|e| anyhow!("serialize Ping: {:?}", e))?;
+    let ping = FrameHeader { flags: FrameFlags::CONTROL, ..header(FrameType::RkyvAligned, 0, 0, body.len() as u64) };
+    write_frame(&mut send, ping, &body).await?;
+
+    let (reply, body) = read_frame(&mut recv).await?
+        .ok_or_else(|| anyhow!("stream closed before Pong"))?;
+    let outcome = if !reply.flags.contains(FrameFlags::CONTROL) {
+        Outcome::Fail("reply is not a control frame".into())
+    } else {
+        match rkyv::from_bytes::<ControlMessage>(&aligned(&body)) {
+            Ok(ControlMessage::Pong { ts: TS }) => Outcome::Pass,
+            Ok(other) => Outcome::Fail(format!("expected Pong {{ ts: {} }}, got {:?}", TS, other)),
+            Err(_) => Outcome::Fail("reply is not a ControlMessage".into()),
+        }
+    };
+    connection.close(0u32.into(), b"done");
+    Ok(outcome)
+}
+
+async fn migration_survived(target: &Target) -> Result<Outcome> {
+    // A client of its own: rebinding moves every connection on the endpoint
+    let client = OrzattyClient::with_config(true).await?;
+    let connection = connect(&client, target).await?;
+    let (mut send, mut recv) = connection.open_bi().await?;
+
+    let before = header(FrameType::RawBinary, 1, 0, 6);
+    write_frame(&mut send, before, b"before").await?;
+    if let Some(why) = expect_echo(&mut recv, before, b"before").await? {
+        return Ok(Outcome::Fail(why));
+    }
+
+    // Same interface, new port: to the server the client moved
+    let local = client.endpoint().local_addr()?;
+    client.endpoint().rebind(std::net::UdpSocket::bind(SocketAddr::new(local.ip(), 0))?)?;
+    let after = header(FrameType::RawBinary, 1, 0, 5);
+    write_frame(&mut send, after, b"after").await?;
+    let outcome = match expect_echo(&mut recv, after, b"after").await {
+        Ok(None) => Outcome::Pass,
+        Ok(Some(why)) => Outcome::Fail(format!("after migrating: {}", why)),
+        Err(e) => Outcome::Fail(format!("after migrating: {}", e)),
+    };
+    connection.close(0u32.into(), b"done");
+    Ok(outcome)
+}
+
+fn aligned(bytes: &[u8]) -> rkyv::AlignedVec {
+    let mut buf = rkyv::AlignedVec::new();
+    buf.extend_from_slice(bytes);
+    buf
+}
+
+fn header(frame_type: FrameType, channel_id: u32, stream_id: u64, length: u64) -> FrameHeader {
+    FrameHeader { flags: FrameFlags::empty(), frame_type, channel_id, stream_id, length }
+}
+
+async fn write_frame(send: &mut SendStream, header: FrameHeader, payload: &[u8]) -> Result<()> {
+    let mut head = vec![0u8; header.encoded_len()];
+    header.encode(&mut head)?;
+    send.write_all(&head).await?;
+    send.write_all(payload).await?;
+    Ok(())
+}
+
+async fn read_frame(recv: &mut RecvStream) -> Result<Option<(FrameHeader, Vec<u8>)>> {
+    let mut framer = Framer::new();
+    let frame = tokio::time::timeout(CHECK_TIMEOUT, framer.read_frame(recv)).await??;
+    Ok(frame.map(|(h, p)| (h, p.to_vec())))
+}
+
+/// Reads one frame and compares it to what was sent; `Some(reason)` on mismatch.
+async fn expect_echo(recv: &mut RecvStream, sent: FrameHeader, payload: &[u8]) -> Result<Option<String>> {
+    let (echoed, body) = read_frame(recv).await?
+        .ok_or_else(|| anyhow!("stream closed before echo"))?;
+    if echoed != sent {
+        return Ok(Some(format!("header {:?} echoed as {:?}", sent, echoed)));
+    }
+    if body != payload {
+        return Ok(Some("payload differs".into()));
+    }
+    Ok(None)
+}
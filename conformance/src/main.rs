@@ -0,0 +1,30 @@
+use anyhow::Result;
+use orzatty_conformance::{run_all, Outcome, Target};
+
+/// Usage: `conformance [addr] [server_name] [token]`
+#[tokio::main]
+async fn main() -> Result<()> {
+    let mut args = std::env::args().skip(1);
+    let target = Target {
+        addr: args.next().as_deref().unwrap_or("127.0.0.1:5000").parse()?,
+        server_name: args.next().unwrap_or_else(|| "localhost".into()),
+        token: args.next().unwrap_or_else(|| "conformance".into()),
+    };
+
+    println!("Orzatty conformance against {}", target.addr);
+    let results = run_all(&target).await?;
+
+    let mut failed = 0;
+    for result in &results {
+        println!("  {:<32} {}", result.name, result.outcome);
+        if matches!(result.outcome, Outcome::Fail(_)) {
+            failed += 1;
+        }
+    }
+    println!("{} checks, {} failed", results.len(), failed);
+
+    if failed > 0 {
+        std::process::exit(1);
+    }
+    Ok(())
+}
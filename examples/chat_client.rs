@@ -0,0 +1,34 @@
+use orzatty_client::easy::{EasyClient, EasyClientConfig};
+use orzatty_client::OrzattyClient;
+use tokio::io::{AsyncBufReadExt, BufReader};
+
+/// Channel the reference server relays to every connected client.
+const CHAT_CHANNEL: u32 = 10;
+
+/// Certificate `simple_server` writes at startup; ORZATTY_CERT overrides it.
+const DEV_CERT_FILE: &str = "orzatty-dev-cert.der";
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    // 🔗 1. Connect to the reference server (`simple_server`), trusting the
+    // self-signed certificate it wrote out
+    println!("💬 Orzatty Chat Client Starting...");
+    let cert_path = std::env::var("ORZATTY_CERT").unwrap_or_else(|_| DEV_CERT_FILE.into());
+    let quic = OrzattyClient::builder().add_root_cert(std::fs::read(&cert_path)?).build().await?;
+    let client = EasyClient::connect_with_client(&quic, "127.0.0.1:5000", "chat-user", EasyClientConfig::default()).await?;
+    println!("✅ Connected! Type a line and press Enter; run several clients to chat.");
+
+    // 📥 2. Print whatever the server relays, our own lines included
+    client.on(CHAT_CHANNEL, |data| println!("{}", String::from_utf8_lossy(&data))).await;
+    client.on_disconnect(|reason| println!("🔌 Disconnected: {:?}", reason)).await;
+
+    // 📤 3. Send each stdin line to the chat channel
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    while let Some(line) = lines.next_line().await? {
+        client.send(CHAT_CHANNEL, line.as_bytes()).await?;
+    }
+
+    // 👋 4. Leave cleanly once stdin closes
+    client.close(0, "bye").await?;
+    Ok(())
+}
@@ -1,29 +1,38 @@
+use orzatty_client::easy::{EasyClient, EasyClientConfig};
 use orzatty_client::OrzattyClient;
-use std::net::SocketAddr;
+use std::time::Duration;
+
+/// Certificate `simple_server` writes at startup; ORZATTY_CERT overrides it.
+const DEV_CERT_FILE: &str = "orzatty-dev-cert.der";
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    // 🔗 1. Create the indestructible client
+    // 🔗 1. Create the indestructible client, trusting the reference
+    // server's self-signed certificate
     println!("🚀 Orzatty Echo Client Starting...");
-    let client = OrzattyClient::new().await?;
+    let cert_path = std::env::var("ORZATTY_CERT").unwrap_or_else(|_| DEV_CERT_FILE.into());
+    let quic = OrzattyClient::builder().add_root_cert(std::fs::read(&cert_path)?).build().await?;
 
     // 📍 2. Define server address and token
-    let addr: SocketAddr = "127.0.0.1:5000".parse()?;
+    let addr = "127.0.0.1:5000";
     let token = "YOUR_SECRET_TOKEN"; // Change this in production
 
     // 🤝 3. Connect to the server
     println!("🔗 Connecting to {}...", addr);
-    let connection = client.connect(addr, "localhost", token).await?;
+    let client = EasyClient::connect_with_client(&quic, addr, token, EasyClientConfig::default()).await?;
     println!("✅ Authenticated and Connected!");
 
     // 📤 4. Send a message on Channel 1
     let message = b"Hello from the open-source client!";
     println!("📤 Sending: {:?}", String::from_utf8_lossy(message));
     
-    // The .send() method uses actor-based backpressure (The Governor)
-    connection.send(1, message).await?;
+    // Sends go through actor-based backpressure (The Governor); the
+    // reference server echoes the frame back as the reply
+    let reply = client.request(1, message, Duration::from_secs(5)).await?;
+    println!("📥 Echoed: {:?}", String::from_utf8_lossy(&reply));
 
-    println!("🎉 Message sent successfully! Orzatty is reliable and fast.");
+    println!("🎉 Round trip complete! Orzatty is reliable and fast.");
+    client.close(0, "done").await?;
 
     Ok(())
 }
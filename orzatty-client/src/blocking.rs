@@ -0,0 +1,194 @@
+//! A synchronous facade over [`EasyClient`], for callers without an async
+//! runtime of their own (e.g. a game engine's frame loop).
+//!
+//! [`BlockingClient`] runs the client's actors on a single-threaded tokio
+//! runtime in a background thread, and each call blocks the calling thread
+//! until the async operation completes. Incoming frames are queued for
+//! [`recv`](BlockingClient::recv) / [`try_recv`](BlockingClient::try_recv)
+//! instead of being handed to callbacks. Must not be used from inside an
+//! async context, where blocking would stall the caller's runtime.
+//!
+//! ```ignore
+//! let client = BlockingClient::connect("127.0.0.1:5000", "token")?;
+//! client.send(1, b"hello")?;
+//! loop {
+//!     while let Some(frame) = client.try_recv()? {
+//!         handle(frame.header.channel_id, &frame.payload);
+//!     }
+//!     step_simulation();
+//! }
+//! ```
+
+use std::future::Future;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use tokio::runtime::Handle;
+use tokio::sync::oneshot;
+
+use crate::easy::{EasyClient, EasyClientConfig, ReceivedFrame, SessionSummary};
+use crate::{ClientError, Result};
+
+/// A connected client whose calls block instead of returning futures.
+///
+/// Dropping it stops the background runtime, discarding frames still queued
+/// for sending; call [`close`](Self::close) or [`shutdown`](Self::shutdown)
+/// first for an orderly exit.
+pub struct BlockingClient {
+    inner: EasyClient,
+    frames: mpsc::Receiver<ReceivedFrame>,
+    // Declared last: the runtime must outlive the client's tasks
+    runtime: RuntimeThread,
+}
+
+impl BlockingClient {
+    /// Connects and authenticates like [`EasyClient::connect`].
+    pub fn connect(addr: &str, token: &str) -> Result<Self> {
+        Self::connect_with_config(addr, token, EasyClientConfig::default())
+    }
+
+    /// Like [`connect`](Self::connect), with the tuning of
+    /// [`EasyClient::connect_with_config`].
+    pub fn connect_with_config(addr: &str, token: &str, config: EasyClientConfig) -> Result<Self> {
+        let runtime = RuntimeThread::start()?;
+        let (tx, frames) = mpsc::channel();
+        let inner = runtime.block_on(async {
+            let client = EasyClient::connect_with_config(addr, token, config).await?;
+            // Dropping the sender on disconnect ends `recv` once the queue drains
+            let tx = Arc::new(Mutex::new(Some(tx)));
+            let queue = tx.clone();
+            client.on_any_ctx(move |header, payload| {
+                if let Some(tx) = &*queue.lock().unwrap() {
+                    let _ = tx.send(ReceivedFrame { header, payload });
+                }
+            }).await;
+            client.on_disconnect(move |_| {
+                tx.lock().unwrap().take();
+            }).await;
+            Ok::<_, ClientError>(client)
+        })?;
+        Ok(Self { inner, frames, runtime })
+    }
+
+    /// The wrapped client, for anything not mirrored here. Its async methods
+    /// can be driven with [`block_on`](Self::block_on).
+    pub fn client(&self) -> &EasyClient {
+        &self.inner
+    }
+
+    /// Runs `future` on the client's runtime and blocks until it completes.
+    pub fn block_on<F: Future>(&self, future: F) -> F::Output {
+        self.runtime.block_on(future)
+    }
+
+    /// Queues `data` on `channel_id`, blocking while the outbound queue is
+    /// full; see [`EasyClient::send`].
+    pub fn send(&self, channel_id: u32, data: &[u8]) -> Result<()> {
+        self.block_on(self.inner.send(channel_id, data))
+    }
+
+    /// Blocks until the next frame arrives. Fails once the connection has
+    /// ended and every frame received before that has been returned.
+    pub fn recv(&self) -> Result<ReceivedFrame> {
+        self.frames.recv().map_err(|_| Self::disconnected())
+    }
+
+    /// Like [`recv`](Self::recv), giving up with `Ok(None)` after `timeout`.
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<Option<ReceivedFrame>> {
+        match self.frames.recv_timeout(timeout) {
+            Ok(frame) => Ok(Some(frame)),
+            Err(mpsc::RecvTimeoutError::Timeout) => Ok(None),
+            Err(mpsc::RecvTimeoutError::Disconnected) => Err(Self::disconnected()),
+        }
+    }
+
+    /// The next frame if one is already queued, without blocking. Fails
+    /// like [`recv`](Self::recv) once the connection has ended.
+    pub fn try_recv(&self) -> Result<Option<ReceivedFrame>> {
+        match self.frames.try_recv() {
+            Ok(frame) => Ok(Some(frame)),
+            Err(mpsc::TryRecvError::Empty) => Ok(None),
+            Err(mpsc::TryRecvError::Disconnected) => Err(Self::disconnected()),
+        }
+    }
+
+    /// Waits until every frame queued so far has been written; see
+    /// [`EasyClient::flush`].
+    pub fn flush(&self) -> Result<()> {
+        self.block_on(self.inner.flush())
+    }
+
+    /// See [`EasyClient::shutdown`].
+    pub fn shutdown(&self) -> SessionSummary {
+        self.block_on(self.inner.shutdown())
+    }
+
+    /// See [`EasyClient::close`].
+    pub fn close(self, code: u16, reason: &str) -> Result<SessionSummary> {
+        self.runtime.block_on(self.inner.close(code, reason))
+    }
+
+    fn disconnected() -> ClientError {
+        ClientError::ConnectionClosed("Connection closed".into())
+    }
+}
+
+/// A current-thread runtime driven by a background thread until dropped.
+struct RuntimeThread {
+    handle: Handle,
+    stop: Option<oneshot::Sender<()>>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl RuntimeThread {
+    fn start() -> Result<Self> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(|e| ClientError::Config(format!("Cannot start runtime: {}", e)))?;
+        let handle = runtime.handle().clone();
+        let (stop, stopped) = oneshot::channel();
+        let thread = thread::Builder::new()
+            .name("orzatty-blocking".into())
+            // This thread drives IO and timers; callers only poll their own futures
+            .spawn(move || runtime.block_on(async { let _ = stopped.await; }))
+            .map_err(|e| ClientError::Config(format!("Cannot start runtime thread: {}", e)))?;
+        Ok(Self { handle, stop: Some(stop), thread: Some(thread) })
+    }
+
+    fn block_on<F: Future>(&self, future: F) -> F::Output {
+        self.handle.block_on(future)
+    }
+}
+
+impl Drop for RuntimeThread {
+    fn drop(&mut self) {
+        if let Some(stop) = self.stop.take() {
+            let _ = stop.send(());
+        }
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_runtime_thread_drives_timers() {
+        let runtime = RuntimeThread::start().unwrap();
+        // Timers fire only if the background thread is driving the runtime
+        let slept = runtime.block_on(async {
+            let started = tokio::time::Instant::now();
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            started.elapsed()
+        });
+        assert!(slept >= Duration::from_millis(20));
+
+        let task = runtime.handle.spawn(async { 7 });
+        assert_eq!(runtime.block_on(task).unwrap(), 7);
+    }
+}
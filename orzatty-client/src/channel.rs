@@ -0,0 +1,139 @@
+//! Per-channel handles over an [`EasyClient`].
+//!
+//! A frame header carries two ids that are easy to mix up:
+//!
+//! - `channel_id` is the application's multiplexing key. It picks the handler
+//!   a frame is delivered to on either side, and is what a [`Channel`] wraps.
+//! - `stream_id` is an opaque per-frame field. `EasyClient` fills it with the
+//!   index of the QUIC stream the frame was written to, and
+//!   [`request`](Channel::request) puts a correlation id there instead. It
+//!   never selects a transport stream; which stream a channel uses is decided
+//!   by [`StreamStrategy`](crate::easy::StreamStrategy).
+
+use std::future::Future;
+use std::time::Duration;
+
+use bytes::Bytes;
+
+use rkyv::de::deserializers::SharedDeserializeMap;
+use rkyv::ser::serializers::AllocSerializer;
+use rkyv::validation::validators::DefaultValidator;
+use rkyv::{Archive, CheckBytes, Deserialize, Serialize};
+
+use crate::easy::{EasyClient, ReceivedFrame};
+use crate::{Result, TrySendError};
+
+/// One channel of an [`EasyClient`], obtained from [`EasyClient::channel`].
+///
+/// Every call is the `EasyClient` method of the same name with this channel's
+/// id filled in. Cheap to clone; clones share the client.
+///
+/// ```ignore
+/// let chat = client.channel(client.open_channel().await?);
+/// chat.on(|msg| println!("{}", String::from_utf8_lossy(&msg))).await;
+/// chat.send(b"hello").await?;
+/// let pong = chat.request(b"ping", Duration::from_secs(1)).await?;
+/// chat.close().await?;
+/// ```
+#[derive(Clone)]
+pub struct Channel {
+    client: EasyClient,
+    id: u32,
+}
+
+impl Channel {
+    pub(crate) fn new(client: EasyClient, id: u32) -> Self {
+        Self { client, id }
+    }
+
+    /// The `channel_id` stamped on every frame sent through this handle.
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// The client this channel belongs to.
+    pub fn client(&self) -> &EasyClient {
+        &self.client
+    }
+
+    pub async fn send(&self, data: &[u8]) -> Result<()> {
+        self.client.send(self.id, data).await
+    }
+
+    pub async fn send_text(&self, text: &str) -> Result<()> {
+        self.client.send_text(self.id, text).await
+    }
+
+    pub fn try_send(&self, data: &[u8]) -> std::result::Result<(), TrySendError> {
+        self.client.try_send(self.id, data)
+    }
+
+    pub async fn send_priority(&self, data: &[u8]) -> Result<()> {
+        self.client.send_priority(self.id, data).await
+    }
+
+    pub async fn send_datagram(&self, data: &[u8]) -> Result<()> {
+        self.client.send_datagram(self.id, data).await
+    }
+
+    pub async fn send_typed<T>(&self, value: &T) -> Result<()>
+    where
+        T: Serialize<AllocSerializer<256>>,
+    {
+        self.client.send_typed(self.id, value).await
+    }
+
+    pub async fn request(&self, data: &[u8], timeout: Duration) -> Result<Bytes> {
+        self.client.request(self.id, data, timeout).await
+    }
+
+    pub async fn on(&self, callback: impl Fn(Bytes) + Send + Sync + 'static) {
+        self.client.on(self.id, callback).await
+    }
+
+    pub async fn on_owned(&self, callback: impl Fn(Vec<u8>) + Send + Sync + 'static) {
+        self.client.on_owned(self.id, callback).await
+    }
+
+    pub async fn on_text(&self, callback: impl Fn(String) + Send + Sync + 'static) {
+        self.client.on_text(self.id, callback).await
+    }
+
+    pub async fn on_frame(&self, callback: impl Fn(ReceivedFrame) + Send + Sync + 'static) {
+        self.client.on_frame(self.id, callback).await
+    }
+
+    pub async fn on_async<F, Fut>(&self, callback: F)
+    where
+        F: Fn(Bytes) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.client.on_async(self.id, callback).await
+    }
+
+    pub async fn on_typed<T>(&self, callback: impl Fn(T) + Send + Sync + 'static)
+    where
+        T: Archive + 'static,
+        T::Archived: for<'a> CheckBytes<DefaultValidator<'a>> + Deserialize<T, SharedDeserializeMap>,
+    {
+        self.client.on_typed(self.id, callback).await
+    }
+
+    pub async fn sequence(&self) {
+        self.client.sequence_channel(self.id).await
+    }
+
+    pub fn set_rate(&self, frames_per_sec: u32) {
+        self.client.set_channel_rate(self.id, frames_per_sec)
+    }
+
+    pub fn clear_rate(&self) {
+        self.client.clear_channel_rate(self.id)
+    }
+
+    /// Drops the handler and, for an id from
+    /// [`open_channel`](EasyClient::open_channel), releases it to the server.
+    pub async fn close(self) -> Result<()> {
+        self.client.close_channel(self.id).await
+    }
+}
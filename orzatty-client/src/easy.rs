@@ -1,11 +1,32 @@
+use std::any::Any;
 use std::collections::HashMap;
+use std::future::Future;
+use std::net::{IpAddr, SocketAddr};
+use std::panic::{self, AssertUnwindSafe};
 use std::sync::Arc;
-use tokio::sync::{mpsc, Mutex};
-use crate::OrzattyClient;
-use orzatty_core::frame::{FrameHeader, FrameType, FrameFlags};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, oneshot, watch, Mutex, Semaphore};
+use tokio::task::JoinHandle;
+use crate::{ClientError, OrzattyClient, Result, TrySendError, DEFAULT_AUTH_ATTEMPTS, DEFAULT_AUTH_TIMEOUT, DEFAULT_CONNECT_TIMEOUT};
+use crate::channel::Channel;
+use crate::rate::TokenBucket;
+use crate::typed;
+use orzatty_core::frame::{FrameBuilder, FrameHeader, FrameType, FrameFlags};
+use orzatty_core::auth::{Capabilities, SessionInfo};
+use orzatty_core::control::ControlMessage;
+use orzatty_core::sequence::{self, SequenceCheck, SequenceTracker, SEQUENCE_BASE};
+use orzatty_core::decoder::LimitAction;
 use orzatty_core::Framer;
-use anyhow::{Result, anyhow};
-use quinn::{Connection, SendStream};
+use bytes::{Bytes, BytesMut};
+use futures::FutureExt;
+use quinn::{Connection, Endpoint, SendStream};
+use rkyv::de::deserializers::SharedDeserializeMap;
+use rkyv::ser::serializers::AllocSerializer;
+use rkyv::validation::validators::DefaultValidator;
+use rkyv::{Archive, CheckBytes, Deserialize, Serialize};
+#[cfg(feature = "stream-digest")]
+use orzatty_core::StreamDigest;
 
 /// A high-level wrapper around `OrzattyClient` that manages channels and callbacks.
 /// 
@@ -17,146 +38,2627 @@ use quinn::{Connection, SendStream};
 #[derive(Clone)]
 pub struct EasyClient {
     connection: Connection,
+    // The connection's endpoint, which owns the UDP socket (see `rebind`)
+    endpoint: Endpoint,
     router: Arc<Mutex<Router>>,
     // The "Governor" channel - entry point for all outgoing messages
     tx: mpsc::Sender<OutboundMessage>,
+    // Priority lane of the Governor, drained ahead of `tx` (see `send_priority`)
+    priority_tx: mpsc::Sender<OutboundMessage>,
+    // Per-channel queues, each drained onto its own stream (`StreamPerChannel`)
+    channel_streams: Arc<std::sync::Mutex<HashMap<u32, mpsc::Sender<OutboundMessage>>>>,
+    config: EasyClientConfig,
+    // Features agreed with the server in the handshake
+    capabilities: Capabilities,
+    // Identity, connection id and metadata the server assigned in the handshake
+    session: Arc<SessionInfo>,
+    #[cfg(feature = "stream-digest")]
+    digests: Arc<Digests>,
+    // Client-side per-channel emission budgets (see `set_channel_rate`)
+    channel_rates: Arc<std::sync::Mutex<HashMap<u32, TokenBucket>>>,
+    counters: Arc<Counters>,
+    lifecycle: Arc<Lifecycle>,
+    control: Arc<Control>,
+    // Frames no handler took, for `recv`
+    inbox: Arc<Mutex<mpsc::Receiver<ReceivedFrame>>>,
+}
+
+/// Requests waiting for a server reply, keyed by request id, and ping state.
+struct Control {
+    // Ping timestamps are microseconds since this instant
+    epoch: Instant,
+    next_request: AtomicU32,
+    pending_opens: std::sync::Mutex<HashMap<u32, oneshot::Sender<u32>>>,
+    // `request` correlation ids, offset by REQUEST_ID_BASE
+    next_correlation: AtomicU64,
+    pending_replies: std::sync::Mutex<HashMap<u64, oneshot::Sender<Bytes>>>,
+    // Latest ping round trip in microseconds; 0 until the first pong
+    last_rtt_micros: AtomicU64,
+    pongs_received: AtomicU64,
+    // Set by the pinger before it closes a connection whose server went quiet
+    heartbeat_expired: AtomicBool,
+    // Next sequence number of each channel set up with `sequence_channel`
+    next_sequence: std::sync::Mutex<HashMap<u32, u64>>,
+    // Payload caps from `set_channel_limit`, watched by every reader
+    channel_limits: watch::Sender<HashMap<u32, (usize, OversizedAction)>>,
+    // `EasyClientConfig::validate_utf8`, applied by every reader
+    validate_utf8: bool,
+}
+
+impl Default for Control {
+    fn default() -> Self {
+        Self {
+            epoch: Instant::now(),
+            next_request: AtomicU32::new(0),
+            pending_opens: Default::default(),
+            next_correlation: AtomicU64::new(0),
+            pending_replies: Default::default(),
+            last_rtt_micros: AtomicU64::new(0),
+            pongs_received: AtomicU64::new(0),
+            heartbeat_expired: AtomicBool::new(false),
+            next_sequence: Default::default(),
+            validate_utf8: false,
+            channel_limits: watch::channel(HashMap::new()).0,
+        }
+    }
+}
+
+/// First `stream_id` used as a correlation id by [`EasyClient::request`].
+///
+/// Far above any stamped QUIC stream index, so ordinary frames are never
+/// mistaken for replies, and below the sequence numbers of
+/// [`sequence_channel`](EasyClient::sequence_channel), which start at
+/// [`SEQUENCE_BASE`]. Avoid both ranges in [`EasyClient::send_with_stream_id`].
+pub const REQUEST_ID_BASE: u64 = 1 << 40;
+
+/// Lifetime counters updated by the actor tasks.
+#[derive(Default)]
+struct Counters {
+    frames_sent: AtomicU64,
+    bytes_sent: AtomicU64,
+    frames_received: AtomicU64,
+    bytes_received: AtomicU64,
+    panicked_handlers: AtomicU64,
+    undecodable_frames: AtomicU64,
+}
+
+/// Shutdown signalling and ownership of the actor tasks.
+struct Lifecycle {
+    started: Instant,
+    shutdown: watch::Sender<bool>,
+    tasks: std::sync::Mutex<Vec<JoinHandle<()>>>,
+}
+
+/// Lifetime statistics returned by [`EasyClient::shutdown`].
+#[derive(Debug, Clone)]
+pub struct SessionSummary {
+    pub duration: Duration,
+    pub frames_sent: u64,
+    pub bytes_sent: u64,
+    pub frames_received: u64,
+    pub bytes_received: u64,
+    pub panicked_handlers: u64,
+    /// Frames dropped because they could not be decoded for their handler.
+    pub undecodable_frames: u64,
+    /// `false` if the actors did not stop within [`SHUTDOWN_TIMEOUT`] and were aborted.
+    pub clean: bool,
+}
+
+/// Point-in-time connection metrics returned by [`EasyClient::stats`].
+///
+/// Frame and byte counts are Orzatty frames as counted by the actors; the
+/// rest is copied from quinn's connection statistics, so this type stays the
+/// same across quinn upgrades.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ConnectionStats {
+    pub frames_sent: u64,
+    pub bytes_sent: u64,
+    pub frames_received: u64,
+    pub bytes_received: u64,
+    /// Frames dropped because they could not be decoded for their handler.
+    pub undecodable_frames: u64,
+    /// UDP payload bytes sent, including QUIC overhead and retransmissions.
+    pub udp_bytes_sent: u64,
+    /// UDP payload bytes received, including QUIC overhead.
+    pub udp_bytes_received: u64,
+    /// QUIC's current smoothed RTT estimate.
+    pub rtt: Duration,
+    /// Congestion window in bytes.
+    pub cwnd: u64,
+    /// Times the congestion controller has backed off.
+    pub congestion_events: u64,
+    pub sent_packets: u64,
+    pub lost_packets: u64,
+    pub lost_bytes: u64,
+}
+
+/// How long [`EasyClient::shutdown`] waits for the actors to finish.
+pub const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long [`EasyClient::open_channel`] waits for the server to assign an id.
+pub const CONTROL_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Default for [`EasyClientConfig::ping_interval`].
+pub const PING_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Application close code the client sends when its heartbeat gives up on
+/// the server; see [`EasyClientConfig::max_missed_pongs`].
+pub const HEARTBEAT_TIMEOUT_CODE: u32 = 3;
+
+/// Close code, in both the `ControlMessage::Close` and the QUIC close, the
+/// client sends when a frame breaks a limit set with
+/// [`OversizedAction::Close`].
+pub const FRAME_TOO_LARGE_CODE: u32 = 4;
+
+/// Stream error code with which the client stops a stream the server opens
+/// beyond [`EasyClientConfig::max_accepted_streams`].
+pub const STREAM_REFUSED_CODE: u32 = 5;
+
+/// What [`EasyClient`] does with a received frame over its channel's limit;
+/// see [`EasyClient::set_channel_limit_with`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OversizedAction {
+    /// Discard the frame as it arrives, without buffering it.
+    Drop,
+    /// Stop reading, send the server a `ControlMessage::Close` with
+    /// [`FRAME_TOO_LARGE_CODE`] and close the connection.
+    Close,
+}
+
+/// Default for [`EasyClientConfig::outbound_capacity`].
+pub const DEFAULT_OUTBOUND_CAPACITY: usize = 64;
+
+/// Default for [`EasyClientConfig::max_batch_frames`].
+pub const DEFAULT_MAX_BATCH_FRAMES: usize = 32;
+
+/// Default for [`EasyClientConfig::max_batch_bytes`].
+pub const DEFAULT_MAX_BATCH_BYTES: usize = 64 * 1024;
+
+/// Default for [`EasyClientConfig::max_priority_burst`].
+pub const DEFAULT_MAX_PRIORITY_BURST: usize = 8;
+
+/// Default for [`EasyClientConfig::inbox_capacity`].
+pub const DEFAULT_INBOX_CAPACITY: usize = 256;
+
+/// Default for [`EasyClientConfig::max_accepted_streams`].
+pub const DEFAULT_MAX_ACCEPTED_STREAMS: usize = 16;
+
+/// How [`EasyClient`] maps channels onto QUIC streams.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StreamStrategy {
+    /// Every frame travels on the one session stream, in send order across
+    /// channels. A large message delays everything queued behind it.
+    #[default]
+    SingleStream,
+    /// Each application channel gets its own bidirectional stream, opened on
+    /// its first send, so a large message on one channel does not hold up the
+    /// others. Order is kept within a channel only. Control frames stay on
+    /// the session stream, and the server's replies come back on the stream
+    /// the request used.
+    StreamPerChannel,
+}
+
+/// Tuning for [`EasyClient::connect_with_config`].
+///
+/// The writer coalesces frames that are already queued when it wakes up into
+/// a single stream write. It never waits for a batch to fill, so a lone frame
+/// on an empty queue is written immediately.
+#[derive(Debug, Clone)]
+pub struct EasyClientConfig {
+    /// Depth of the Governor queue between `send` and the writer.
+    ///
+    /// Once it is full, `send().await` waits, which is how backpressure
+    /// reaches the app. A small queue pushes back almost at once and keeps
+    /// queueing delay low; a larger one absorbs bursts at the cost of frames
+    /// sitting longer before they hit the network. Must be at least 1.
+    pub outbound_capacity: usize,
+    /// Whether channels share the session stream or get their own.
+    pub stream_strategy: StreamStrategy,
+    /// Most frames per stream write; `1` disables batching.
+    pub max_batch_frames: usize,
+    /// No more frames are added to a batch once it holds this many bytes.
+    pub max_batch_bytes: usize,
+    /// Most priority frames the writer takes in a row while normal frames
+    /// are waiting, so a steady stream of
+    /// [`send_priority`](EasyClient::send_priority) calls cannot starve
+    /// [`send`](EasyClient::send). Must be at least 1.
+    pub max_priority_burst: usize,
+    /// Depth of the queue behind [`recv`](EasyClient::recv). Once it is
+    /// full, the reader waits for the app to catch up, which pushes back on
+    /// the server through QUIC flow control. Must be at least 1.
+    pub inbox_capacity: usize,
+    /// How often the client pings the server, for
+    /// [`last_rtt`](EasyClient::last_rtt) and the heartbeat. Must not be zero.
+    pub ping_interval: Duration,
+    /// Heartbeat: after this many pings in a row go unanswered, the client
+    /// closes the connection with [`HEARTBEAT_TIMEOUT_CODE`] and reports
+    /// [`DisconnectReason::HeartbeatTimeout`]. This catches a server whose
+    /// QUIC stack still acknowledges packets while the application behind it
+    /// has hung. A server answers within about `ping_interval`, so it is
+    /// declared dead after roughly `max_missed_pongs * ping_interval`.
+    /// `None`, the default, only measures the RTT. Must be at least 1.
+    pub max_missed_pongs: Option<u32>,
+    /// Hostname sent as SNI and checked against the server's certificate.
+    /// `None`, the default, uses the IP of the address passed to
+    /// [`connect`](EasyClient::connect), which then has to appear in the
+    /// certificate as an IP SAN. Set it when the server is reached by IP but
+    /// its certificate names a host.
+    pub server_name: Option<String>,
+    /// Most streams opened by the server that are read at once. Frames on
+    /// them go to the same handlers as those on the session stream; a
+    /// stream beyond the limit is stopped with [`STREAM_REFUSED_CODE`].
+    /// `0` refuses every one.
+    pub max_accepted_streams: usize,
+    /// Application-defined bytes sent to the server in the handshake, e.g. a
+    /// client version or device id. Empty by default.
+    pub metadata: Vec<u8>,
+    /// Checks every `Utf8Text` frame as it is read (see
+    /// [`FrameDecoder::with_validate_utf8`](orzatty_core::FrameDecoder::with_validate_utf8)).
+    /// One that is not valid UTF-8 is counted and dropped before it reaches a
+    /// handler or [`recv`](EasyClient::recv); the stream carries on. Off by
+    /// default, leaving the check to [`on_text`](EasyClient::on_text).
+    pub validate_utf8: bool,
+}
+
+impl EasyClientConfig {
+    /// Rejects settings the actors cannot run with.
+    fn validate(&self) -> Result<()> {
+        if self.outbound_capacity == 0 {
+            return Err(ClientError::Config("outbound_capacity must be at least 1".into()));
+        }
+        if self.max_priority_burst == 0 {
+            return Err(ClientError::Config("max_priority_burst must be at least 1".into()));
+        }
+        if self.inbox_capacity == 0 {
+            return Err(ClientError::Config("inbox_capacity must be at least 1".into()));
+        }
+        if self.ping_interval.is_zero() {
+            return Err(ClientError::Config("ping_interval must not be zero".into()));
+        }
+        if self.max_missed_pongs == Some(0) {
+            return Err(ClientError::Config("max_missed_pongs must be at least 1".into()));
+        }
+        Ok(())
+    }
+}
+
+impl Default for EasyClientConfig {
+    fn default() -> Self {
+        Self {
+            outbound_capacity: DEFAULT_OUTBOUND_CAPACITY,
+            stream_strategy: StreamStrategy::SingleStream,
+            max_batch_frames: DEFAULT_MAX_BATCH_FRAMES,
+            max_batch_bytes: DEFAULT_MAX_BATCH_BYTES,
+            max_priority_burst: DEFAULT_MAX_PRIORITY_BURST,
+            inbox_capacity: DEFAULT_INBOX_CAPACITY,
+            ping_interval: PING_INTERVAL,
+            max_missed_pongs: None,
+            server_name: None,
+            max_accepted_streams: DEFAULT_MAX_ACCEPTED_STREAMS,
+            metadata: Vec::new(),
+            validate_utf8: false,
+        }
+    }
+}
+
+/// Latest raw-byte digests of the session stream, published by the actors.
+#[cfg(feature = "stream-digest")]
+struct Digests {
+    sent: std::sync::Mutex<[u8; 32]>,
+    received: std::sync::Mutex<[u8; 32]>,
 }
 
 struct OutboundMessage {
     channel_id: u32,
+    // Session control message rather than application data
+    control: bool,
+    // Sent with `FrameFlags::PRIORITY`
+    priority: bool,
+    frame_type: FrameType,
+    // `None` = stamp the QUIC stream index (default behavior)
+    stream_id: Option<u64>,
     data: Vec<u8>,
+    // Flush marker: carries no frame, and is signalled once everything
+    // queued ahead of it has been written (see `EasyClient::flush`)
+    flushed: Option<oneshot::Sender<()>>,
+}
+
+/// A frame delivered to an [`EasyClient::on_frame`] handler or returned by
+/// [`EasyClient::recv`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReceivedFrame {
+    /// The header as received: type, flags, channel and `stream_id`.
+    pub header: FrameHeader,
+    /// The payload as read off the stream or datagram, without a copy.
+    /// Cloning it is cheap; holding on to it keeps its read buffer alive.
+    pub payload: Bytes,
+}
+
+impl ReceivedFrame {
+    /// The payload as an owned `Vec`, reusing its buffer when nothing else
+    /// holds a reference to it and copying otherwise.
+    pub fn into_vec(self) -> Vec<u8> {
+        self.payload.into()
+    }
 }
 
-type MsgCallback = Box<dyn Fn(Vec<u8>) + Send + Sync>;
+// Shared so the reader can clone a handler out and release the router lock
+// before running user code
+type FrameCallback = Arc<dyn Fn(ReceivedFrame) + Send + Sync>;
+type AnyCallback = Arc<dyn Fn(FrameHeader, Bytes) + Send + Sync>;
+type CloseCallback = Box<dyn Fn(u16, String) + Send + Sync>;
+type DisconnectCallback = Box<dyn Fn(DisconnectReason) + Send + Sync>;
+type GapCallback = Arc<dyn Fn(u32, SequenceCheck) + Send + Sync>;
+
+/// Why the session stream stopped, passed to [`EasyClient::on_disconnect`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DisconnectReason {
+    /// The stream ended without an error: the server finished it, or this
+    /// client shut down.
+    Clean,
+    /// The server announced a `Close` before the stream ended.
+    PeerClose { code: u16, reason: String },
+    /// The connection was lost or the server sent a malformed frame.
+    Error(ClientError),
+    /// The server stopped answering pings and the client gave up on it; see
+    /// [`EasyClientConfig::max_missed_pongs`].
+    HeartbeatTimeout,
+}
+
+/// How often [`EasyClient::on_datagram_size_change`] samples the datagram size limit.
+pub const DATAGRAM_SIZE_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// How often [`EasyClient::on_path_change`] samples the connection's addresses.
+pub const PATH_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The network path a connection runs over, as seen from the client.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NetworkPath {
+    /// Address of the local UDP socket; `None` if the OS cannot report it.
+    pub local: Option<SocketAddr>,
+    /// Local IP the server's packets last arrived on, where the platform
+    /// reports it (it tells interfaces apart behind a wildcard socket).
+    pub local_ip: Option<IpAddr>,
+    /// Address the server's packets come from.
+    pub remote: SocketAddr,
+}
 
 struct Router {
-    handlers: HashMap<u32, MsgCallback>,
-    default_handler: Option<MsgCallback>,
+    handlers: HashMap<u32, FrameCallback>,
+    default_handler: Option<AnyCallback>,
+    close_handler: Option<CloseCallback>,
+    disconnect_handler: Option<DisconnectCallback>,
+    // Incoming numbers on sequenced channels, and who to tell about anomalies
+    sequences: SequenceTracker,
+    gap_handler: Option<GapCallback>,
+    // Frames of a type this client does not know; dropped if unset
+    unknown_handler: Option<AnyCallback>,
+    // Feeds `recv`; set once it has been called, dropped when the reader stops
+    inbox: Option<mpsc::Sender<ReceivedFrame>>,
+    pulling: bool,
 }
 
 impl EasyClient {
     pub async fn connect(addr: &str, token: &str) -> Result<Self> {
-        let client = OrzattyClient::new().await?; 
-        
+        Self::connect_with_config(addr, token, EasyClientConfig::default()).await
+    }
+
+    /// Like [`connect`](Self::connect), with a custom queue depth, writer
+    /// tuning or server name.
+    pub async fn connect_with_config(addr: &str, token: &str, config: EasyClientConfig) -> Result<Self> {
+        Self::connect_with_client(&OrzattyClient::new().await?, addr, token, config).await
+    }
+
+    /// Like [`connect_with_config`](Self::connect_with_config), over a client
+    /// built with [`OrzattyClient::builder`], e.g. to trust a private CA or
+    /// set the idle timeout, ALPN or congestion controller.
+    ///
+    /// The client's endpoint is shared, so several `EasyClient`s can connect
+    /// through one socket. `config.metadata` is what is sent in the
+    /// handshake; the client's own [`with_metadata`](OrzattyClient::with_metadata)
+    /// only applies to [`OrzattyClient::connect`].
+    ///
+    /// ```ignore
+    /// let client = OrzattyClient::builder().add_root_cert(ca_pem).build().await?;
+    /// let easy = EasyClient::connect_with_client(&client, "10.0.0.5:5000", token, Default::default()).await?;
+    /// ```
+    pub async fn connect_with_client(client: &OrzattyClient, addr: &str, token: &str, config: EasyClientConfig) -> Result<Self> {
+        config.validate()?;
+        let socket_addr: SocketAddr = addr.parse()
+            .map_err(|_| ClientError::Config(format!("Invalid address format: {}", addr)))?;
+        let server_name = match &config.server_name {
+            Some(name) => name.clone(),
+            None => socket_addr.ip().to_string(),
+        };
+
+        let (connection, capabilities, session) = client.connect_negotiated(socket_addr, &server_name, token, &config.metadata, DEFAULT_CONNECT_TIMEOUT).await?;
+
+        Self::start(connection, client.endpoint().clone(), capabilities, session, config).await
+    }
+
+    /// First phase of a two-phase connect: completes the QUIC/TLS handshake only.
+    ///
+    /// Call [`UnauthenticatedClient::authenticate`] to log in and obtain the
+    /// `EasyClient`. In between, the raw connection is available (e.g. to fetch
+    /// a challenge), and UIs can tell "connecting" apart from "logging in".
+    pub async fn establish(addr: &str, server_name: &str) -> Result<UnauthenticatedClient> {
+        Self::establish_with_client(&OrzattyClient::new().await?, addr, server_name).await
+    }
+
+    /// Like [`establish`](Self::establish), over a client built with
+    /// [`OrzattyClient::builder`]; see [`connect_with_client`](Self::connect_with_client).
+    pub async fn establish_with_client(client: &OrzattyClient, addr: &str, server_name: &str) -> Result<UnauthenticatedClient> {
         let socket_addr = addr.parse()
-            .map_err(|_| anyhow!("Invalid address format"))?;
+            .map_err(|_| ClientError::Config(format!("Invalid address format: {}", addr)))?;
+
+        let (connection, zero_rtt) = client.establish_early(socket_addr, server_name, DEFAULT_CONNECT_TIMEOUT).await?;
 
-        let connection = client.connect(socket_addr, "localhost", token).await?;
+        Ok(UnauthenticatedClient { connection, endpoint: client.endpoint().clone(), zero_rtt })
+    }
 
+    /// Spawns the actors on an authenticated connection.
+    async fn start(connection: Connection, endpoint: Endpoint, capabilities: Capabilities, session: SessionInfo, config: EasyClientConfig) -> Result<Self> {
+        let (inbox_tx, inbox) = mpsc::channel(config.inbox_capacity);
         let router = Arc::new(Mutex::new(Router {
             handlers: HashMap::new(),
             default_handler: None,
+            close_handler: None,
+            disconnect_handler: None,
+            sequences: SequenceTracker::new(),
+            gap_handler: None,
+            unknown_handler: None,
+            inbox: Some(inbox_tx),
+            pulling: false,
         }));
 
         // Create the Governor Channel (Bounded for Backpressure)
-        // Tune: Capacity = 64. 
         // Small buffer = Instant backpressure. Large buffer = Latency spikes.
-        let (tx, rx) = mpsc::channel(64);
+        let (tx, rx) = mpsc::channel(config.outbound_capacity);
+        let (priority_tx, priority_rx) = mpsc::channel(config.outbound_capacity);
+        let lanes = Lanes::new(Some(priority_rx), rx, config.max_priority_burst);
 
         // Configure Transport (Hardening)
         // Handled in OrzattyClient::new() now.
         
+        let control = Arc::new(Control { validate_utf8: config.validate_utf8, ..Default::default() });
         let client = Self {
             connection: connection.clone(),
+            endpoint,
             router,
             tx,
+            priority_tx,
+            channel_streams: Arc::new(std::sync::Mutex::new(HashMap::new())),
+            config,
+            capabilities,
+            session: Arc::new(session),
+            channel_rates: Arc::new(std::sync::Mutex::new(HashMap::new())),
+            counters: Arc::new(Counters::default()),
+            control,
+            inbox: Arc::new(Mutex::new(inbox)),
+            lifecycle: Arc::new(Lifecycle {
+                started: Instant::now(),
+                shutdown: watch::channel(false).0,
+                tasks: std::sync::Mutex::new(Vec::new()),
+            }),
+            #[cfg(feature = "stream-digest")]
+            digests: Arc::new(Digests {
+                sent: std::sync::Mutex::new(StreamDigest::new().finalize()),
+                received: std::sync::Mutex::new(StreamDigest::new().finalize()),
+            }),
         };
 
         // Initialize streams and spawn the Actor tasks
-        client.init_system(rx).await?;
+        client.init_system(lanes).await?;
 
         Ok(client)
     }
 
-    async fn init_system(&self, rx: mpsc::Receiver<OutboundMessage>) -> Result<()> {
+    async fn init_system(&self, lanes: Lanes) -> Result<()> {
         // Open a Bi-directional stream for the session
         let (send_stream, recv_stream) = self.connection.open_bi().await?;
         
         // 1. Spawn the "Writer Actor" (The Governor)
         // This task owns the SendStream exclusively. Zero contention.
-        tokio::spawn(async move {
-            Self::writer_loop(send_stream, rx).await;
+        let counters = self.counters.clone();
+        let control = self.control.clone();
+        let shutdown = self.lifecycle.shutdown.subscribe();
+        let config = self.config.clone();
+        #[cfg(feature = "stream-digest")]
+        let digests = self.digests.clone();
+        let writer = self.spawn_actor("writer", None, async move {
+            Self::writer_loop(
+                send_stream,
+                lanes,
+                counters,
+                control,
+                shutdown,
+                config,
+                #[cfg(feature = "stream-digest")]
+                Some(digests),
+            ).await;
         });
 
         // 2. Spawn the "Reader Actor"
         // This task owns the RecvStream exclusively.
+        let connection = self.connection.clone();
         let router = self.router.clone();
-        tokio::spawn(async move {
-            Self::reader_loop(recv_stream, router).await;
+        let counters = self.counters.clone();
+        let control = self.control.clone();
+        let tx = self.priority_tx.clone();
+        let shutdown = self.lifecycle.shutdown.subscribe();
+        #[cfg(feature = "stream-digest")]
+        let digests = self.digests.clone();
+        let reader = self.spawn_actor("reader", None, async move {
+            let reason = Self::reader_loop(
+                recv_stream,
+                router.clone(),
+                counters.clone(),
+                control,
+                tx.clone(),
+                shutdown,
+                #[cfg(feature = "stream-digest")]
+                Some(digests),
+            ).await;
+            Self::close_if_oversized(&reason, &connection, &tx).await;
+            #[cfg(feature = "tracing")]
+            tracing::debug!(?reason, "session stream ended");
+            let mut router = router.lock().await;
+            // `recv` returns `None` once the frames already queued are taken
+            router.inbox = None;
+            if let Some(handler) = &router.disconnect_handler {
+                Self::dispatch(0, &counters, || handler(reason));
+            }
         });
 
+        // 3. Spawn the "Datagram Actor"
+        // Unreliable frames bypass the session stream and arrive here.
+        let connection = self.connection.clone();
+        let router = self.router.clone();
+        let counters = self.counters.clone();
+        let control = self.control.clone();
+        let shutdown = self.lifecycle.shutdown.subscribe();
+        let datagrams = self.spawn_actor("datagrams", None, async move {
+            Self::datagram_loop(connection, router, counters, control, shutdown).await;
+        });
+
+        // 4. Spawn the liveness pinger
+        // Pings ride the priority lane so queued data does not inflate the RTT
+        let connection = self.connection.clone();
+        let tx = self.priority_tx.clone();
+        let control = self.control.clone();
+        let shutdown = self.lifecycle.shutdown.subscribe();
+        let heartbeat = Heartbeat::new(self.config.max_missed_pongs);
+        let interval = self.config.ping_interval;
+        let pinger = self.spawn_actor("pinger", None, async move {
+            Self::ping_loop(connection, tx, control, interval, heartbeat, shutdown).await;
+        });
+
+        // 5. Spawn the "Accept Actor"
+        // Streams the server opens feed the same router as the session stream
+        let connection = self.connection.clone();
+        let router = self.router.clone();
+        let counters = self.counters.clone();
+        let control = self.control.clone();
+        let tx = self.priority_tx.clone();
+        let shutdown = self.lifecycle.shutdown.subscribe();
+        let max_streams = self.config.max_accepted_streams;
+        let acceptor = self.spawn_actor("accept", None, async move {
+            Self::accept_loop(connection, router, counters, control, tx, shutdown, max_streams).await;
+        });
+
+        self.lifecycle.tasks.lock().unwrap().extend([writer, reader, datagrams, pinger, acceptor]);
+
         Ok(())
     }
 
+    /// Spawns one of the connection's actor tasks. With the `tracing` feature
+    /// it runs in a span naming the connection, the actor and, for a
+    /// per-channel stream, the channel.
+    fn spawn_actor<T: Send + 'static>(
+        &self,
+        actor: &'static str,
+        channel_id: Option<u32>,
+        task: impl Future<Output = T> + Send + 'static,
+    ) -> JoinHandle<T> {
+        #[cfg(feature = "tracing")]
+        let task = tracing::Instrument::instrument(
+            task,
+            tracing::debug_span!("orzatty", connection_id = self.connection.stable_id(), actor, channel_id),
+        );
+        #[cfg(not(feature = "tracing"))]
+        let _ = (actor, channel_id);
+        tokio::spawn(task)
+    }
+
     /// The Writer Actor Loop
-    /// Drains the queue and writes to the network as fast as possible.
-    async fn writer_loop(mut stream: SendStream, mut rx: mpsc::Receiver<OutboundMessage>) {
-        // Optimization: We could implement batching here if needed (read N items, write once).
-        // For now, simple loop is already much faster than Mutex contention.
-        
-        // Reusable buffer for headers to avoid allocs
-        let mut head_buf = [0u8; 32];
+    /// Drains the queue and writes to the network as fast as possible,
+    /// one stream write per batch of already-queued frames.
+    async fn writer_loop(
+        mut stream: SendStream,
+        mut lanes: Lanes,
+        counters: Arc<Counters>,
+        control: Arc<Control>,
+        mut shutdown: watch::Receiver<bool>,
+        config: EasyClientConfig,
+        #[cfg(feature = "stream-digest")] digests: Option<Arc<Digests>>,
+    ) {
+        // Reusable batch buffer (headers + payloads) to avoid allocs
+        let mut frame_buf = BytesMut::with_capacity(1024);
+        let mut flushed = Vec::new();
+        let stream_index = stream.id().index();
+        #[cfg(feature = "stream-digest")]
+        let mut digest = StreamDigest::new();
 
-        while let Some(msg) = rx.recv().await {
-            let header = FrameHeader {
-                flags: FrameFlags::empty(),
-                frame_type: FrameType::RawBinary, 
-                channel_id: msg.channel_id,
-                stream_id: stream.id().index(), 
-                length: msg.data.len() as u64,
+        loop {
+            let msg = tokio::select! {
+                msg = lanes.next() => match msg {
+                    Some(msg) => msg,
+                    None => break, // All senders dropped
+                },
+                _ = shutdown.changed() => {
+                    // Stop accepting new messages but flush what is already queued
+                    lanes.close();
+                    match lanes.next().await {
+                        Some(msg) => msg,
+                        None => break,
+                    }
+                }
             };
 
-            if let Ok(h_len) = header.encode(&mut head_buf) {
+            #[cfg(feature = "stream-digest")]
+            let msg = match &digests {
+                Some(_) => Self::seal_close(msg, &digest),
+                None => msg,
+            };
+            frame_buf.clear();
+            let frames = Self::fill_batch(msg, &mut lanes, &mut frame_buf, &mut flushed, &control.next_sequence, stream_index, &config);
+            if frames > 0 {
                 // We ignore write errors here (if connection dies, loop will eventually exit)
-                if stream.write_all(&head_buf[..h_len]).await.is_err() { break; }
-                if stream.write_all(&msg.data).await.is_err() { break; }
+                if let Err(_e) = stream.write_all(&frame_buf).await {
+                    #[cfg(feature = "tracing")]
+                    tracing::debug!(error = %_e, "stream write failed");
+                    break;
+                }
+                #[cfg(feature = "tracing")]
+                tracing::trace!(frames, bytes = frame_buf.len(), "batch written");
+
+                counters.frames_sent.fetch_add(frames, Ordering::Relaxed);
+                counters.bytes_sent.fetch_add(frame_buf.len() as u64, Ordering::Relaxed);
+
+                #[cfg(feature = "stream-digest")]
+                {
+                    digest.update(&frame_buf);
+                    if let Some(digests) = &digests {
+                        *digests.sent.lock().unwrap() = digest.finalize();
+                    }
+                }
+            }
+            for marker in flushed.drain(..) {
+                let _ = marker.send(());
             }
         }
         // Channel closed or write error
         let _ = stream.finish().await;
     }
 
+    /// Fills in the digest of a `Close` that does not carry one yet with
+    /// `digest`, the bytes written ahead of it. Other messages pass through.
+    #[cfg(feature = "stream-digest")]
+    fn seal_close(mut msg: OutboundMessage, digest: &StreamDigest) -> OutboundMessage {
+        if !msg.control {
+            return msg;
+        }
+        let mut aligned = rkyv::AlignedVec::new();
+        aligned.extend_from_slice(&msg.data);
+        if let Ok(ControlMessage::Close { code, reason, digest: None }) = rkyv::from_bytes(&aligned) {
+            if let Ok(sealed) = Self::control_message(&ControlMessage::Close { code, reason, digest: Some(digest.finalize()) }) {
+                msg.data = sealed.data;
+            }
+        }
+        msg
+    }
+
+    /// Encodes `first` plus whatever is already queued, up to the batch
+    /// limits, into `buf`. Returns how many frames were encoded; flush markers
+    /// go to `flushed`, to be signalled once `buf` is written. Frames on
+    /// sequenced channels are numbered here, in the order they are written.
+    fn fill_batch(
+        first: OutboundMessage,
+        lanes: &mut Lanes,
+        buf: &mut BytesMut,
+        flushed: &mut Vec<oneshot::Sender<()>>,
+        sequences: &std::sync::Mutex<HashMap<u32, u64>>,
+        stream_index: u64,
+        config: &EasyClientConfig,
+    ) -> u64 {
+        let mut frames = 0;
+        let mut next = Some(first);
+        while let Some(msg) = next.take() {
+            if let Some(marker) = msg.flushed {
+                flushed.push(marker);
+                next = lanes.try_next();
+                continue;
+            }
+            let mut flags = if msg.control { FrameFlags::CONTROL } else { FrameFlags::empty() };
+            if msg.priority { flags |= FrameFlags::PRIORITY; }
+            let stream_id = match msg.stream_id {
+                Some(stream_id) => stream_id,
+                None if msg.control => stream_index,
+                None => Self::next_sequence(sequences, msg.channel_id).map_or(stream_index, sequence::to_stream_id),
+            };
+            let header = FrameHeader {
+                flags,
+                frame_type: msg.frame_type,
+                channel_id: msg.channel_id,
+                stream_id,
+                length: msg.data.len() as u64,
+            };
+            if header.encode_with_payload_bytes(&msg.data, buf).is_ok() {
+                #[cfg(feature = "tracing")]
+                tracing::trace!(channel_id = header.channel_id, frame_type = ?header.frame_type, len = msg.data.len(), "frame queued for write");
+                frames += 1;
+            }
+            // Only take what is already queued; never wait for a fuller batch
+            if frames < config.max_batch_frames as u64 && buf.len() < config.max_batch_bytes {
+                next = lanes.try_next();
+            }
+        }
+        frames
+    }
+
+    /// Takes the next number of a sequenced channel; `None` for other channels.
+    fn next_sequence(sequences: &std::sync::Mutex<HashMap<u32, u64>>, channel_id: u32) -> Option<u64> {
+        let mut sequences = sequences.lock().unwrap();
+        let next = sequences.get_mut(&channel_id)?;
+        let sequence = *next;
+        *next += 1;
+        Some(sequence)
+    }
+
     /// The Reader Actor Loop
-    async fn reader_loop(mut stream: QuicRecvStream, router: Arc<Mutex<Router>>) {
-        let mut framer = Framer::new();
+    /// Returns why it stopped reading.
+    async fn reader_loop(
+        mut stream: QuicRecvStream,
+        router: Arc<Mutex<Router>>,
+        counters: Arc<Counters>,
+        control: Arc<Control>,
+        tx: mpsc::Sender<OutboundMessage>,
+        mut shutdown: watch::Receiver<bool>,
+        #[cfg(feature = "stream-digest")] digests: Option<Arc<Digests>>,
+    ) -> DisconnectReason {
+        // A newer server's frame types are skipped rather than ending the stream
+        let mut framer = Framer::new().with_unknown_types();
+        if control.validate_utf8 {
+            framer = framer.with_validate_utf8();
+        }
+        let mut limits = control.channel_limits.subscribe();
+        let mut applied = HashMap::new();
+        Self::apply_limits(&mut framer, &mut applied, &limits.borrow_and_update());
+        // Set once the server announces a `Close`, so its end of stream is not
+        // reported as plain `Clean`
+        let mut peer_close = None;
         loop {
-            match framer.read_frame(&mut stream).await {
+            let frame = tokio::select! {
+                biased;
+                // New limits apply to the next header, even mid-read
+                Ok(()) = limits.changed() => {
+                    Self::apply_limits(&mut framer, &mut applied, &limits.borrow_and_update());
+                    continue;
+                }
+                _ = shutdown.changed() => return DisconnectReason::Clean,
+                frame = framer.read_frame(&mut stream) => frame,
+            };
+            match frame {
                 Ok(Some((header, payload))) => {
-                    let router = router.lock().await;
-                    if let Some(handler) = router.handlers.get(&header.channel_id) {
-                        (handler)(payload.to_vec());
-                    } else if let Some(default) = &router.default_handler {
-                        (default)(payload.to_vec());
+                    #[cfg(feature = "tracing")]
+                    tracing::trace!(channel_id = header.channel_id, frame_type = ?header.frame_type, len = payload.len(), "frame received");
+                    counters.frames_received.fetch_add(1, Ordering::Relaxed);
+                    counters.bytes_received.fetch_add(payload.len() as u64, Ordering::Relaxed);
+                    #[cfg(feature = "stream-digest")]
+                    {
+                        if let Some(digests) = &digests {
+                            *digests.received.lock().unwrap() = framer.stream_digest();
+                        }
+                    }
+                    if header.frame_type == FrameType::Unknown {
+                        let payload = payload.freeze();
+                        Self::route_unknown(&router, &counters, header, payload.clone()).await;
+                        if let Ok(payload) = payload.try_into_mut() {
+                            framer.recycle(payload);
+                        }
+                        continue;
                     }
+                    if header.flags.contains(FrameFlags::CONTROL) {
+                        let closed = Self::handle_control(&payload, &control, &tx);
+                        framer.recycle(payload);
+                        if let Some((code, reason)) = closed {
+                            let router = router.lock().await;
+                            if let Some(handler) = &router.close_handler {
+                                Self::dispatch(0, &counters, || handler(code, reason.clone()));
+                            }
+                            peer_close = Some(DisconnectReason::PeerClose { code, reason });
+                        }
+                        continue;
+                    }
+                    // Replies to `request` go to the waiting caller, not the handlers
+                    if (REQUEST_ID_BASE..SEQUENCE_BASE).contains(&header.stream_id) {
+                        let waiter = control.pending_replies.lock().unwrap().remove(&header.stream_id);
+                        if let Some(reply) = waiter {
+                            let _ = reply.send(payload.freeze());
+                            continue;
+                        }
+                    }
+                    let payload = payload.freeze();
+                    Self::route(&router, &counters, header, payload.clone()).await;
+                    // Reusable unless a handler kept the payload
+                    if let Ok(payload) = payload.try_into_mut() {
+                        framer.recycle(payload);
+                    }
+                }
+                // The peer's close usually tears the connection down with it
+                Ok(None) => return peer_close.unwrap_or(DisconnectReason::Clean),
+                Err(_) if control.heartbeat_expired.load(Ordering::Relaxed) => return DisconnectReason::HeartbeatTimeout,
+                Err(e) => {
+                    // Only the bad text frame is lost; the decoder has moved past it
+                    if let Some(&orzatty_core::Error::InvalidUtf8 { channel_id, .. }) = e.downcast_ref() {
+                        Self::report_undecodable(channel_id, &counters, e);
+                        continue;
+                    }
+                    return peer_close.unwrap_or_else(|| DisconnectReason::Error(ClientError::from_read(e)));
+                }
+            }
+        }
+    }
+
+    /// The Accept Actor Loop
+    /// Reads each stream the server opens, bidirectional or not, with its own
+    /// reader, `max_streams` at a time. Nothing is written back on them.
+    async fn accept_loop(
+        connection: Connection,
+        router: Arc<Mutex<Router>>,
+        counters: Arc<Counters>,
+        control: Arc<Control>,
+        tx: mpsc::Sender<OutboundMessage>,
+        mut shutdown: watch::Receiver<bool>,
+        max_streams: usize,
+    ) {
+        let readers = Arc::new(Semaphore::new(max_streams));
+        loop {
+            let mut stream = tokio::select! {
+                // Dropping the send half finishes it
+                stream = connection.accept_bi() => match stream {
+                    Ok((_send_stream, recv_stream)) => recv_stream,
+                    Err(_) => break, // Connection closed
+                },
+                stream = connection.accept_uni() => match stream {
+                    Ok(recv_stream) => recv_stream,
+                    Err(_) => break,
+                },
+                _ = shutdown.changed() => break,
+            };
+            let Ok(permit) = readers.clone().try_acquire_owned() else {
+                #[cfg(feature = "tracing")]
+                tracing::debug!(max_streams, "server stream refused");
+                let _ = stream.stop(quinn::VarInt::from_u32(STREAM_REFUSED_CODE));
+                continue;
+            };
+            let (connection, router, counters, control, tx, shutdown) =
+                (connection.clone(), router.clone(), counters.clone(), control.clone(), tx.clone(), shutdown.clone());
+            tokio::spawn(async move {
+                let reason = Self::reader_loop(
+                    stream,
+                    router,
+                    counters,
+                    control,
+                    tx.clone(),
+                    shutdown,
+                    #[cfg(feature = "stream-digest")]
+                    None,
+                ).await;
+                Self::close_if_oversized(&reason, &connection, &tx).await;
+                drop(permit);
+            });
+        }
+    }
+
+    /// Brings the framer's channel limits in line with `limits`, given the
+    /// ones `applied` so far.
+    fn apply_limits(
+        framer: &mut Framer,
+        applied: &mut HashMap<u32, (usize, OversizedAction)>,
+        limits: &HashMap<u32, (usize, OversizedAction)>,
+    ) {
+        for channel_id in applied.keys().filter(|id| !limits.contains_key(id)) {
+            framer.clear_channel_limit(*channel_id);
+        }
+        for (&channel_id, &(max_bytes, action)) in limits {
+            let action = match action {
+                OversizedAction::Drop => LimitAction::Skip,
+                OversizedAction::Close => LimitAction::Fail,
+            };
+            framer.set_channel_limit(channel_id, max_bytes, action);
+        }
+        applied.clone_from(limits);
+    }
+
+    /// If a reader stopped on a frame over an [`OversizedAction::Close`]
+    /// limit, tells the server why and closes the connection.
+    async fn close_if_oversized(reason: &DisconnectReason, connection: &Connection, tx: &mpsc::Sender<OutboundMessage>) {
+        let DisconnectReason::Error(ClientError::Protocol(orzatty_core::Error::ChannelFrameTooLarge { channel_id, .. })) = *reason else {
+            return;
+        };
+        let why = format!("frame too large for channel {}", channel_id);
+        #[cfg(feature = "tracing")]
+        tracing::warn!(channel_id, "oversized frame, closing");
+        let close = ControlMessage::Close { code: FRAME_TOO_LARGE_CODE as u16, reason: why.clone(), digest: None };
+        if let Ok(close) = Self::control_message(&close) {
+            let (marker, written) = oneshot::channel();
+            let flushed = OutboundMessage {
+                channel_id: 0,
+                control: false,
+                priority: false,
+                frame_type: FrameType::RawBinary,
+                stream_id: None,
+                data: Vec::new(),
+                flushed: Some(marker),
+            };
+            // Give the writer a moment to put the Close on the wire first
+            let _ = tokio::time::timeout(CONTROL_TIMEOUT, async {
+                tx.send(close).await.ok()?;
+                tx.send(flushed).await.ok()?;
+                written.await.ok()
+            }).await;
+        }
+        connection.close(quinn::VarInt::from_u32(FRAME_TOO_LARGE_CODE), why.as_bytes());
+    }
+
+    /// The Datagram Actor Loop
+    /// Each datagram carries exactly one frame; malformed ones are dropped.
+    async fn datagram_loop(
+        connection: Connection,
+        router: Arc<Mutex<Router>>,
+        counters: Arc<Counters>,
+        control: Arc<Control>,
+        mut shutdown: watch::Receiver<bool>,
+    ) {
+        loop {
+            let datagram = tokio::select! {
+                datagram = connection.read_datagram() => match datagram {
+                    Ok(datagram) => datagram,
+                    Err(_) => break, // Connection closed
+                },
+                _ = shutdown.changed() => break,
+            };
+            let Ok((mut header, head_len)) = FrameHeader::decode_lenient(&datagram) else { continue };
+            let body = &datagram[head_len..];
+            if header.length != body.len() as u64 {
+                continue;
+            }
+            let Ok(payload) = header.strip_checksum(body) else { continue };
+            // Over a channel limit: dropped whatever the action, the datagram being read already
+            let limit = control.channel_limits.borrow().get(&header.channel_id).map(|&(max, _)| max);
+            if limit.is_some_and(|max| payload.len() > max) {
+                continue;
+            }
+            header.length = payload.len() as u64;
+            counters.frames_received.fetch_add(1, Ordering::Relaxed);
+            counters.bytes_received.fetch_add(payload.len() as u64, Ordering::Relaxed);
+            let payload = datagram.slice_ref(payload);
+            if header.frame_type == FrameType::Unknown {
+                Self::route_unknown(&router, &counters, header, payload).await;
+            } else {
+                Self::route(&router, &counters, header, payload).await;
+            }
+        }
+    }
+
+    /// Hands a frame of a type this client does not know to the
+    /// [`on_unknown`](Self::on_unknown) handler, or drops it.
+    async fn route_unknown(router: &Mutex<Router>, counters: &Counters, header: FrameHeader, payload: Bytes) {
+        #[cfg(feature = "tracing")]
+        tracing::debug!(channel_id = header.channel_id, len = payload.len(), "frame of unknown type skipped");
+        let handler = router.lock().await.unknown_handler.clone();
+        if let Some(handler) = handler {
+            Self::dispatch(header.channel_id, counters, || handler(header, payload));
+        }
+    }
+
+    /// Hands an application frame to its channel handler, or to the fallback.
+    async fn route(router: &Mutex<Router>, counters: &Counters, header: FrameHeader, payload: Bytes) {
+        let (handler, fallback, inbox, gap) = {
+            let mut router = router.lock().await;
+            // Only numbered ids are checked; replies and explicit ids are not
+            let check = router.sequences.observe_stream_id(header.channel_id, header.stream_id);
+            let gap = match check {
+                None | Some(SequenceCheck::InOrder) => None,
+                Some(check) => router.gap_handler.clone().map(|handler| (handler, check)),
+            };
+            match (router.handlers.get(&header.channel_id), &router.default_handler) {
+                (Some(handler), _) => (Some(handler.clone()), None, None, gap),
+                (None, Some(fallback)) => (None, Some(fallback.clone()), None, gap),
+                (None, None) => (None, None, router.inbox.clone().filter(|_| router.pulling), gap),
+            }
+        };
+        if let Some((on_gap, check)) = gap {
+            Self::dispatch(header.channel_id, counters, || on_gap(header.channel_id, check));
+        }
+        if let Some(handler) = handler {
+            Self::dispatch(header.channel_id, counters, || handler(ReceivedFrame { header, payload }));
+        } else if let Some(handler) = fallback {
+            Self::dispatch(header.channel_id, counters, || handler(header, payload));
+        } else if let Some(inbox) = inbox {
+            // Waiting here holds up the reader until the app calls `recv` again
+            let _ = inbox.send(ReceivedFrame { header, payload }).await;
+        }
+    }
+
+    /// Sends a `Ping` every `interval`, starting right away, and closes the
+    /// connection once `heartbeat` gives up on the server.
+    async fn ping_loop(
+        connection: Connection,
+        tx: mpsc::Sender<OutboundMessage>,
+        control: Arc<Control>,
+        interval: Duration,
+        mut heartbeat: Heartbeat,
+        mut shutdown: watch::Receiver<bool>,
+    ) {
+        let mut tick = tokio::time::interval(interval);
+        loop {
+            tokio::select! {
+                _ = tick.tick() => {}
+                _ = shutdown.changed() => break,
+            }
+            if heartbeat.expired(control.pongs_received.load(Ordering::Relaxed)) {
+                #[cfg(feature = "tracing")]
+                tracing::warn!("server stopped answering pings, closing");
+                control.heartbeat_expired.store(true, Ordering::Relaxed);
+                connection.close(quinn::VarInt::from_u32(HEARTBEAT_TIMEOUT_CODE), b"heartbeat timeout");
+                break;
+            }
+            let ping = ControlMessage::Ping { ts: control.epoch.elapsed().as_micros() as u64 };
+            let Ok(msg) = Self::control_message(&ping) else { break };
+            // A hung server stops reading, which backs the queue up; a ping
+            // that cannot be queued counts as missed rather than stalling here
+            match tx.try_send(msg) {
+                Ok(()) | Err(mpsc::error::TrySendError::Full(_)) => {}
+                Err(mpsc::error::TrySendError::Closed(_)) => break,
+            }
+        }
+    }
+
+    /// Applies a control message from the server. Malformed ones are ignored.
+    ///
+    /// Returns the code and reason if the server announced it is closing.
+    fn handle_control(payload: &[u8], control: &Control, tx: &mpsc::Sender<OutboundMessage>) -> Option<(u16, String)> {
+        // Copy into an aligned buffer: the payload may sit at any offset
+        let mut aligned = rkyv::AlignedVec::new();
+        aligned.extend_from_slice(payload);
+        match rkyv::from_bytes(&aligned) {
+            Ok(ControlMessage::ChannelOpened { request_id, channel_id }) => {
+                if let Some(reply) = control.pending_opens.lock().unwrap().remove(&request_id) {
+                    let _ = reply.send(channel_id);
                 }
-                Ok(None) => break, // Stream closed
-                Err(_) => break, // Error
             }
+            Ok(ControlMessage::Ping { ts }) => {
+                // Never block the reader on a full queue; the peer pings again
+                if let Ok(pong) = Self::control_message(&ControlMessage::Pong { ts }) {
+                    let _ = tx.try_send(pong);
+                }
+            }
+            Ok(ControlMessage::Pong { ts }) => {
+                let rtt = (control.epoch.elapsed().as_micros() as u64).saturating_sub(ts);
+                control.last_rtt_micros.store(rtt.max(1), Ordering::Relaxed);
+                control.pongs_received.fetch_add(1, Ordering::Relaxed);
+            }
+            Ok(ControlMessage::Close { code, reason, .. }) => return Some((code, reason)),
+            _ => {}
+        }
+        None
+    }
+
+    /// Round-trip time measured by the most recent ping, or `None` before
+    /// the first pong arrives. Pings go out every
+    /// [`ping_interval`](EasyClientConfig::ping_interval) on the
+    /// session stream, so this includes any queueing behind application frames.
+    pub fn last_rtt(&self) -> Option<Duration> {
+        match self.control.last_rtt_micros.load(Ordering::Relaxed) {
+            0 => None,
+            micros => Some(Duration::from_micros(micros)),
+        }
+    }
+
+    /// Features agreed with the server during the handshake: those both
+    /// sides support. Calls that need a missing one fail instead of sending.
+    pub fn capabilities(&self) -> Capabilities {
+        self.capabilities
+    }
+
+    /// What the server told this client about its session in the handshake:
+    /// the identity it authenticated as, the server's id for the connection
+    /// and any metadata the server attached.
+    pub fn session_info(&self) -> &SessionInfo {
+        &self.session
+    }
+
+    /// A snapshot of traffic, RTT, congestion window and loss counters, e.g.
+    /// for exporting as metrics. Cheap enough to call on every scrape.
+    pub fn stats(&self) -> ConnectionStats {
+        let quic = self.connection.stats();
+        let c = &self.counters;
+        ConnectionStats {
+            frames_sent: c.frames_sent.load(Ordering::Relaxed),
+            bytes_sent: c.bytes_sent.load(Ordering::Relaxed),
+            frames_received: c.frames_received.load(Ordering::Relaxed),
+            bytes_received: c.bytes_received.load(Ordering::Relaxed),
+            undecodable_frames: c.undecodable_frames.load(Ordering::Relaxed),
+            udp_bytes_sent: quic.udp_tx.bytes,
+            udp_bytes_received: quic.udp_rx.bytes,
+            rtt: self.connection.rtt(),
+            cwnd: quic.path.cwnd,
+            congestion_events: quic.path.congestion_events,
+            sent_packets: quic.path.sent_packets,
+            lost_packets: quic.path.lost_packets,
+            lost_bytes: quic.path.lost_bytes,
+        }
+    }
+
+    /// A handle on `channel_id`, so the id is not repeated on every call.
+    /// See [`channel`](crate::channel) for how channel and stream ids differ.
+    pub fn channel(&self, channel_id: u32) -> Channel {
+        Channel::new(self.clone(), channel_id)
+    }
+
+    /// Asks the server for a fresh channel id.
+    ///
+    /// Server-assigned ids start at [`DYNAMIC_CHANNEL_BASE`](orzatty_core::control::DYNAMIC_CHANNEL_BASE),
+    /// so they never collide with hardcoded ones or with ids handed to other
+    /// parts of the app. Register a handler with [`on`](Self::on) and release
+    /// the id with [`close_channel`](Self::close_channel) when done.
+    pub async fn open_channel(&self) -> Result<u32> {
+        let request_id = self.control.next_request.fetch_add(1, Ordering::Relaxed);
+        let (reply_tx, reply_rx) = oneshot::channel();
+        self.control.pending_opens.lock().unwrap().insert(request_id, reply_tx);
+
+        let assigned = async {
+            self.send_control(&ControlMessage::OpenChannel { request_id }).await?;
+            reply_rx.await.map_err(|_| ClientError::ConnectionClosed("Connection closed before channel was assigned".into()))
+        };
+        let result = tokio::time::timeout(CONTROL_TIMEOUT, assigned).await;
+        self.control.pending_opens.lock().unwrap().remove(&request_id);
+        result.map_err(|_| ClientError::RequestTimeout(CONTROL_TIMEOUT))?
+    }
+
+    /// Releases a channel id obtained from [`open_channel`](Self::open_channel)
+    /// and drops its handler. The server may hand the id out again.
+    pub async fn close_channel(&self, channel_id: u32) -> Result<()> {
+        self.router.lock().await.handlers.remove(&channel_id);
+        // Dropping the queue lets its writer drain and finish the stream
+        self.channel_streams.lock().unwrap().remove(&channel_id);
+        self.send_control(&ControlMessage::CloseChannel { channel_id }).await
+    }
+
+    async fn send_control(&self, msg: &ControlMessage) -> Result<()> {
+        // Closes queue behind the data sent before them; the rest jump ahead
+        let lane = match msg {
+            ControlMessage::Close { .. } | ControlMessage::CloseChannel { .. } => &self.tx,
+            _ => &self.priority_tx,
+        };
+        lane.send(Self::control_message(msg)?).await
+            .map_err(|_| ClientError::ConnectionClosed("Governor dropped message".into()))
+    }
+
+    fn control_message(msg: &ControlMessage) -> Result<OutboundMessage> {
+        let data = rkyv::to_bytes::<_, 64>(msg)
+            .map_err(|e| ClientError::InvalidMessage(format!("Failed to serialize control message: {:?}", e)))?;
+        Ok(OutboundMessage {
+            channel_id: 0,
+            control: true,
+            priority: false,
+            frame_type: FrameType::RkyvAligned,
+            stream_id: Some(0),
+            data: data.to_vec(),
+            flushed: None,
+        })
+    }
+
+    /// Shuts the client down in order and returns its lifetime statistics.
+    ///
+    /// 1. The writer stops accepting messages, flushes what is already queued
+    ///    and finishes the session stream.
+    /// 2. The reader stops dispatching.
+    /// 3. Both actor tasks are awaited for up to [`SHUTDOWN_TIMEOUT`], then aborted.
+    /// 4. The QUIC connection is closed with the normal (`0`) close code.
+    ///
+    /// With the `tracing` feature, the summary is also logged as an `info`
+    /// event. Affects every clone of this client; sends after shutdown fail.
+    pub async fn shutdown(&self) -> SessionSummary {
+        self.stop(0, b"shutdown").await
+    }
+
+    /// Closes the session on purpose, telling the server why.
+    ///
+    /// Sends a `ControlMessage::Close` as the last frame on the session
+    /// stream, then shuts down like [`shutdown`](Self::shutdown) (flushing the
+    /// queue and finishing the stream) but closes the QUIC connection with
+    /// `code` and `reason`. The server can thus tell an intentional close from
+    /// a crash.
+    ///
+    /// With the `stream-digest` feature, the `Close` also carries
+    /// [`sent_digest`](Self::sent_digest) as of the frame before it, for the
+    /// server to check against what it read.
+    pub async fn close(self, code: u16, reason: &str) -> Result<SessionSummary> {
+        // The writer seals a `Close` that starts a batch; one queued behind
+        // unwritten frames would be folded into their batch and go unsealed
+        #[cfg(feature = "stream-digest")]
+        self.flush().await?;
+        self.send_control(&ControlMessage::Close { code, reason: reason.to_string(), digest: None }).await?;
+        Ok(self.stop(code.into(), reason.as_bytes()).await)
+    }
+
+    /// Registers a callback for a `Close` announced by the server.
+    ///
+    /// It receives the server's code and reason. A connection that drops
+    /// without one (a crash or network loss) does not trigger it.
+    pub async fn on_close(&self, callback: impl Fn(u16, String) + Send + Sync + 'static) {
+        self.router.lock().await.close_handler = Some(Box::new(callback));
+    }
+
+    /// Numbers the frames sent on `channel_id` and checks the numbers on
+    /// frames received on it, reporting gaps and late frames to
+    /// [`on_sequence_gap`](Self::on_sequence_gap).
+    ///
+    /// The number travels in the `stream_id` header field, from
+    /// [`SEQUENCE_BASE`] up (see [`orzatty_core::sequence`]), so the peer must
+    /// treat the channel as sequenced too; a server echoing frames with their
+    /// header qualifies. Numbers are taken as frames leave the client (when
+    /// written to their stream, or once `send_datagram` hands one to QUIC),
+    /// not when queued. Frames sent with [`request`](Self::request) or
+    /// [`send_with_stream_id`](Self::send_with_stream_id) keep their own
+    /// `stream_id`; they are not numbered, and not checked when received.
+    pub async fn sequence_channel(&self, channel_id: u32) {
+        self.control.next_sequence.lock().unwrap().entry(channel_id).or_insert(0);
+        self.router.lock().await.sequences.track(channel_id);
+    }
+
+    /// Registers a callback for frames that arrive out of sequence on a
+    /// channel set up with [`sequence_channel`](Self::sequence_channel). It
+    /// receives the channel id and what was wrong; the frame itself is still
+    /// delivered to the channel's handler afterwards.
+    pub async fn on_sequence_gap(&self, callback: impl Fn(u32, SequenceCheck) + Send + Sync + 'static) {
+        self.router.lock().await.gap_handler = Some(Arc::new(callback));
+    }
+
+    /// Registers a callback for frames whose type this client does not know,
+    /// e.g. an extension added by a newer server. Their header reads
+    /// [`FrameType::Unknown`]. They never reach the channel handlers, control
+    /// handling or [`recv`](Self::recv); without this callback they are
+    /// dropped.
+    pub async fn on_unknown(&self, callback: impl Fn(FrameHeader, Bytes) + Send + Sync + 'static) {
+        self.router.lock().await.unknown_handler = Some(Arc::new(callback));
+    }
+
+    /// Registers a callback for the end of the session stream, e.g. to decide
+    /// whether to reconnect.
+    ///
+    /// Fires once, when the reader stops, with the [`DisconnectReason`]:
+    /// `Clean` after [`shutdown`](Self::shutdown) or an orderly end of stream,
+    /// `PeerClose` after a `Close` from the server (see [`on_close`](Self::on_close)),
+    /// `HeartbeatTimeout` once the server stops answering pings, and `Error`
+    /// on network loss or a malformed frame.
+    pub async fn on_disconnect(&self, callback: impl Fn(DisconnectReason) + Send + Sync + 'static) {
+        self.router.lock().await.disconnect_handler = Some(Box::new(callback));
+    }
+
+    async fn stop(&self, code: u32, reason: &[u8]) -> SessionSummary {
+        let _ = self.lifecycle.shutdown.send(true);
+
+        let tasks: Vec<_> = std::mem::take(&mut *self.lifecycle.tasks.lock().unwrap());
+        let aborts: Vec<_> = tasks.iter().map(|t| t.abort_handle()).collect();
+        let clean = tokio::time::timeout(SHUTDOWN_TIMEOUT, async {
+            for task in tasks {
+                let _ = task.await;
+            }
+        }).await.is_ok();
+        if !clean {
+            aborts.iter().for_each(|a| a.abort());
+        }
+
+        self.connection.close(quinn::VarInt::from_u32(code), reason);
+
+        let c = &self.counters;
+        let summary = SessionSummary {
+            duration: self.lifecycle.started.elapsed(),
+            frames_sent: c.frames_sent.load(Ordering::Relaxed),
+            bytes_sent: c.bytes_sent.load(Ordering::Relaxed),
+            frames_received: c.frames_received.load(Ordering::Relaxed),
+            bytes_received: c.bytes_received.load(Ordering::Relaxed),
+            panicked_handlers: c.panicked_handlers.load(Ordering::Relaxed),
+            undecodable_frames: c.undecodable_frames.load(Ordering::Relaxed),
+            clean,
+        };
+        #[cfg(feature = "tracing")]
+        tracing::info!(
+            duration = ?summary.duration,
+            frames_sent = summary.frames_sent,
+            bytes_sent = summary.bytes_sent,
+            frames_received = summary.frames_received,
+            bytes_received = summary.bytes_received,
+            panicked_handlers = summary.panicked_handlers,
+            undecodable_frames = summary.undecodable_frames,
+            clean = summary.clean,
+            "session ended",
+        );
+        summary
+    }
+
+    /// Runs a user handler, isolating the reader actor from panics.
+    ///
+    /// A panicking handler is logged and counted; the reader keeps serving
+    /// subsequent frames on every channel.
+    fn dispatch(channel_id: u32, counters: &Counters, call: impl FnOnce()) {
+        if let Err(cause) = panic::catch_unwind(AssertUnwindSafe(call)) {
+            Self::report_panic(channel_id, counters, cause);
+        }
+    }
+
+    fn report_panic(channel_id: u32, counters: &Counters, cause: Box<dyn Any + Send>) {
+        counters.panicked_handlers.fetch_add(1, Ordering::Relaxed);
+        #[cfg(feature = "tracing")]
+        {
+            let msg = cause.downcast_ref::<&str>().copied()
+                .or_else(|| cause.downcast_ref::<String>().map(String::as_str))
+                .unwrap_or("<non-string panic>");
+            tracing::error!(channel_id, panic = msg, "handler panicked");
+        }
+        #[cfg(not(feature = "tracing"))]
+        let _ = (channel_id, cause);
+    }
+
+    /// Counts a frame dropped because it could not be decoded for its handler.
+    fn report_undecodable(channel_id: u32, counters: &Counters, error: impl std::fmt::Display) {
+        counters.undecodable_frames.fetch_add(1, Ordering::Relaxed);
+        #[cfg(feature = "tracing")]
+        tracing::warn!(channel_id, %error, "dropping undecodable frame");
+        #[cfg(not(feature = "tracing"))]
+        let _ = (channel_id, error);
+    }
+
+    /// The underlying QUIC connection, as an escape hatch.
+    ///
+    /// Use it to open additional streams or datagrams alongside the managed
+    /// ones, or to reach quinn-specific APIs. Caveats:
+    /// - The session bi-stream is owned by the writer/reader actors; do not
+    ///   try to interact with it.
+    /// - Streams you open yourself are not framed, routed or flow-controlled
+    ///   by the Governor.
+    /// - Closing the connection ends this `EasyClient` as well.
+    pub fn connection(&self) -> &Connection {
+        &self.connection
+    }
+
+    /// Current maximum datagram payload size, or `None` if datagrams are
+    /// unsupported by the peer or disabled.
+    pub fn max_datagram_size(&self) -> Option<usize> {
+        self.connection.max_datagram_size()
+    }
+
+    /// Invokes `callback` whenever the maximum datagram size changes.
+    ///
+    /// Path MTU can shrink mid-connection (e.g. after a network change), so
+    /// payloads that used to fit may start being rejected. Apps sending
+    /// datagrams should re-chunk when the limit drops. The size is polled
+    /// every [`DATAGRAM_SIZE_POLL_INTERVAL`]; polling stops when the
+    /// connection closes. Each registered callback runs independently.
+    pub fn on_datagram_size_change(&self, callback: impl Fn(Option<usize>) + Send + 'static) {
+        let connection = self.connection.clone();
+        tokio::spawn(async move {
+            let mut last = connection.max_datagram_size();
+            let mut tick = tokio::time::interval(DATAGRAM_SIZE_POLL_INTERVAL);
+            while connection.close_reason().is_none() {
+                tick.tick().await;
+                let current = connection.max_datagram_size();
+                if current != last {
+                    last = current;
+                    callback(current);
+                }
+            }
+        });
+    }
+
+    /// The path the connection currently runs over.
+    pub fn path(&self) -> NetworkPath {
+        Self::path_of(&self.connection, &self.endpoint)
+    }
+
+    /// The ALPN protocol agreed with the server; `None` if none was offered
+    /// (see [`OrzattyClientBuilder::alpn_protocols`](crate::OrzattyClientBuilder::alpn_protocols)).
+    pub fn alpn_protocol(&self) -> Option<Vec<u8>> {
+        self.connection.handshake_data()?.downcast::<quinn::crypto::rustls::HandshakeData>().ok()?.protocol
+    }
+
+    fn path_of(connection: &Connection, endpoint: &Endpoint) -> NetworkPath {
+        NetworkPath {
+            local: endpoint.local_addr().ok(),
+            local_ip: connection.local_ip(),
+            remote: connection.remote_address(),
         }
     }
 
-    pub async fn on(&self, channel_id: u32, callback: impl Fn(Vec<u8>) + Send + Sync + 'static) {
+    /// Invokes `callback` with the new [`NetworkPath`] whenever the
+    /// connection moves to another one: after [`rebind`](Self::rebind), when
+    /// packets start arriving on another local interface, or when the server
+    /// migrates.
+    ///
+    /// A ping goes out as soon as the change is seen, so
+    /// [`last_rtt`](Self::last_rtt) reflects the new path within a round
+    /// trip. The path is polled every [`PATH_POLL_INTERVAL`]; polling stops
+    /// when the connection closes. Each registered callback runs independently.
+    pub fn on_path_change(&self, callback: impl Fn(NetworkPath) + Send + 'static) {
+        let connection = self.connection.clone();
+        let endpoint = self.endpoint.clone();
+        let control = self.control.clone();
+        let tx = self.priority_tx.clone();
+        // Taken now, so a `rebind` right after this call counts as a change
+        let mut last = self.path();
+        tokio::spawn(async move {
+            let mut tick = tokio::time::interval(PATH_POLL_INTERVAL);
+            while connection.close_reason().is_none() {
+                tick.tick().await;
+                let current = Self::path_of(&connection, &endpoint);
+                if current != last {
+                    last = current;
+                    let ping = ControlMessage::Ping { ts: control.epoch.elapsed().as_micros() as u64 };
+                    if let Ok(ping) = Self::control_message(&ping) {
+                        let _ = tx.try_send(ping);
+                    }
+                    callback(current);
+                }
+            }
+        });
+    }
+
+    /// Moves the connection onto a new UDP socket bound to `addr` (port `0`
+    /// for any), e.g. once the app learns that Wi-Fi dropped and cellular
+    /// took over.
+    ///
+    /// QUIC migrates the connection without a new handshake: the session
+    /// stream, the writer and everything queued carry on, and packets lost
+    /// on the old path are retransmitted on the new one. The server sees the
+    /// client's new address once its first packet arrives.
+    pub fn rebind(&self, addr: SocketAddr) -> Result<()> {
+        let socket = std::net::UdpSocket::bind(addr)?;
+        self.endpoint.rebind(socket)?;
+        Ok(())
+    }
+
+    /// Number of handler invocations that panicked since the client connected.
+    pub fn panicked_handlers(&self) -> u64 {
+        self.counters.panicked_handlers.load(Ordering::Relaxed)
+    }
+
+    /// Number of frames dropped since the client connected because they could
+    /// not be decoded: invalid UTF-8 text, or payloads a typed handler such as
+    /// [`on_typed`](Self::on_typed) rejected.
+    pub fn undecodable_frames(&self) -> u64 {
+        self.counters.undecodable_frames.load(Ordering::Relaxed)
+    }
+
+    /// Registers the handler for `channel_id`, receiving only the payload.
+    /// Shorthand for [`on_frame`](Self::on_frame) when the header is not needed.
+    ///
+    /// The payload refers to the buffer it was read into, so large frames
+    /// reach the handler without a copy.
+    pub async fn on(&self, channel_id: u32, callback: impl Fn(Bytes) + Send + Sync + 'static) {
+        self.on_frame(channel_id, move |frame| callback(frame.payload)).await;
+    }
+
+    /// Like [`on`](Self::on), for handlers that need to own the payload as a
+    /// `Vec`; see [`ReceivedFrame::into_vec`].
+    pub async fn on_owned(&self, channel_id: u32, callback: impl Fn(Vec<u8>) + Send + Sync + 'static) {
+        self.on_frame(channel_id, move |frame| callback(frame.into_vec())).await;
+    }
+
+    /// Like [`on`](Self::on), for text channels: the handler receives the
+    /// payload as a `String`. Frames that are not valid UTF-8 are counted and
+    /// dropped before reaching it, whatever their frame type.
+    pub async fn on_text(&self, channel_id: u32, callback: impl Fn(String) + Send + Sync + 'static) {
+        let counters = self.counters.clone();
+        self.on_frame(channel_id, move |frame| match String::from_utf8(frame.into_vec()) {
+            Ok(text) => callback(text),
+            Err(e) => Self::report_undecodable(channel_id, &counters, e),
+        }).await;
+    }
+
+    /// Registers the handler for `channel_id`, receiving each frame with its
+    /// header, so it can branch on `frame_type` (e.g. text and binary on one
+    /// channel), flags such as `PRIORITY` or `DATAGRAM`, and `stream_id`.
+    /// Replaces any handler set for the channel by `on` or its variants.
+    ///
+    /// Handlers run on the reader task, which reads no further frames until
+    /// they return; use [`on_async`](Self::on_async) for slow work.
+    pub async fn on_frame(&self, channel_id: u32, callback: impl Fn(ReceivedFrame) + Send + Sync + 'static) {
         let mut router = self.router.lock().await;
-        router.handlers.insert(channel_id, Box::new(callback));
+        router.handlers.insert(channel_id, Arc::new(callback));
+    }
+
+    /// Registers an async handler for `channel_id`.
+    ///
+    /// Each frame's future is spawned onto the runtime, so a slow handler
+    /// (database write, HTTP call) never holds up the reader or other
+    /// channels. The flip side: futures run concurrently, so frames on the
+    /// channel may be processed out of order. Panics are logged and counted
+    /// like those of sync handlers. Replaces any handler set for the channel.
+    pub async fn on_async<F, Fut>(&self, channel_id: u32, callback: F)
+    where
+        F: Fn(Bytes) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let counters = self.counters.clone();
+        self.on(channel_id, move |payload| {
+            let task = AssertUnwindSafe(callback(payload)).catch_unwind();
+            let counters = counters.clone();
+            tokio::spawn(async move {
+                if let Err(cause) = task.await {
+                    Self::report_panic(channel_id, &counters, cause);
+                }
+            });
+        }).await;
+    }
+
+    /// Like [`on`](Self::on), but validates and deserializes each payload as a `T`.
+    ///
+    /// Payloads that fail validation are counted and dropped; the reader keeps
+    /// going. To handle such errors yourself, register with `on` and call
+    /// [`typed::decode`] in the callback.
+    pub async fn on_typed<T>(&self, channel_id: u32, callback: impl Fn(T) + Send + Sync + 'static)
+    where
+        T: Archive + 'static,
+        T::Archived: for<'a> CheckBytes<DefaultValidator<'a>> + Deserialize<T, SharedDeserializeMap>,
+    {
+        let counters = self.counters.clone();
+        self.on(channel_id, move |payload| match typed::decode::<T>(&payload) {
+            Ok(value) => callback(value),
+            Err(e) => Self::report_undecodable(channel_id, &counters, e),
+        }).await;
+    }
+
+    /// Registers a handler that parses each payload on `channel_id` as JSON
+    /// into `T`. Frames that fail to parse are counted and dropped.
+    #[cfg(feature = "serde")]
+    pub async fn on_serde<T>(&self, channel_id: u32, callback: impl Fn(T) + Send + Sync + 'static)
+    where
+        T: serde::de::DeserializeOwned + 'static,
+    {
+        let counters = self.counters.clone();
+        self.on(channel_id, move |payload| match crate::json::decode::<T>(&payload) {
+            Ok(value) => callback(value),
+            Err(e) => Self::report_undecodable(channel_id, &counters, e),
+        }).await;
+    }
+
+    /// Registers a handler that decodes each payload on `channel_id` as the
+    /// protobuf message `T`. Frames that fail to decode are counted and
+    /// dropped.
+    #[cfg(feature = "prost")]
+    pub async fn on_proto<T>(&self, channel_id: u32, callback: impl Fn(T) + Send + Sync + 'static)
+    where
+        T: prost::Message + Default + 'static,
+    {
+        let counters = self.counters.clone();
+        self.on(channel_id, move |payload| match crate::proto::decode::<T>(&payload) {
+            Ok(value) => callback(value),
+            Err(e) => Self::report_undecodable(channel_id, &counters, e),
+        }).await;
     }
 
-    pub async fn on_any(&self, callback: impl Fn(Vec<u8>) + Send + Sync + 'static) {
+    /// Like [`on_typed`](Self::on_typed), but hands the callback the archived
+    /// `T` in place instead of deserializing it (see [`typed::access_archived`]).
+    ///
+    /// The reference points into the frame's payload and is only valid during
+    /// the call; copy out what must outlive it. Payloads are realigned (one
+    /// copy) only when they arrive misaligned for `T::Archived`. Payloads that
+    /// fail validation are counted and dropped.
+    pub async fn on_archived<T>(&self, channel_id: u32, callback: impl Fn(&T::Archived) + Send + Sync + 'static)
+    where
+        T: Archive + 'static,
+        T::Archived: for<'a> CheckBytes<DefaultValidator<'a>>,
+    {
+        let counters = self.counters.clone();
+        self.on(channel_id, move |payload| {
+            let realigned;
+            let bytes = if payload.as_ptr().align_offset(std::mem::align_of::<T::Archived>()) == 0 {
+                &payload[..]
+            } else {
+                let mut aligned = rkyv::AlignedVec::with_capacity(payload.len());
+                aligned.extend_from_slice(&payload);
+                realigned = aligned;
+                &realigned[..]
+            };
+            match typed::access_archived::<T>(bytes) {
+                Ok(archived) => callback(archived),
+                Err(e) => Self::report_undecodable(channel_id, &counters, e),
+            }
+        }).await;
+    }
+
+    /// Registers the fallback for channels without an [`on`](Self::on) handler.
+    pub async fn on_any(&self, callback: impl Fn(Bytes) + Send + Sync + 'static) {
+        self.on_any_ctx(move |_, payload| callback(payload)).await;
+    }
+
+    /// Like [`on_any`](Self::on_any), but the fallback also receives the frame
+    /// header, so it can tell which channel, stream and flags the frame had.
+    /// Replaces any fallback set by either method.
+    pub async fn on_any_ctx(&self, callback: impl Fn(FrameHeader, Bytes) + Send + Sync + 'static) {
         let mut router = self.router.lock().await;
-        router.default_handler = Some(Box::new(callback));
+        router.default_handler = Some(Arc::new(callback));
+    }
+
+    /// Waits for the next frame that no handler takes: one on a channel
+    /// without an [`on`](Self::on) handler, while no [`on_any`](Self::on_any)
+    /// fallback is set. A pull-based alternative to callbacks; the two mix
+    /// freely, e.g. handlers for a few hot channels and `recv` for the rest.
+    ///
+    /// Frames are queued for `recv` only from its first call on; until then
+    /// unhandled frames are dropped as before. Returns `None` once the session
+    /// stream has ended and every frame queued before that has been returned.
+    /// Clones share the queue, so each frame goes to one caller.
+    pub async fn recv(&self) -> Option<ReceivedFrame> {
+        self.router.lock().await.pulling = true;
+        self.inbox.lock().await.recv().await
     }
 
+    /// The frames [`recv`](Self::recv) would return, as a
+    /// [`Stream`](futures::Stream) for `futures` and tokio combinators:
+    ///
+    /// ```ignore
+    /// let mut chat = client.incoming().filter(|frame| future::ready(frame.header.channel_id == 1));
+    /// tokio::select! {
+    ///     Some(frame) = chat.next() => println!("{:?}", frame.payload),
+    ///     _ = shutdown.recv() => {}
+    /// }
+    /// ```
+    ///
+    /// Handlers come first: a frame goes to its channel's [`on`](Self::on)
+    /// handler or the [`on_any`](Self::on_any) fallback if there is one, and
+    /// only otherwise to the stream. Frames are queued from the first poll on
+    /// and shared with `recv` and other `incoming` streams, each frame going
+    /// to one of them. The stream ends with the session stream.
+    pub fn incoming(&self) -> impl futures::Stream<Item = ReceivedFrame> + Send + Unpin + 'static {
+        Box::pin(futures::stream::unfold(self.clone(), |client| async move {
+            let frame = client.recv().await?;
+            Some((frame, client))
+        }))
+    }
+
+    /// BLAKE3 digest of every byte the writer actor has put on the session stream.
+    ///
+    /// Once both sides are done, this should equal the peer's `received_digest`.
+    #[cfg(feature = "stream-digest")]
+    pub fn sent_digest(&self) -> [u8; 32] {
+        *self.digests.sent.lock().unwrap()
+    }
+
+    /// BLAKE3 digest of every byte read from the session stream, as of the last complete frame.
+    #[cfg(feature = "stream-digest")]
+    pub fn received_digest(&self) -> [u8; 32] {
+        *self.digests.received.lock().unwrap()
+    }
+
+    /// Sends `data` on `channel_id`.
+    ///
+    /// The frame's `stream_id` is stamped with the index of the underlying QUIC
+    /// stream. Use [`send_with_stream_id`](Self::send_with_stream_id) to carry an
+    /// application-defined value instead.
     pub async fn send(&self, channel_id: u32, data: &[u8]) -> Result<()> {
+        self.enqueue(channel_id, FrameType::RawBinary, None, data).await
+    }
+
+    /// Sends `text` on `channel_id` as a `FrameType::Utf8Text` frame. Pair
+    /// with [`on_text`](Self::on_text).
+    pub async fn send_text(&self, channel_id: u32, text: &str) -> Result<()> {
+        self.enqueue(channel_id, FrameType::Utf8Text, None, text.as_bytes()).await
+    }
+
+    /// Queues `data` on `channel_id` without waiting.
+    ///
+    /// Where [`send`](Self::send) awaits a free queue slot (and the channel's
+    /// rate budget), this fails at once with [`TrySendError::Full`] or
+    /// [`TrySendError::RateLimited`], so a game loop can drop a non-critical
+    /// update under congestion instead of stalling the frame.
+    pub fn try_send(&self, channel_id: u32, data: &[u8]) -> std::result::Result<(), TrySendError> {
+        // Held until the frame is queued, so the token is only spent on success
+        let mut rates = self.channel_rates.lock().unwrap();
+        let mut bucket = rates.get_mut(&channel_id);
+        if bucket.as_mut().is_some_and(|bucket| !bucket.available()) {
+            return Err(TrySendError::RateLimited);
+        }
+        let tx = self.outbound_for(channel_id).map_err(|_| TrySendError::Closed)?;
+        tx.try_send(OutboundMessage {
+            channel_id,
+            control: false,
+            priority: false,
+            frame_type: FrameType::RawBinary,
+            stream_id: None,
+            data: data.to_vec(),
+            flushed: None,
+        }).map_err(|e| match e {
+            mpsc::error::TrySendError::Full(_) => TrySendError::Full,
+            mpsc::error::TrySendError::Closed(_) => TrySendError::Closed,
+        })?;
+        if let Some(bucket) = bucket {
+            bucket.take();
+        }
+        Ok(())
+    }
+
+    /// Sends `data` on `channel_id` ahead of frames queued with [`send`](Self::send).
+    ///
+    /// The frame goes on the session stream's priority lane and carries
+    /// `FrameFlags::PRIORITY`. The writer drains that lane first, but takes a
+    /// normal frame after every [`EasyClientConfig::max_priority_burst`]
+    /// priority ones, so ordinary traffic still moves. Order is kept among
+    /// priority frames, not between the two lanes. Always uses the session
+    /// stream, even under [`StreamStrategy::StreamPerChannel`].
+    pub async fn send_priority(&self, channel_id: u32, data: &[u8]) -> Result<()> {
+        self.throttle(channel_id).await;
+        self.priority_tx.send(OutboundMessage {
+            channel_id,
+            control: false,
+            priority: true,
+            frame_type: FrameType::RawBinary,
+            stream_id: None,
+            data: data.to_vec(),
+            flushed: None,
+        }).await.map_err(|_| ClientError::ConnectionClosed("Governor dropped message".into()))
+    }
+
+    /// Frames queued for the writer actors but not yet written, across the
+    /// session queues and any per-channel queues.
+    ///
+    /// Approaching [`EasyClientConfig::outbound_capacity`] means `send` is
+    /// about to block: the network is not keeping up.
+    pub fn pending_outbound(&self) -> usize {
+        let queued = |tx: &mpsc::Sender<OutboundMessage>| tx.max_capacity() - tx.capacity();
+        queued(&self.tx) + queued(&self.priority_tx) + self.channel_streams.lock().unwrap().values().map(queued).sum::<usize>()
+    }
+
+    /// Waits until every frame queued before the call has been written to its
+    /// QUIC stream, across the session queues and any per-channel queues.
+    ///
+    /// `send` only queues; flush before dropping the client or handing the
+    /// connection to other code so nothing is left behind. Written frames are
+    /// then QUIC's to deliver, and it retransmits them until acknowledged or
+    /// the connection is lost. [`shutdown`](Self::shutdown) and
+    /// [`close`](Self::close) drain the queues themselves. Fails if the client
+    /// shut down or the connection dropped first.
+    pub async fn flush(&self) -> Result<()> {
+        let mut queues = vec![self.priority_tx.clone(), self.tx.clone()];
+        queues.extend(self.channel_streams.lock().unwrap().values().cloned());
+
+        let mut markers = Vec::with_capacity(queues.len());
+        for tx in queues {
+            let (marker, written) = oneshot::channel();
+            tx.send(OutboundMessage {
+                channel_id: 0,
+                control: false,
+                priority: false,
+                frame_type: FrameType::RawBinary,
+                stream_id: None,
+                data: Vec::new(),
+                flushed: Some(marker),
+            }).await.map_err(|_| ClientError::ConnectionClosed("Governor dropped message".into()))?;
+            markers.push(written);
+        }
+        for written in markers {
+            written.await.map_err(|_| ClientError::ConnectionClosed("Connection closed before the queue was flushed".into()))?;
+        }
+        Ok(())
+    }
+
+    /// Serializes `value` with rkyv and sends it on `channel_id` as a
+    /// `FrameType::RkyvAligned` frame. Pair with [`on_typed`](Self::on_typed).
+    pub async fn send_typed<T>(&self, channel_id: u32, value: &T) -> Result<()>
+    where
+        T: Serialize<AllocSerializer<256>>,
+    {
+        let bytes = typed::encode(value)?;
+        self.enqueue(channel_id, FrameType::RkyvAligned, None, &bytes).await
+    }
+
+    /// Serializes `value` with serde_json and sends it on `channel_id` as a
+    /// `FrameType::Json` frame, readable by non-Rust peers. Pair with
+    /// [`on_serde`](Self::on_serde).
+    #[cfg(feature = "serde")]
+    pub async fn send_serde<T: serde::Serialize>(&self, channel_id: u32, value: &T) -> Result<()> {
+        let bytes = crate::json::encode(value)?;
+        self.enqueue(channel_id, FrameType::Json, None, &bytes).await
+    }
+
+    /// Encodes `value` as a protobuf message and sends it on `channel_id` as
+    /// a `FrameType::Protobuf` frame. Pair with [`on_proto`](Self::on_proto).
+    #[cfg(feature = "prost")]
+    pub async fn send_proto<T: prost::Message>(&self, channel_id: u32, value: &T) -> Result<()> {
+        let bytes = crate::proto::encode(value);
+        self.enqueue(channel_id, FrameType::Protobuf, None, &bytes).await
+    }
+
+    /// Sends `data` on `channel_id` with a caller-chosen `stream_id` header field.
+    ///
+    /// At the protocol level `stream_id` is an opaque logical id; it does not
+    /// select or correspond to a QUIC transport stream. All frames still travel
+    /// on the session stream owned by the writer actor. Ids from
+    /// [`REQUEST_ID_BASE`] up are read as reply correlation ids or sequence
+    /// numbers by the receiving end.
+    pub async fn send_with_stream_id(&self, channel_id: u32, stream_id: u64, data: &[u8]) -> Result<()> {
+        self.enqueue(channel_id, FrameType::RawBinary, Some(stream_id), data).await
+    }
+
+    /// Sends `data` on `channel_id` and waits up to `timeout` for the reply.
+    ///
+    /// The frame's `stream_id` carries a fresh correlation id (from
+    /// [`REQUEST_ID_BASE`] up); the server must answer with a frame carrying
+    /// the same `stream_id`. The reply is delivered here instead of to the
+    /// channel handlers. On expiry a [`ClientError::RequestTimeout`] is
+    /// returned and a late reply is routed to the handlers as usual.
+    pub async fn request(&self, channel_id: u32, data: &[u8], timeout: Duration) -> Result<Bytes> {
+        let id = REQUEST_ID_BASE + self.control.next_correlation.fetch_add(1, Ordering::Relaxed);
+        let (reply_tx, reply_rx) = oneshot::channel();
+        self.control.pending_replies.lock().unwrap().insert(id, reply_tx);
+
+        let exchange = async {
+            self.enqueue(channel_id, FrameType::RawBinary, Some(id), data).await?;
+            reply_rx.await.map_err(|_| ClientError::ConnectionClosed("Connection closed before reply".into()))
+        };
+        let result = tokio::time::timeout(timeout, exchange).await;
+        self.control.pending_replies.lock().unwrap().remove(&id);
+        result.map_err(|_| ClientError::RequestTimeout(timeout))?
+    }
+
+    /// Sends `data` on `channel_id` as an unreliable QUIC datagram.
+    ///
+    /// The frame is flagged [`FrameFlags::DATAGRAM`] and bypasses the Governor
+    /// queue and the session stream, so it may be lost or reordered: use it for
+    /// state that is quickly superseded, like position updates. Incoming
+    /// datagram frames go to the same handlers as stream frames. Fails if
+    /// [`Capabilities::DATAGRAMS`] was not negotiated, the peer does not
+    /// accept datagrams or the frame exceeds
+    /// [`max_datagram_size`](Self::max_datagram_size), the latter with
+    /// [`ClientError::DatagramTooLarge`] before the channel's rate budget is
+    /// charged.
+    pub async fn send_datagram(&self, channel_id: u32, data: &[u8]) -> Result<()> {
+        if !self.capabilities.contains(Capabilities::DATAGRAMS) {
+            return Err(ClientError::InvalidMessage("Datagrams were not negotiated with the server".into()));
+        }
+
+        let build = |stream_id: Option<u64>| {
+            let frame = FrameBuilder::new(data).channel(channel_id).datagram();
+            stream_id.map_or(frame, |id| frame.stream(id)).to_bytes()
+        };
+        // Numbered ids all take 8 bytes, so the size is known before a number is taken
+        let sequenced = self.control.next_sequence.lock().unwrap().contains_key(&channel_id);
+        let frame = build(sequenced.then_some(SEQUENCE_BASE))?;
+        if let Some(max) = self.connection.max_datagram_size() {
+            if frame.len() > max {
+                return Err(ClientError::DatagramTooLarge { size: frame.len(), max });
+            }
+        }
+        self.throttle(channel_id).await;
+
+        // Held until the datagram is handed to QUIC, so only one that was sent uses up a number
+        let mut sequences = self.control.next_sequence.lock().unwrap();
+        let next = sequences.get_mut(&channel_id);
+        let frame = match &next {
+            Some(next) => build(Some(sequence::to_stream_id(**next)))?,
+            None => frame,
+        };
+        let len = frame.len() as u64;
+        self.connection.send_datagram(frame.into()).map_err(|e| match e {
+            quinn::SendDatagramError::ConnectionLost(e) => ClientError::from(e),
+            // Too large, or datagrams are disabled or unsupported by the peer
+            e => ClientError::InvalidMessage(e.to_string()),
+        })?;
+        if let Some(next) = next {
+            *next += 1;
+        }
+        drop(sequences);
+
+        self.counters.frames_sent.fetch_add(1, Ordering::Relaxed);
+        self.counters.bytes_sent.fetch_add(len, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Caps how fast `channel_id` may emit frames, client-side.
+    ///
+    /// Sends beyond the budget are delayed (a burst of up to one second's worth
+    /// passes immediately), so a runaway loop on one channel cannot starve the
+    /// others in the Governor queue. This is self-regulation only; the server
+    /// may enforce its own limits. A `frames_per_sec` of 0 is taken as 1;
+    /// to stop a channel, stop sending on it.
+    pub fn set_channel_rate(&self, channel_id: u32, frames_per_sec: u32) {
+        self.channel_rates.lock().unwrap().insert(channel_id, TokenBucket::new(frames_per_sec));
+    }
+
+    /// Removes a limit set with [`set_channel_rate`](Self::set_channel_rate).
+    pub fn clear_channel_rate(&self, channel_id: u32) {
+        self.channel_rates.lock().unwrap().remove(&channel_id);
+    }
+
+    /// Caps the payload of frames received on `channel_id` at `max_bytes`.
+    /// Larger ones are discarded by the reader as they arrive, their size
+    /// known from the header, so none of the payload is buffered. A
+    /// fragmented message counts at its reassembled size. The cap is set
+    /// per direction: it does not limit what this client sends.
+    ///
+    /// Finer-grained than the global frame size limit, e.g. a few hundred
+    /// bytes for a control channel next to megabytes for file transfer.
+    /// Datagrams over the cap are dropped too.
+    pub fn set_channel_limit(&self, channel_id: u32, max_bytes: usize) {
+        self.set_channel_limit_with(channel_id, max_bytes, OversizedAction::Drop);
+    }
+
+    /// Like [`set_channel_limit`](Self::set_channel_limit), choosing what
+    /// happens to an oversized stream frame: dropped, or the session closed
+    /// with [`FRAME_TOO_LARGE_CODE`] and reported to
+    /// [`on_disconnect`](Self::on_disconnect) as a
+    /// [`ChannelFrameTooLarge`](orzatty_core::Error::ChannelFrameTooLarge)
+    /// protocol error.
+    pub fn set_channel_limit_with(&self, channel_id: u32, max_bytes: usize, action: OversizedAction) {
+        self.control.channel_limits.send_modify(|limits| {
+            limits.insert(channel_id, (max_bytes, action));
+        });
+    }
+
+    /// Removes a cap set with [`set_channel_limit`](Self::set_channel_limit).
+    pub fn clear_channel_limit(&self, channel_id: u32) {
+        self.control.channel_limits.send_modify(|limits| {
+            limits.remove(&channel_id);
+        });
+    }
+
+    async fn enqueue(&self, channel_id: u32, frame_type: FrameType, stream_id: Option<u64>, data: &[u8]) -> Result<()> {
+        // Honor the channel budget before taking a Governor slot
+        self.throttle(channel_id).await;
+
         // Send to the Governor channel.
         // If channel is full, this `.send().await` will pause (Backpressure).
         // This prevents the app from overwhelming the network buffer.
-        self.tx.send(OutboundMessage {
+        self.outbound_for(channel_id)?.send(OutboundMessage {
             channel_id,
+            control: false,
+            priority: false,
+            frame_type,
+            stream_id,
             data: data.to_vec(),
-        }).await.map_err(|_| anyhow!("Connection closed (Governor dropped message)"))?;
+            flushed: None,
+        }).await.map_err(|_| ClientError::ConnectionClosed("Governor dropped message".into()))?;
         
         Ok(())
     }
+
+    /// The queue feeding `channel_id`: the Governor channel, or under
+    /// [`StreamStrategy::StreamPerChannel`] the channel's own queue, whose
+    /// stream and actors are started on first use.
+    fn outbound_for(&self, channel_id: u32) -> Result<mpsc::Sender<OutboundMessage>> {
+        if self.config.stream_strategy == StreamStrategy::SingleStream {
+            return Ok(self.tx.clone());
+        }
+        let mut streams = self.channel_streams.lock().unwrap();
+        if let Some(tx) = streams.get(&channel_id) {
+            return Ok(tx.clone());
+        }
+        if *self.lifecycle.shutdown.borrow() {
+            return Err(ClientError::ConnectionClosed("Client is shut down".into()));
+        }
+
+        let (tx, rx) = mpsc::channel(self.config.outbound_capacity);
+        let connection = self.connection.clone();
+        let router = self.router.clone();
+        let counters = self.counters.clone();
+        let control = self.control.clone();
+        let session_tx = self.priority_tx.clone();
+        let shutdown = self.lifecycle.shutdown.subscribe();
+        let config = self.config.clone();
+        let task = self.spawn_actor("channel_stream", Some(channel_id), async move {
+            let Ok((send_stream, recv_stream)) = connection.open_bi().await else { return };
+            // Replies to frames sent here come back on the same stream
+            let reader = async {
+                let reason = Self::reader_loop(
+                    recv_stream,
+                    router,
+                    counters.clone(),
+                    control.clone(),
+                    session_tx.clone(),
+                    shutdown.clone(),
+                    #[cfg(feature = "stream-digest")]
+                    None,
+                ).await;
+                Self::close_if_oversized(&reason, &connection, &session_tx).await;
+            };
+            tokio::join!(
+                Self::writer_loop(
+                    send_stream,
+                    Lanes::new(None, rx, config.max_priority_burst),
+                    counters.clone(),
+                    control.clone(),
+                    shutdown.clone(),
+                    config,
+                    #[cfg(feature = "stream-digest")]
+                    None,
+                ),
+                reader,
+            );
+        });
+        self.lifecycle.tasks.lock().unwrap().push(task);
+        streams.insert(channel_id, tx.clone());
+        Ok(tx)
+    }
+
+    /// Waits out the budget set with [`set_channel_rate`](Self::set_channel_rate), if any.
+    async fn throttle(&self, channel_id: u32) {
+        let delay = self.channel_rates.lock().unwrap()
+            .get_mut(&channel_id)
+            .map(|bucket| bucket.reserve());
+        if let Some(delay) = delay.filter(|d| !d.is_zero()) {
+            tokio::time::sleep(delay).await;
+        }
+    }
+}
+
+/// A connection whose QUIC/TLS handshake completed but which has not authenticated yet.
+///
+/// Obtained from [`EasyClient::establish`]. Dropping it closes the connection.
+pub struct UnauthenticatedClient {
+    connection: Connection,
+    endpoint: Endpoint,
+    // Set when the connection was resumed with 0-RTT data
+    zero_rtt: Option<quinn::ZeroRttAccepted>,
+}
+
+impl UnauthenticatedClient {
+    /// The raw connection, e.g. for a pre-auth exchange on a stream of your own.
+    pub fn connection(&self) -> &Connection {
+        &self.connection
+    }
+
+    /// Runs the auth handshake and starts the managed client with the
+    /// default [`EasyClientConfig`].
+    ///
+    /// On failure the connection is closed.
+    pub async fn authenticate(self, token: &str) -> Result<EasyClient> {
+        self.authenticate_with_config(token, None, EasyClientConfig::default()).await
+    }
+
+    /// Like [`authenticate`](Self::authenticate), answering a server
+    /// `AuthMessage::Challenge` with an HMAC proof keyed by `secret`.
+    pub async fn authenticate_with_secret(self, token: &str, secret: &[u8]) -> Result<EasyClient> {
+        self.authenticate_with_config(token, Some(secret), EasyClientConfig::default()).await
+    }
+
+    /// Like [`authenticate`](Self::authenticate), sending `config.metadata`
+    /// in the handshake and starting the client with `config`, as
+    /// [`EasyClient::connect_with_config`] does. `secret`, if given, answers
+    /// a challenge as in [`authenticate_with_secret`](Self::authenticate_with_secret).
+    /// `config.server_name` is not used: the TLS handshake is already done.
+    pub async fn authenticate_with_config(self, token: &str, secret: Option<&[u8]>, config: EasyClientConfig) -> Result<EasyClient> {
+        config.validate()?;
+        let auth = OrzattyClient::authenticate(&self.connection, token, &config.metadata, secret, DEFAULT_AUTH_ATTEMPTS, DEFAULT_AUTH_TIMEOUT, self.zero_rtt);
+        match tokio::time::timeout(DEFAULT_CONNECT_TIMEOUT, auth).await {
+            Ok(Ok((capabilities, session))) => EasyClient::start(self.connection, self.endpoint, capabilities, session, config).await,
+            Ok(Err(e)) => {
+                self.connection.close(quinn::VarInt::from_u32(0), b"auth failed");
+                Err(e)
+            }
+            Err(_) => {
+                self.connection.close(quinn::VarInt::from_u32(0), b"auth timeout");
+                Err(ClientError::ConnectTimeout(DEFAULT_CONNECT_TIMEOUT))
+            }
+        }
+    }
+}
+
+/// The queues one writer drains: an optional priority lane served ahead of
+/// the normal one, but never for more than `max_burst` frames in a row while
+/// normal frames wait.
+struct Lanes {
+    priority: Option<mpsc::Receiver<OutboundMessage>>,
+    normal: mpsc::Receiver<OutboundMessage>,
+    // Priority frames taken since the last normal one
+    burst: usize,
+    max_burst: usize,
+}
+
+impl Lanes {
+    fn new(priority: Option<mpsc::Receiver<OutboundMessage>>, normal: mpsc::Receiver<OutboundMessage>, max_burst: usize) -> Self {
+        Self { priority, normal, burst: 0, max_burst }
+    }
+
+    /// The next frame already queued, if any.
+    fn try_next(&mut self) -> Option<OutboundMessage> {
+        if self.burst < self.max_burst {
+            if let Some(msg) = self.priority.as_mut().and_then(|rx| rx.try_recv().ok()) {
+                self.burst += 1;
+                return Some(msg);
+            }
+        }
+        if let Ok(msg) = self.normal.try_recv() {
+            self.burst = 0;
+            return Some(msg);
+        }
+        // Burst spent but nothing normal is waiting
+        let msg = self.priority.as_mut()?.try_recv().ok()?;
+        self.burst += 1;
+        Some(msg)
+    }
+
+    /// Waits for the next frame; `None` once the normal lane is closed and
+    /// both lanes are empty.
+    async fn next(&mut self) -> Option<OutboundMessage> {
+        if let Some(msg) = self.try_next() {
+            return Some(msg);
+        }
+        let Lanes { priority, normal, burst, .. } = self;
+        let priority = async {
+            match priority {
+                Some(rx) => rx.recv().await,
+                None => std::future::pending().await,
+            }
+        };
+        tokio::select! {
+            biased;
+            Some(msg) = priority => {
+                *burst += 1;
+                Some(msg)
+            }
+            msg = normal.recv() => {
+                *burst = 0;
+                msg
+            }
+        }
+    }
+
+    /// Stops both lanes accepting frames; what is queued can still be taken.
+    fn close(&mut self) {
+        if let Some(rx) = &mut self.priority {
+            rx.close();
+        }
+        self.normal.close();
+    }
+}
+
+/// Counts pings the server left unanswered in a row.
+struct Heartbeat {
+    max_missed: Option<u32>,
+    missed: u32,
+    // Pongs seen at the previous tick; `None` before the first ping
+    pongs_at_last_ping: Option<u64>,
+}
+
+impl Heartbeat {
+    fn new(max_missed: Option<u32>) -> Self {
+        Self { max_missed, missed: 0, pongs_at_last_ping: None }
+    }
+
+    /// Called just before each ping with the pongs received so far. Returns
+    /// `true` once `max_missed` pings in a row went without a pong.
+    fn expired(&mut self, pongs: u64) -> bool {
+        if let Some(previous) = self.pongs_at_last_ping.replace(pongs) {
+            self.missed = if pongs > previous { 0 } else { self.missed + 1 };
+        }
+        self.max_missed.is_some_and(|max| self.missed >= max)
+    }
 }
 
 // Type alias to make signagures cleaner
 use quinn::RecvStream as QuicRecvStream;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn outbound(channel_id: u32, data: &[u8]) -> OutboundMessage {
+        OutboundMessage { channel_id, control: false, priority: false, frame_type: FrameType::RawBinary, stream_id: None, data: data.to_vec(), flushed: None }
+    }
+
+    #[test]
+    fn test_writer_batches_queued_frames() {
+        let (tx, rx) = mpsc::channel(16);
+        for channel_id in 2..=5 {
+            tx.try_send(outbound(channel_id, b"queued")).unwrap();
+        }
+        let config = EasyClientConfig { max_batch_frames: 3, ..Default::default() };
+        let mut rx = Lanes::new(None, rx, config.max_priority_burst);
+
+        let mut buf = BytesMut::new();
+        let mut flushed = Vec::new();
+        assert_eq!(EasyClient::fill_batch(outbound(1, b"first"), &mut rx, &mut buf, &mut flushed, &Default::default(), 0, &config), 3);
+        let mut decoder = orzatty_core::FrameDecoder::new();
+        decoder.push(&buf);
+        let channels: Vec<u32> = std::iter::from_fn(|| decoder.next_frame().unwrap())
+            .map(|(header, _)| header.channel_id)
+            .collect();
+        assert_eq!(channels, [1, 2, 3]);
+
+        // Never waits for more: an empty queue ends the batch at once
+        buf.clear();
+        assert_eq!(EasyClient::fill_batch(rx.try_next().unwrap(), &mut rx, &mut buf, &mut flushed, &Default::default(), 0, &config), 2);
+        buf.clear();
+        assert_eq!(EasyClient::fill_batch(outbound(6, b"lone"), &mut rx, &mut buf, &mut flushed, &Default::default(), 0, &config), 1);
+        assert!(flushed.is_empty());
+    }
+
+    #[test]
+    fn test_flush_marker_joins_batch_without_a_frame() {
+        let (tx, rx) = mpsc::channel(16);
+        let (marker, mut written) = oneshot::channel();
+        tx.try_send(OutboundMessage { flushed: Some(marker), ..outbound(0, b"") }).unwrap();
+        tx.try_send(outbound(2, b"after")).unwrap();
+        let config = EasyClientConfig::default();
+        let mut rx = Lanes::new(None, rx, config.max_priority_burst);
+
+        let mut buf = BytesMut::new();
+        let mut flushed = Vec::new();
+        assert_eq!(EasyClient::fill_batch(outbound(1, b"before"), &mut rx, &mut buf, &mut flushed, &Default::default(), 0, &config), 2);
+        assert_eq!(flushed.len(), 1);
+        // Signalled by the writer only after the batch is on the stream
+        assert!(written.try_recv().is_err());
+        flushed.pop().unwrap().send(()).unwrap();
+        assert_eq!(written.try_recv(), Ok(()));
+    }
+
+    #[test]
+    fn test_sequenced_channels_numbered_in_write_order() {
+        let (tx, rx) = mpsc::channel(16);
+        for channel_id in [1, 2, 1] {
+            tx.try_send(outbound(channel_id, b"queued")).unwrap();
+        }
+        tx.try_send(OutboundMessage { stream_id: Some(77), ..outbound(1, b"explicit") }).unwrap();
+        let config = EasyClientConfig::default();
+        let mut rx = Lanes::new(None, rx, config.max_priority_burst);
+        let sequences = std::sync::Mutex::new(HashMap::from([(1, 5)]));
+
+        let mut buf = BytesMut::new();
+        EasyClient::fill_batch(outbound(1, b"first"), &mut rx, &mut buf, &mut Vec::new(), &sequences, 9, &config);
+        let mut decoder = orzatty_core::FrameDecoder::new();
+        decoder.push(&buf);
+        let ids: Vec<(u32, u64)> = std::iter::from_fn(|| decoder.next_frame().unwrap())
+            .map(|(header, _)| (header.channel_id, header.stream_id))
+            .collect();
+        // Channel 2 is not sequenced and keeps the stream index
+        let numbered = sequence::to_stream_id;
+        assert_eq!(ids, [(1, numbered(5)), (1, numbered(6)), (2, 9), (1, numbered(7)), (1, 77)]);
+        assert_eq!(sequences.lock().unwrap()[&1], 8);
+    }
+
+    #[tokio::test]
+    async fn test_only_numbered_frames_sequence_checked() {
+        let (gap_tx, mut gaps) = mpsc::unbounded_channel();
+        let mut sequences = SequenceTracker::new();
+        sequences.track(1);
+        let router = Mutex::new(Router {
+            handlers: HashMap::new(),
+            default_handler: None,
+            close_handler: None,
+            disconnect_handler: None,
+            sequences,
+            gap_handler: Some(Arc::new(move |_channel_id, check| { let _ = gap_tx.send(check); })),
+            unknown_handler: None,
+            inbox: None,
+            pulling: false,
+        });
+        let counters = Counters::default();
+        let header = |stream_id| FrameBuilder::new(b"x").channel(1).stream(stream_id).header();
+
+        // An explicit id and a request correlation id are not numbers
+        for stream_id in [0, 77, REQUEST_ID_BASE + 3, sequence::to_stream_id(0), sequence::to_stream_id(2)] {
+            EasyClient::route(&router, &counters, header(stream_id), Bytes::from_static(b"x")).await;
+        }
+        assert_eq!(gaps.try_recv(), Ok(SequenceCheck::Gap { expected: 1, received: 2 }));
+        assert!(gaps.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_unhandled_frames_queue_for_recv() {
+        let (inbox_tx, mut inbox) = mpsc::channel(4);
+        let router = Mutex::new(Router {
+            handlers: HashMap::new(),
+            default_handler: None,
+            close_handler: None,
+            disconnect_handler: None,
+            sequences: SequenceTracker::new(),
+            gap_handler: None,
+            unknown_handler: None,
+            inbox: Some(inbox_tx),
+            pulling: false,
+        });
+        let counters = Counters::default();
+        let header = |channel_id| FrameBuilder::new(b"x").channel(channel_id).header();
+
+        // Dropped until `recv` has been called
+        EasyClient::route(&router, &counters, header(1), Bytes::from_static(b"early")).await;
+        assert!(inbox.try_recv().is_err());
+
+        router.lock().await.pulling = true;
+        router.lock().await.handlers.insert(2, Arc::new(|_| {}));
+        EasyClient::route(&router, &counters, header(2), Bytes::from_static(b"handled")).await;
+        let pulled = Bytes::from_static(b"pulled");
+        EasyClient::route(&router, &counters, header(1), pulled.clone()).await;
+        let frame = inbox.try_recv().unwrap();
+        assert_eq!((frame.header.channel_id, &frame.payload[..]), (1, &b"pulled"[..]));
+        // Delivered without a copy
+        assert_eq!(frame.payload.as_ptr(), pulled.as_ptr());
+        assert!(inbox.try_recv().is_err());
+    }
+
+    #[cfg(feature = "test-util")]
+    #[tokio::test]
+    async fn test_incoming_streams_unhandled_frames() {
+        use futures::StreamExt;
+
+        let crate::test_util::Loopback { client, mut server, listener: _listener } = crate::test_util::pair().await.unwrap();
+        server.on_any(|_header, payload| Some(payload));
+        let served = tokio::spawn(server.run());
+        let (tx, mut handled) = mpsc::unbounded_channel();
+        client.on(2, move |payload| { let _ = tx.send(payload); }).await;
+
+        let mut incoming = client.incoming();
+        for (channel, data) in [(1, &b"a"[..]), (2, b"b"), (3, b"c"), (1, b"d")] {
+            client.send(channel, data).await.unwrap();
+        }
+        let ones: Vec<_> = (&mut incoming)
+            .filter(|frame| futures::future::ready(frame.header.channel_id == 1))
+            .take(2)
+            .map(|frame| frame.payload)
+            .collect()
+            .await;
+        assert_eq!(ones, [&b"a"[..], b"d"]);
+        assert_eq!(&handled.recv().await.unwrap()[..], b"b");
+
+        // The stream ends with the session
+        client.clone().close(0, "done").await.unwrap();
+        let _ = served.await;
+        assert!(incoming.next().await.is_none());
+    }
+
+    #[cfg(all(feature = "test-util", feature = "stream-digest"))]
+    #[tokio::test]
+    async fn test_close_carries_stream_digest() {
+        let crate::test_util::Loopback { client, mut server, listener: _listener } = crate::test_util::pair().await.unwrap();
+        let (tx, mut closed) = mpsc::unbounded_channel();
+        server.on_close(move |code, _reason| { let _ = tx.send(code); });
+        let (tx, mut mismatched) = mpsc::unbounded_channel();
+        server.on_digest_mismatch(move |sent, read| { let _ = tx.send((sent, read)); });
+        let served = tokio::spawn(server.run());
+
+        for data in [&b"one"[..], b"two", &[7; 300]] {
+            client.send(1, data).await.unwrap();
+        }
+        client.close(3, "bye").await.unwrap();
+        assert_eq!(closed.recv().await, Some(3));
+        let _ = served.await;
+        assert!(mismatched.try_recv().is_err());
+    }
+
+    #[cfg(all(feature = "test-util", feature = "stream-digest"))]
+    #[tokio::test]
+    async fn test_close_with_wrong_digest_reported() {
+        let crate::test_util::Loopback { client, mut server, listener: _listener } = crate::test_util::pair().await.unwrap();
+        let (tx, mut mismatched) = mpsc::unbounded_channel();
+        server.on_digest_mismatch(move |sent, read| { let _ = tx.send((sent, read)); });
+        tokio::spawn(server.run());
+
+        client.send(1, b"data").await.unwrap();
+        client.flush().await.unwrap();
+        // Already sealed, so the writer leaves it as is
+        client.send_control(&ControlMessage::Close { code: 0, reason: String::new(), digest: Some([0; 32]) }).await.unwrap();
+        let (sent, read) = tokio::time::timeout(Duration::from_secs(5), mismatched.recv()).await.unwrap().unwrap();
+        assert_eq!(sent, [0; 32]);
+        assert_ne!(read, [0; 32]);
+    }
+
+    #[cfg(feature = "test-util")]
+    #[tokio::test]
+    async fn test_server_streams_accepted_up_to_limit() {
+        use crate::test_util::{bind_server, pair_with, Loopback, TEST_TOKEN};
+
+        let config = EasyClientConfig { max_accepted_streams: 1, ..Default::default() };
+        let listener = bind_server().unwrap().with_authenticator(orzatty_server::auth::Anonymous);
+        let Loopback { client, server, listener: _listener } = pair_with(listener, TEST_TOKEN, config).await.unwrap();
+        let (tx, mut pushed) = mpsc::unbounded_channel();
+        client.on(7, move |payload| { let _ = tx.send(payload); }).await;
+
+        // A pushed stream, left open, holds the only reader
+        let mut first = server.connection().open_uni().await.unwrap();
+        first.write_all(&FrameBuilder::new(b"one").channel(7).to_bytes().unwrap()).await.unwrap();
+        assert_eq!(&pushed.recv().await.unwrap()[..], b"one");
+
+        let (mut second, _recv) = server.connection().open_bi().await.unwrap();
+        second.write_all(&FrameBuilder::new(b"two").channel(7).to_bytes().unwrap()).await.unwrap();
+        assert_eq!(second.stopped().await.unwrap(), quinn::VarInt::from_u32(STREAM_REFUSED_CODE));
+
+        // Once the first ends, its reader is free for another
+        first.finish().await.unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let (mut third, _recv) = server.connection().open_bi().await.unwrap();
+        third.write_all(&FrameBuilder::new(b"three").channel(7).to_bytes().unwrap()).await.unwrap();
+        assert_eq!(&pushed.recv().await.unwrap()[..], b"three");
+        assert!(pushed.try_recv().is_err());
+    }
+
+    #[cfg(feature = "test-util")]
+    #[tokio::test]
+    async fn test_oversized_datagram_rejected() {
+        let crate::test_util::Loopback { client, server: _server, listener: _listener } = crate::test_util::pair().await.unwrap();
+        let max = client.max_datagram_size().unwrap();
+        let sent = client.stats().frames_sent;
+        client.sequence_channel(1).await;
+        let next_sequence = || client.control.next_sequence.lock().unwrap()[&1];
+
+        // The payload alone fits; with the frame header it does not
+        let err = client.send_datagram(1, &vec![0; max]).await.unwrap_err();
+        assert!(matches!(err, ClientError::DatagramTooLarge { size, max: limit } if size > max && limit == max), "{:?}", err);
+        assert_eq!(client.stats().frames_sent, sent);
+        // A datagram that was never sent takes no number
+        assert_eq!(next_sequence(), 0);
+        client.send_datagram(1, &vec![0; max / 2]).await.unwrap();
+        assert_eq!(next_sequence(), 1);
+    }
+
+    #[cfg(feature = "test-util")]
+    #[tokio::test]
+    async fn test_session_info_from_handshake() {
+        use crate::test_util::{bind_server, pair_with, Loopback, TEST_TOKEN};
+
+        let config = EasyClientConfig { metadata: b"v1.4".to_vec(), ..Default::default() };
+        let listener = bind_server().unwrap()
+            .with_authenticator(orzatty_server::auth::Anonymous)
+            .with_session_metadata(|_identity, metadata| [b"region=eu;client=", metadata].concat());
+        let Loopback { client, server, listener: _listener } = pair_with(listener, TEST_TOKEN, config).await.unwrap();
+
+        assert_eq!(server.client_metadata(), b"v1.4");
+        let session = client.session_info();
+        assert_eq!(session.identity, "anonymous");
+        assert_eq!(session.connection_id, server.id());
+        assert_eq!(session.metadata, b"region=eu;client=v1.4");
+    }
+
+    #[cfg(feature = "test-util")]
+    #[tokio::test]
+    async fn test_two_phase_connect_uses_config() {
+        use crate::test_util::{bind_server, root_cert, TEST_TOKEN};
+
+        let listener = bind_server().unwrap()
+            .with_authenticator(orzatty_server::auth::Anonymous)
+            .with_session_metadata(|_identity, metadata| metadata.to_vec());
+        let addr = listener.local_addr().unwrap().to_string();
+        let quic = OrzattyClient::builder().add_root_cert(root_cert()).build().await.unwrap();
+        let pending = EasyClient::establish_with_client(&quic, &addr, "localhost").await.unwrap();
+
+        let config = EasyClientConfig { metadata: b"v2".to_vec(), ..Default::default() };
+        let (client, server) = tokio::join!(
+            pending.authenticate_with_config(TEST_TOKEN, None, config),
+            async { listener.accept().await.unwrap().authenticate().await.unwrap() },
+        );
+        assert_eq!(server.client_metadata(), b"v2");
+        assert_eq!(client.unwrap().session_info().metadata, b"v2");
+
+        // Checked like `connect_with_config` does, before logging in
+        let pending = EasyClient::establish_with_client(&quic, &addr, "localhost").await.unwrap();
+        let bad = EasyClientConfig { inbox_capacity: 0, ..Default::default() };
+        let err = pending.authenticate_with_config(TEST_TOKEN, None, bad).await.err().unwrap();
+        assert!(matches!(err, ClientError::Config(_)), "{:?}", err);
+    }
+
+    #[cfg(feature = "test-util")]
+    #[tokio::test]
+    async fn test_on_text_delivers_valid_utf8() {
+        let crate::test_util::Loopback { client, mut server, listener: _listener } = crate::test_util::pair().await.unwrap();
+        server.on_any(|_header, payload| Some(payload));
+        tokio::spawn(server.run());
+        let (tx, mut texts) = mpsc::unbounded_channel();
+        client.on_text(4, move |text| { let _ = tx.send(text); }).await;
+
+        client.send_text(4, "héllo").await.unwrap();
+        client.send(4, b"\xff\xfe").await.unwrap();
+        client.send_text(4, "wörld").await.unwrap();
+        assert_eq!(texts.recv().await.unwrap(), "héllo");
+        assert_eq!(texts.recv().await.unwrap(), "wörld");
+        assert_eq!(client.undecodable_frames(), 1);
+    }
+
+    #[cfg(feature = "test-util")]
+    #[tokio::test]
+    async fn test_validate_utf8_drops_bad_text() {
+        use crate::test_util::{bind_server, pair_with, Loopback, TEST_TOKEN};
+
+        let config = EasyClientConfig { validate_utf8: true, ..Default::default() };
+        let listener = bind_server().unwrap().with_authenticator(orzatty_server::auth::Anonymous);
+        let Loopback { client, mut server, listener: _listener } = pair_with(listener, TEST_TOKEN, config).await.unwrap();
+        server.on_any(|_header, payload| Some(payload));
+        tokio::spawn(server.run());
+
+        client.send_text(4, "héllo").await.unwrap();
+        client.enqueue(4, FrameType::Utf8Text, None, b"\xff\xfe").await.unwrap();
+        client.send(4, b"\xff").await.unwrap();
+        client.send_text(4, "wörld").await.unwrap();
+        let mut received = Vec::new();
+        for _ in 0..3 {
+            received.push(client.recv().await.unwrap().payload);
+        }
+        assert_eq!(received, ["héllo".as_bytes(), b"\xff", "wörld".as_bytes()]);
+        assert_eq!(client.undecodable_frames(), 1);
+    }
+
+    #[cfg(feature = "test-util")]
+    #[tokio::test]
+    async fn test_try_send_spends_rate_only_when_queued() {
+        use crate::test_util::{bind_server, pair_with, Loopback, TEST_TOKEN};
+
+        // The writer only drains the queue when this test yields
+        let config = EasyClientConfig { outbound_capacity: 1, ..Default::default() };
+        let listener = bind_server().unwrap().with_authenticator(orzatty_server::auth::Anonymous);
+        let Loopback { client, server, listener: _listener } = pair_with(listener, TEST_TOKEN, config).await.unwrap();
+        tokio::spawn(server.run());
+        client.set_channel_rate(1, 2);
+
+        assert_eq!(client.try_send(1, b"a"), Ok(()));
+        assert_eq!(client.try_send(1, b"b"), Err(TrySendError::Full));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(client.try_send(1, b"c"), Ok(()));
+        assert_eq!(client.try_send(1, b"d"), Err(TrySendError::RateLimited));
+    }
+
+    #[cfg(feature = "test-util")]
+    #[tokio::test]
+    async fn test_channel_limit_drops_or_closes() {
+        let crate::test_util::Loopback { client, mut server, listener: _listener } = crate::test_util::pair().await.unwrap();
+        server.on_any(|_header, payload| Some(payload));
+        tokio::spawn(server.run());
+        let (tx, mut disconnected) = mpsc::channel(1);
+        client.on_disconnect(move |reason| { let _ = tx.try_send(reason); }).await;
+
+        client.set_channel_limit(1, 8);
+        client.send(1, &[0; 64]).await.unwrap();
+        client.send(1, b"small").await.unwrap();
+        let frame = client.recv().await.unwrap();
+        assert_eq!(&frame.payload[..], b"small");
+
+        client.set_channel_limit_with(1, 8, OversizedAction::Close);
+        client.send(1, &[0; 64]).await.unwrap();
+        let reason = tokio::time::timeout(Duration::from_secs(5), disconnected.recv()).await.unwrap().unwrap();
+        assert_eq!(reason, DisconnectReason::Error(ClientError::Protocol(
+            orzatty_core::Error::ChannelFrameTooLarge { channel_id: 1, len: 64, max: 8 },
+        )));
+    }
+
+    #[test]
+    fn test_heartbeat_expires_after_consecutive_misses() {
+        let mut heartbeat = Heartbeat::new(Some(2));
+        assert!(!heartbeat.expired(0)); // First ping: nothing owed yet
+        assert!(!heartbeat.expired(0)); // One miss
+        assert!(!heartbeat.expired(1)); // A pong resets the count
+        assert!(!heartbeat.expired(1));
+        assert!(heartbeat.expired(1));
+
+        let mut disabled = Heartbeat::new(None);
+        assert!((0..10).all(|_| !disabled.expired(0)));
+    }
+
+    #[test]
+    fn test_priority_lane_bounded_burst() {
+        let (priority_tx, priority_rx) = mpsc::channel(16);
+        let (normal_tx, normal_rx) = mpsc::channel(16);
+        for channel_id in 1..=5 {
+            priority_tx.try_send(outbound(channel_id, b"urgent")).unwrap();
+        }
+        for channel_id in 101..=102 {
+            normal_tx.try_send(outbound(channel_id, b"bulk")).unwrap();
+        }
+
+        // Two priority frames, then one normal frame gets its turn
+        let mut lanes = Lanes::new(Some(priority_rx), normal_rx, 2);
+        let order: Vec<u32> = std::iter::from_fn(|| lanes.try_next()).map(|msg| msg.channel_id).collect();
+        assert_eq!(order, [1, 2, 101, 3, 4, 102, 5]);
+    }
+}
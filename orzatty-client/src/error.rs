@@ -0,0 +1,177 @@
+//! Typed client errors.
+//!
+//! Every fallible public call returns [`ClientError`], so callers can match
+//! on the cause, e.g. reconnect only when [`ClientError::is_transport`] holds.
+//! It implements `std::error::Error`, so `?` still converts it into an
+//! `anyhow::Error` for code that uses anyhow.
+
+use std::fmt;
+use std::time::Duration;
+
+/// `Result` with [`ClientError`] as the default error type.
+pub type Result<T, E = ClientError> = std::result::Result<T, E>;
+
+/// Errors returned by the client API.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClientError {
+    /// The client could not be set up: bad address, certificate or transport limit.
+    Config(String),
+    /// The QUIC dial or TLS handshake failed.
+    ConnectFailed(String),
+    /// Dial, handshake and authentication did not complete within the given bound.
+    ConnectTimeout(Duration),
+    /// [`EasyClient::request`](crate::easy::EasyClient::request) got no reply in time.
+    RequestTimeout(Duration),
+    /// The server sent no auth reply within the auth timeout; see
+    /// [`OrzattyClient::with_auth_timeout`](crate::OrzattyClient::with_auth_timeout).
+    AuthTimeout(Duration),
+    /// The server answered the auth exchange with `AuthMessage::Fail`.
+    AuthRejected(String),
+    /// [`connect_with_retry`](crate::OrzattyClient::connect_with_retry) gave up;
+    /// `last` is the final attempt's error.
+    RetriesExhausted { attempts: u32, last: Box<ClientError> },
+    /// The peer sent a malformed frame.
+    Protocol(orzatty_core::Error),
+    /// A message could not be serialized, or a received one was unexpected or
+    /// failed validation.
+    InvalidMessage(String),
+    /// The connection or stream was closed, locally or by the peer.
+    ConnectionClosed(String),
+    /// A datagram frame of `size` bytes exceeds the connection's current
+    /// `max` datagram size; nothing was sent.
+    DatagramTooLarge { size: usize, max: usize },
+}
+
+impl ClientError {
+    /// Whether the error came from the network rather than from the server's
+    /// verdict, the configuration or the data: the cases where reconnecting
+    /// may help.
+    pub fn is_transport(&self) -> bool {
+        matches!(self,
+            ClientError::ConnectFailed(_) | ClientError::ConnectTimeout(_) | ClientError::AuthTimeout(_)
+            | ClientError::ConnectionClosed(_))
+    }
+
+    /// Classifies an error from `Framer::read_frame`.
+    pub(crate) fn from_read(e: anyhow::Error) -> Self {
+        match e.downcast::<orzatty_core::Error>() {
+            Ok(e) => ClientError::Protocol(e),
+            Err(e) => ClientError::ConnectionClosed(e.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientError::Config(msg) =>
+                write!(f, "Invalid client configuration: {}", msg),
+            ClientError::ConnectFailed(msg) =>
+                write!(f, "Connect failed: {}", msg),
+            ClientError::ConnectTimeout(t) =>
+                write!(f, "Connect timed out after {:?}", t),
+            ClientError::RequestTimeout(t) =>
+                write!(f, "No reply within {:?}", t),
+            ClientError::AuthTimeout(t) =>
+                write!(f, "No auth reply from the server within {:?}", t),
+            ClientError::AuthRejected(reason) =>
+                write!(f, "Authentication Failed: {}", reason),
+            ClientError::RetriesExhausted { attempts, .. } =>
+                write!(f, "Connect failed after {} attempts", attempts),
+            ClientError::Protocol(e) =>
+                write!(f, "Protocol error: {}", e),
+            ClientError::InvalidMessage(msg) =>
+                write!(f, "Invalid message: {}", msg),
+            ClientError::ConnectionClosed(msg) =>
+                write!(f, "Connection closed: {}", msg),
+            ClientError::DatagramTooLarge { size, max } =>
+                write!(f, "Datagram of {} bytes exceeds the current limit of {}", size, max),
+        }
+    }
+}
+
+impl std::error::Error for ClientError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ClientError::RetriesExhausted { last, .. } => Some(last.as_ref()),
+            ClientError::Protocol(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Why [`EasyClient::try_send`](crate::easy::EasyClient::try_send) did not queue a frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrySendError {
+    /// The outbound queue is full: the network is not keeping up.
+    Full,
+    /// The channel is over the budget set with `set_channel_rate`.
+    RateLimited,
+    /// The client has shut down or its connection is gone.
+    Closed,
+}
+
+impl fmt::Display for TrySendError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TrySendError::Full => write!(f, "Outbound queue is full"),
+            TrySendError::RateLimited => write!(f, "Channel is over its send rate"),
+            TrySendError::Closed => write!(f, "Connection closed"),
+        }
+    }
+}
+
+impl std::error::Error for TrySendError {}
+
+impl From<orzatty_core::Error> for ClientError {
+    fn from(e: orzatty_core::Error) -> Self {
+        ClientError::Protocol(e)
+    }
+}
+
+/// I/O errors only arise on QUIC streams, where they mean the stream was
+/// reset or the connection lost.
+impl From<std::io::Error> for ClientError {
+    fn from(e: std::io::Error) -> Self {
+        ClientError::ConnectionClosed(e.to_string())
+    }
+}
+
+impl From<quinn::ConnectError> for ClientError {
+    fn from(e: quinn::ConnectError) -> Self {
+        ClientError::ConnectFailed(e.to_string())
+    }
+}
+
+impl From<quinn::ConnectionError> for ClientError {
+    fn from(e: quinn::ConnectionError) -> Self {
+        ClientError::ConnectionClosed(e.to_string())
+    }
+}
+
+impl From<quinn::WriteError> for ClientError {
+    fn from(e: quinn::WriteError) -> Self {
+        ClientError::ConnectionClosed(e.to_string())
+    }
+}
+
+impl From<quinn::ReadError> for ClientError {
+    fn from(e: quinn::ReadError) -> Self {
+        ClientError::ConnectionClosed(e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_converts_into_anyhow() {
+        let err: anyhow::Error = ClientError::AuthRejected("bad token".into()).into();
+        assert_eq!(err.downcast_ref(), Some(&ClientError::AuthRejected("bad token".into())));
+
+        let exhausted = ClientError::RetriesExhausted { attempts: 3, last: Box::new(ClientError::ConnectTimeout(Duration::from_secs(1))) };
+        assert!(!exhausted.is_transport());
+        assert_eq!(std::error::Error::source(&exhausted).unwrap().to_string(), "Connect timed out after 1s");
+    }
+}
@@ -0,0 +1,180 @@
+//! `futures` adapters over raw QUIC streams.
+//!
+//! [`FrameStream`] and [`FrameSink`] expose Orzatty framing through the
+//! standard `Stream`/`Sink` traits, so a stream can be composed with
+//! `futures` combinators (`forward`, `map`, `buffer_unordered`, ...) or wrapped
+//! in tower middleware. `EasyClient` remains the batteries-included option;
+//! these are for users who want to own the streams themselves.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+use bytes::{Buf, Bytes, BytesMut};
+use futures::{Sink, Stream};
+use orzatty_core::frame::FrameHeader;
+use orzatty_core::Framer;
+use quinn::{RecvStream, SendStream};
+use tokio::io::AsyncWrite;
+
+use crate::{ClientError, Result};
+
+/// Buffered bytes above which `poll_ready` flushes before accepting more frames.
+const SINK_HIGH_WATER: usize = 64 * 1024;
+
+type ReadFuture = Pin<Box<dyn Future<Output = (Framer, RecvStream, Result<Option<(FrameHeader, BytesMut)>>)> + Send>>;
+
+/// A `Stream` of frames read from a QUIC receive stream.
+///
+/// Yields `Ok((header, payload))` per frame and ends when the peer finishes the
+/// stream. After an error the stream is terminated.
+pub struct FrameStream {
+    idle: Option<(Framer, RecvStream)>,
+    pending: Option<ReadFuture>,
+}
+
+impl FrameStream {
+    pub fn new(stream: RecvStream) -> Self {
+        Self::with_framer(stream, Framer::new())
+    }
+
+    /// Uses a pre-configured `Framer` (custom capacity, high water mark, ...).
+    pub fn with_framer(stream: RecvStream, framer: Framer) -> Self {
+        Self { idle: Some((framer, stream)), pending: None }
+    }
+
+    /// Returns the framer and stream, unless a read is in flight or the stream ended.
+    pub fn into_inner(self) -> Option<(Framer, RecvStream)> {
+        self.idle
+    }
+}
+
+impl Stream for FrameStream {
+    type Item = Result<(FrameHeader, Bytes)>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if self.pending.is_none() {
+            let Some((mut framer, mut stream)) = self.idle.take() else {
+                return Poll::Ready(None);
+            };
+            self.pending = Some(Box::pin(async move {
+                let res = framer.read_frame(&mut stream).await.map_err(ClientError::from_read);
+                (framer, stream, res)
+            }));
+        }
+
+        let (framer, stream, res) = ready!(self.pending.as_mut().unwrap().as_mut().poll(cx));
+        self.pending = None;
+
+        match res {
+            Ok(Some((header, payload))) => {
+                self.idle = Some((framer, stream));
+                Poll::Ready(Some(Ok((header, payload.freeze()))))
+            }
+            Ok(None) => Poll::Ready(None),
+            Err(e) => Poll::Ready(Some(Err(e))),
+        }
+    }
+}
+
+/// A `Sink` that frames `(header, payload)` items onto a QUIC send stream.
+///
+/// Frames are encoded into an internal buffer and written on `poll_flush`;
+/// `poll_ready` applies backpressure once the buffer passes 64 KiB. The
+/// header's `length` is overwritten with the payload length. Closing the sink
+/// finishes the stream.
+pub struct FrameSink {
+    stream: SendStream,
+    buf: BytesMut,
+}
+
+impl FrameSink {
+    pub fn new(stream: SendStream) -> Self {
+        Self { stream, buf: BytesMut::with_capacity(4096) }
+    }
+
+    /// Returns the stream. Frames not yet flushed are discarded.
+    pub fn into_inner(self) -> SendStream {
+        self.stream
+    }
+
+    fn poll_write_buf(&mut self, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        while !self.buf.is_empty() {
+            let n = ready!(Pin::new(&mut self.stream).poll_write(cx, &self.buf))?;
+            if n == 0 {
+                return Poll::Ready(Err(ClientError::ConnectionClosed("Stream closed while flushing frames".into())));
+            }
+            self.buf.advance(n);
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl Sink<(FrameHeader, Bytes)> for FrameSink {
+    type Error = ClientError;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        let this = self.get_mut();
+        if this.buf.len() >= SINK_HIGH_WATER {
+            ready!(this.poll_write_buf(cx))?;
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(self: Pin<&mut Self>, (header, payload): (FrameHeader, Bytes)) -> Result<()> {
+        let this = self.get_mut();
+        header.encode_with_payload_bytes(&payload, &mut this.buf)?;
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.get_mut().poll_write_buf(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_write_buf(cx))?;
+        ready!(Pin::new(&mut this.stream).poll_shutdown(cx))?;
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(all(test, feature = "test-util"))]
+mod tests {
+    use super::*;
+    use futures::{stream, StreamExt, TryStreamExt};
+    use orzatty_core::frame::{FrameFlags, FrameType};
+
+    #[tokio::test]
+    async fn test_sink_and_stream_round_trip() {
+        let crate::test_util::Loopback { client, mut server, listener: _listener } = crate::test_util::pair().await.unwrap();
+        server.on_any(|_header, payload| Some(payload));
+        tokio::spawn(server.run());
+
+        // The server echoes each frame on the stream it came in on, and
+        // finishes that stream once the client has finished its side
+        let (send, recv) = client.connection().open_bi().await.unwrap();
+        let frames: Vec<_> = (0..3u32)
+            .map(|i| {
+                let header = FrameHeader {
+                    flags: FrameFlags::empty(),
+                    frame_type: FrameType::RawBinary,
+                    channel_id: i + 1,
+                    stream_id: 7,
+                    length: 0,
+                };
+                (header, Bytes::from(vec![i as u8; 100 * i as usize]))
+            })
+            .collect();
+        stream::iter(frames.clone()).map(Ok).forward(FrameSink::new(send)).await.unwrap();
+
+        let echoed: Vec<_> = FrameStream::new(recv).try_collect().await.unwrap();
+        assert_eq!(echoed.len(), frames.len());
+        for ((header, payload), (sent_header, sent_payload)) in echoed.iter().zip(&frames) {
+            assert_eq!(header.channel_id, sent_header.channel_id);
+            assert_eq!(header.stream_id, sent_header.stream_id);
+            assert_eq!(header.length, sent_payload.len() as u64);
+            assert_eq!(payload, sent_payload);
+        }
+    }
+}
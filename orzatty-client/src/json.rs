@@ -0,0 +1,37 @@
+//! JSON encoding for `FrameType::Json` payloads (see
+//! [`EasyClient::send_serde`](crate::easy::EasyClient::send_serde)).
+//!
+//! The human-readable counterpart of [`typed`](crate::typed), for talking to
+//! browsers and non-Rust services.
+
+use crate::{ClientError, Result};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// Serializes `value` as a JSON document.
+pub fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>> {
+    serde_json::to_vec(value)
+        .map_err(|e| ClientError::InvalidMessage(format!("Failed to serialize {}: {}", std::any::type_name::<T>(), e)))
+}
+
+/// Parses a received JSON document into `T`.
+pub fn decode<T: DeserializeOwned>(payload: &[u8]) -> Result<T> {
+    serde_json::from_slice(payload)
+        .map_err(|e| ClientError::InvalidMessage(format!("Failed to decode {}: {}", std::any::type_name::<T>(), e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_json_round_trip() {
+        let scores = BTreeMap::from([("alice".to_string(), 3u32), ("bob".to_string(), 5)]);
+        let bytes = encode(&scores).unwrap();
+        assert_eq!(bytes, br#"{"alice":3,"bob":5}"#);
+        assert_eq!(decode::<BTreeMap<String, u32>>(&bytes).unwrap(), scores);
+
+        assert!(matches!(decode::<BTreeMap<String, u32>>(b"{\"alice\":"), Err(ClientError::InvalidMessage(_))));
+    }
+}
@@ -1,15 +1,57 @@
-use anyhow::Result;
-use quinn::{ClientConfig, Connection, Endpoint};
-use std::{net::SocketAddr, sync::Arc};
+use quinn::{Connection, Endpoint, ZeroRttAccepted};
+use std::{net::SocketAddr, time::Duration};
 use orzatty_core::frame::{FrameHeader, FrameType, FrameFlags};
-use orzatty_core::auth::AuthMessage;
-use orzatty_core::Framer;
+use orzatty_core::auth::{challenge_proof, AuthMessage, Capabilities, SessionInfo};
+use orzatty_core::{Framer, PROTOCOL_VERSION};
 
 
+#[cfg(feature = "blocking")]
+pub mod blocking;
+pub mod builder;
+pub mod channel;
 pub mod easy; // Expose the new Easy API
+pub mod error;
+pub mod framed;
+#[cfg(feature = "serde")]
+pub mod json;
+#[cfg(feature = "prost")]
+pub mod proto;
+mod rate;
+pub mod retry;
+#[cfg(feature = "test-util")]
+pub mod test_util;
+pub mod typed;
+
+pub use builder::{CongestionController, OrzattyClientBuilder};
+pub use error::{ClientError, Result, TrySendError};
+pub use retry::RetryPolicy;
+
+/// Upper bound applied by [`OrzattyClient::connect`] to dial + handshake + auth.
+pub const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Local address used by [`OrzattyClient::new`]: any IPv4 interface, random port.
+pub const DEFAULT_BIND_ADDR: SocketAddr = SocketAddr::new(std::net::IpAddr::V4(std::net::Ipv4Addr::UNSPECIFIED), 0);
+
+/// How many times the auth exchange is attempted when it fails at the transport level.
+pub const DEFAULT_AUTH_ATTEMPTS: u32 = 2;
+
+/// How long the client waits for each auth message from the server.
+pub const DEFAULT_AUTH_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Features this client implements, offered in every `AuthMessage::Hello`.
+pub const SUPPORTED_CAPABILITIES: Capabilities = Capabilities::DATAGRAMS
+    .union(Capabilities::FRAGMENTATION)
+    .union(Capabilities::CHECKSUM);
 
 pub struct OrzattyClient {
     endpoint: Endpoint,
+    auth_attempts: u32,
+    auth_timeout: Duration,
+    auth_secret: Option<Vec<u8>>,
+    // Application metadata offered in `Hello`
+    metadata: Vec<u8>,
+    // Hand out connections in 0-RTT when a resumed session allows it
+    zero_rtt: bool,
 }
 
 impl OrzattyClient {
@@ -17,113 +59,294 @@ impl OrzattyClient {
         &self.endpoint
     }
     /// Creates a new Orzatty Client instance.
-    /// Binds to 0.0.0.0:0 (random port) by default.
+    /// Binds to [`DEFAULT_BIND_ADDR`] (0.0.0.0, random port).
     /// 
-    /// By default, this will:
-    /// - Load system CA certificates for production use
-    /// - Allow self-signed certificates if `ORZATTY_ALLOW_INSECURE=true` is set
+    /// Server certificates are verified against the system CA certificates;
+    /// use [`builder`](Self::builder) to trust more roots.
     pub async fn new() -> Result<Self> {
-        Self::with_config(true).await
+        Self::builder().build().await
     }
 
     /// Creates a new Orzatty Client with custom certificate validation.
     /// 
     /// # Arguments
-    /// * `allow_insecure` - If true, allows self-signed certificates (dev only)
+    /// * `allow_insecure` - If true, allows self-signed certificates (dev only).
+    ///   Fails with [`ClientError::Config`] unless the `dangerous-insecure`
+    ///   feature is enabled.
     pub async fn with_config(allow_insecure: bool) -> Result<Self> {
-        let mut client_crypto = rustls::ClientConfig::builder()
-            .with_safe_defaults()
-            .with_root_certificates({
-                let mut root_store = rustls::RootCertStore::empty();
-                
-                // Try to load system certificates
-                if let Ok(certs) = rustls_native_certs::load_native_certs() {
-                    for cert in certs {
-                        let _ = root_store.add(&rustls::Certificate(cert.0));
-                    }
-                }
-                
-                root_store
-            })
-            .with_no_client_auth();
-
-        // Allow self-signed certificates if requested (dev only)
-        // Check environment variable or parameter
-        let allow_self_signed = allow_insecure || 
-            std::env::var("ORZATTY_ALLOW_INSECURE")
-                .unwrap_or_else(|_| "false".to_string())
-                .parse::<bool>()
-                .unwrap_or(false);
-        
-        if allow_self_signed {
-            client_crypto.dangerous().set_certificate_verifier(Arc::new(SkipServerVerification));
-        }
+        Self::with_bind_addr(DEFAULT_BIND_ADDR, allow_insecure).await
+    }
 
-        let mut client_config = ClientConfig::new(Arc::new(client_crypto));
-        
-        // Hardening: Increase timeouts and enable keep-alives
-        let mut transport_config = quinn::TransportConfig::default();
-        transport_config.max_idle_timeout(Some(quinn::VarInt::from_u32(10_000).into())); // 10s
-        transport_config.keep_alive_interval(Some(std::time::Duration::from_secs(2)));
-        client_config.transport_config(Arc::new(transport_config));
-
-        let mut endpoint = Endpoint::client("0.0.0.0:0".parse().unwrap())?;
-        endpoint.set_default_client_config(client_config);
-        
-        Ok(Self { endpoint })
+    /// Like [`with_config`](Self::with_config), but binds the local UDP socket
+    /// to `bind_addr` instead of [`DEFAULT_BIND_ADDR`].
+    ///
+    /// Use it to pick the outgoing interface on multi-homed hosts or a fixed
+    /// source port for firewall rules. The address family must match the
+    /// servers you connect to (e.g. `[::]:0` for IPv6).
+    pub async fn with_bind_addr(bind_addr: SocketAddr, allow_insecure: bool) -> Result<Self> {
+        Self::builder().bind_addr(bind_addr).allow_insecure(allow_insecure).build().await
+    }
+
+    /// Creates a client that also trusts the CA certificate(s) in `pem`, with
+    /// full server verification; see [`OrzattyClientBuilder::add_root_cert`].
+    pub async fn with_root_cert_pem(pem: &[u8]) -> Result<Self> {
+        Self::builder().add_root_cert(pem).build().await
+    }
+
+    /// Creates a client that authenticates itself with a certificate (mutual
+    /// TLS), for servers that require one. `chain` is leaf first and `key`
+    /// its private key; server certificates are verified against the system
+    /// roots.
+    ///
+    /// A token is still sent by [`connect`](Self::connect).
+    pub async fn with_client_cert(chain: Vec<rustls::Certificate>, key: rustls::PrivateKey) -> Result<Self> {
+        Self::builder().client_cert(chain, key).allow_insecure(false).build().await
+    }
+
+    /// Starts an [`OrzattyClientBuilder`] for custom transport parameters
+    /// (idle timeout, keep-alive, stream limits, flow-control windows).
+    pub fn builder() -> OrzattyClientBuilder {
+        OrzattyClientBuilder::new()
+    }
+
+    /// Sets how many times the auth stream open + Hello/response exchange is
+    /// attempted (minimum 1). Only transport errors are retried; a server
+    /// `AuthMessage::Fail` is returned immediately.
+    pub fn with_auth_attempts(mut self, attempts: u32) -> Self {
+        self.auth_attempts = attempts.max(1);
+        self
+    }
+
+    /// Sets how long to wait for each auth message from the server (default
+    /// [`DEFAULT_AUTH_TIMEOUT`]). A server that stays silent longer fails the
+    /// connect with [`ClientError::AuthTimeout`] rather than hanging it.
+    pub fn with_auth_timeout(mut self, timeout: Duration) -> Self {
+        self.auth_timeout = timeout;
+        self
+    }
+
+    /// Sets the shared secret used to answer an `AuthMessage::Challenge`.
+    ///
+    /// Servers that do not challenge keep using the plain `Hello` → `Ok` flow;
+    /// servers that do are refused if no secret is set.
+    pub fn with_auth_secret(mut self, secret: impl Into<Vec<u8>>) -> Self {
+        self.auth_secret = Some(secret.into());
+        self
+    }
+
+    /// Sets application-defined bytes sent in `AuthMessage::Hello`, e.g. a
+    /// client version or device id. The server reads them with
+    /// `ServerConnection::client_metadata`; empty by default.
+    pub fn with_metadata(mut self, metadata: impl Into<Vec<u8>>) -> Self {
+        self.metadata = metadata.into();
+        self
     }
 
     /// Connects to an Orzatty Server and authenticates.
+    ///
+    /// Bounded by [`DEFAULT_CONNECT_TIMEOUT`]; see [`connect_timeout`](Self::connect_timeout).
     pub async fn connect(&self, addr: SocketAddr, server_name: &str, token: &str) -> Result<Connection> {
-        let connection = self.endpoint.connect(addr, server_name)?.await?;
-        
+        self.connect_timeout(addr, server_name, token, DEFAULT_CONNECT_TIMEOUT).await
+    }
+
+    /// Connects to an Orzatty Server and authenticates, giving up after `timeout`.
+    ///
+    /// The deadline covers the QUIC dial, the TLS handshake and the auth exchange.
+    /// On expiry a [`ClientError::ConnectTimeout`] is returned and any connection
+    /// that was already established is closed rather than left half-open. Each
+    /// auth reply is also bounded by [`with_auth_timeout`](Self::with_auth_timeout),
+    /// failing with [`ClientError::AuthTimeout`] when that fires first.
+    pub async fn connect_timeout(&self, addr: SocketAddr, server_name: &str, token: &str, timeout: Duration) -> Result<Connection> {
+        Ok(self.connect_negotiated(addr, server_name, token, &self.metadata, timeout).await?.0)
+    }
+
+    /// [`connect_timeout`](Self::connect_timeout), also returning the
+    /// capabilities agreed with the server and the session it assigned.
+    /// Sends `metadata` in the `Hello` instead of the client's own.
+    #[cfg_attr(feature = "tracing", tracing::instrument(name = "connect", skip(self, token, metadata), fields(connection_id = tracing::field::Empty), err))]
+    pub(crate) async fn connect_negotiated(&self, addr: SocketAddr, server_name: &str, token: &str, metadata: &[u8], timeout: Duration) -> Result<(Connection, Capabilities, SessionInfo)> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        let (connection, zero_rtt) = self.establish_early(addr, server_name, timeout).await?;
+        #[cfg(feature = "tracing")]
+        tracing::Span::current().record("connection_id", connection.stable_id());
+
+        let auth = Self::authenticate(&connection, token, metadata, self.auth_secret.as_deref(), self.auth_attempts, self.auth_timeout, zero_rtt);
+        match tokio::time::timeout_at(deadline, auth).await {
+            Ok(Ok((capabilities, session))) => {
+                #[cfg(feature = "tracing")]
+                tracing::debug!(?capabilities, identity = %session.identity, "authenticated");
+                Ok((connection, capabilities, session))
+            }
+            Ok(Err(e)) => {
+                connection.close(quinn::VarInt::from_u32(0), b"auth failed");
+                Err(e)
+            }
+            Err(_) => {
+                connection.close(quinn::VarInt::from_u32(0), b"connect timeout");
+                Err(ClientError::ConnectTimeout(timeout))
+            }
+        }
+    }
+
+    /// Connects and authenticates, retrying with exponential backoff and jitter.
+    ///
+    /// Each attempt is a full [`connect`](Self::connect). A server rejection
+    /// ([`ClientError::AuthRejected`]) is final and returned as is; any other
+    /// failure is retried up to `policy.max_retries` times. When attempts run
+    /// out, the error is [`ClientError::RetriesExhausted`] carrying the last
+    /// attempt's error.
+    pub async fn connect_with_retry(&self, addr: SocketAddr, server_name: &str, token: &str, policy: RetryPolicy) -> Result<Connection> {
+        let mut retry = 0;
+        loop {
+            let err = match self.connect(addr, server_name, token).await {
+                Ok(connection) => return Ok(connection),
+                Err(e @ ClientError::AuthRejected(_)) => return Err(e),
+                Err(e) => e,
+            };
+            if retry >= policy.max_retries {
+                return Err(ClientError::RetriesExhausted { attempts: retry + 1, last: Box::new(err) });
+            }
+            tokio::time::sleep(policy.backoff(retry)).await;
+            retry += 1;
+        }
+    }
+
+    /// Completes the QUIC/TLS handshake without authenticating.
+    ///
+    /// The returned connection has not sent `AuthMessage::Hello` yet; most
+    /// servers will not serve application traffic on it until it does.
+    ///
+    /// With [`OrzattyClientBuilder::zero_rtt`] and a cached session for this
+    /// server, it returns before the handshake completes, so the first data
+    /// sent on it travels as replayable 0-RTT data. Should the server turn
+    /// that data down, streams opened before the handshake completes fail
+    /// and have to be opened again; [`connect`](Self::connect) does this for
+    /// the `Hello`.
+    pub async fn establish(&self, addr: SocketAddr, server_name: &str, timeout: Duration) -> Result<Connection> {
+        Ok(self.establish_early(addr, server_name, timeout).await?.0)
+    }
+
+    /// [`establish`](Self::establish), also returning whether the server
+    /// accepts the 0-RTT data when the connection was resumed with it.
+    pub(crate) async fn establish_early(&self, addr: SocketAddr, server_name: &str, timeout: Duration) -> Result<(Connection, Option<ZeroRttAccepted>)> {
+        // Dropping `Connecting` on expiry abandons the handshake.
+        let mut connecting = self.endpoint.connect(addr, server_name)?;
+        if self.zero_rtt {
+            // Falls back to the full handshake when no session can be resumed
+            match connecting.into_0rtt() {
+                Ok((connection, accepted)) => return Ok((connection, Some(accepted))),
+                Err(full) => connecting = full,
+            }
+        }
+        let connection = tokio::time::timeout(timeout, connecting).await
+            .map_err(|_| ClientError::ConnectTimeout(timeout))?
+            .map_err(|e| ClientError::ConnectFailed(e.to_string()))?;
+        Ok((connection, None))
+    }
+
+    /// Runs the auth handshake on a freshly established connection, retrying
+    /// transient stream errors up to `attempts` times in total and waiting at
+    /// most `timeout` for each server message. Returns the negotiated
+    /// capabilities and the session the server assigned.
+    ///
+    /// With `zero_rtt` set, the first `Hello` went out as 0-RTT data; if the
+    /// server rejected it, the exchange is run again without counting
+    /// against `attempts`.
+    pub(crate) async fn authenticate(connection: &Connection, token: &str, metadata: &[u8], secret: Option<&[u8]>, attempts: u32, timeout: Duration, mut zero_rtt: Option<ZeroRttAccepted>) -> Result<(Capabilities, SessionInfo)> {
+        let mut attempt = 1;
+        loop {
+            let result = Self::authenticate_once(connection, token, metadata, secret, timeout).await;
+            // A `Hello` the server rejected as 0-RTT data never reached it:
+            // send it again as the same attempt. Known once the handshake
+            // completes, which such a `Hello` fails after anyway
+            if result.is_err() {
+                if let Some(accepted) = zero_rtt.take() {
+                    if !accepted.await && connection.close_reason().is_none() {
+                        continue;
+                    }
+                }
+            }
+            match result {
+                Err(ClientError::ConnectionClosed(_)) if attempt < attempts && connection.close_reason().is_none() => {
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    async fn authenticate_once(connection: &Connection, token: &str, metadata: &[u8], secret: Option<&[u8]>, timeout: Duration) -> Result<(Capabilities, SessionInfo)> {
         // --- Auth Handshake ---
         // Open bidirectional stream (Stream 0)
         let (mut send, mut recv) = connection.open_bi().await?;
+        let mut framer = Framer::new();
         
         // 1. Send AuthHello
-        let auth_msg = AuthMessage::Hello { token: token.to_string() };
-        let auth_bytes = rkyv::to_bytes::<_, 256>(&auth_msg)
-            .map_err(|e| anyhow::anyhow!("Failed to serialize auth hello: {:?}", e))?;
-            
-        let mut head_buf = [0u8; 32];
-        let header = FrameHeader {
-            flags: FrameFlags::empty(),
-            frame_type: FrameType::RkyvAligned,
-            channel_id: 0,
-            stream_id: 0,
-            length: auth_bytes.len() as u64,
-        };
-        let h_len = header.encode(&mut head_buf)?;
-        
-        send.write_all(&head_buf[..h_len]).await?;
-        send.write_all(&auth_bytes).await?;
-        send.finish().await?;
-        
-        // 2. Wait for AuthResponse using Framer
-        let mut framer = Framer::new();
-        let (_resp_header, payload) = framer.read_frame(&mut recv).await?
-            .ok_or(anyhow::anyhow!("Server closed auth stream before response"))?;
+        write_auth(&mut send, &AuthMessage::Hello {
+            token: token.to_string(),
+            protocol_version: PROTOCOL_VERSION,
+            capabilities: SUPPORTED_CAPABILITIES.bits(),
+            metadata: metadata.to_vec(),
+        }).await?;
         
-        let resp_msg: AuthMessage = rkyv::from_bytes(&payload)
-            .map_err(|e| anyhow::anyhow!("Failed to deserialize auth response: {:?}", e))?;
+        // 2. Wait for AuthResponse, answering a challenge if the server issues one
+        let mut resp_msg = read_auth(&mut framer, &mut recv, timeout).await?;
+        if let AuthMessage::Challenge { nonce } = resp_msg {
+            let secret = secret
+                .ok_or_else(|| ClientError::Config("Server requested a challenge but no auth secret is configured".into()))?;
+            write_auth(&mut send, &AuthMessage::Proof { hmac: challenge_proof(secret, &nonce) }).await?;
+            resp_msg = read_auth(&mut framer, &mut recv, timeout).await?;
+        }
+        // Dropping `send` finishes the stream without waiting for acks
             
         match resp_msg {
-            AuthMessage::Ok => {
-                // Return connection, ready to be used
-                Ok(connection)
+            AuthMessage::Ok { capabilities, session } => {
+                // Connection is ready to be used; never rely on more than we offered
+                Ok((SUPPORTED_CAPABILITIES & Capabilities::from_bits_truncate(capabilities), session))
             }
             AuthMessage::Fail { reason } => {
-                Err(anyhow::anyhow!("Authentication Failed: {}", reason))
+                Err(ClientError::AuthRejected(reason))
             }
-            _ => Err(anyhow::anyhow!("Unexpected auth response")),
+            _ => Err(ClientError::InvalidMessage("Unexpected auth response".into())),
         }
     }
 }
 
+async fn write_auth(send: &mut quinn::SendStream, msg: &AuthMessage) -> Result<()> {
+    let auth_bytes = rkyv::to_bytes::<_, 256>(msg)
+        .map_err(|e| ClientError::InvalidMessage(format!("Failed to serialize auth message: {:?}", e)))?;
+    let header = FrameHeader {
+        flags: FrameFlags::empty(),
+        frame_type: FrameType::RkyvAligned,
+        channel_id: 0,
+        stream_id: 0,
+        length: auth_bytes.len() as u64,
+    };
+    let mut frame = Vec::new();
+    header.encode_with_payload(&auth_bytes, &mut frame)?;
+    send.write_all(&frame).await?;
+    Ok(())
+}
+
+async fn read_auth(framer: &mut Framer, recv: &mut quinn::RecvStream, timeout: Duration) -> Result<AuthMessage> {
+    let (_resp_header, payload) = framer.read_frame_timeout(recv, timeout).await
+        .map_err(|e| match e.downcast_ref() {
+            Some(orzatty_core::Error::ReadTimeout(timeout)) => ClientError::AuthTimeout(*timeout),
+            _ => ClientError::from_read(e),
+        })?
+        .ok_or_else(|| ClientError::ConnectionClosed("Server closed auth stream before response".into()))?;
+    // Copy into an aligned buffer: the payload may sit at any offset
+    let mut aligned = rkyv::AlignedVec::new();
+    aligned.extend_from_slice(&payload);
+    rkyv::from_bytes(&aligned)
+        .map_err(|e| ClientError::InvalidMessage(format!("Failed to deserialize auth response: {:?}", e)))
+}
+
 // Internal helper for skipping cert verification in Dev mode
-struct SkipServerVerification;
+#[cfg(feature = "dangerous-insecure")]
+pub(crate) struct SkipServerVerification;
 
+#[cfg(feature = "dangerous-insecure")]
 impl rustls::client::ServerCertVerifier for SkipServerVerification {
     fn verify_server_cert(
         &self,
@@ -137,3 +360,57 @@ impl rustls::client::ServerCertVerifier for SkipServerVerification {
         Ok(rustls::client::ServerCertVerified::assertion())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_silent_server_times_out_auth() {
+        let cert = rcgen::generate_simple_self_signed(vec!["localhost".into()]).unwrap();
+        let der = cert.serialize_der().unwrap();
+        let server_config = quinn::ServerConfig::with_single_cert(
+            vec![rustls::Certificate(der.clone())],
+            rustls::PrivateKey(cert.serialize_private_key_der()),
+        ).unwrap();
+        let server = Endpoint::server(server_config, "127.0.0.1:0".parse().unwrap()).unwrap();
+        let addr = server.local_addr().unwrap();
+        // Completes the QUIC handshake and reads the Hello, but never answers
+        tokio::spawn(async move {
+            let connection = server.accept().await.unwrap().await.unwrap();
+            let _streams = connection.accept_bi().await.unwrap();
+            std::future::pending::<()>().await;
+        });
+
+        let timeout = Duration::from_millis(200);
+        // A bare quinn server, without ALPN
+        let client = OrzattyClient::builder().bind_addr("127.0.0.1:0".parse().unwrap()).alpn_protocols(Vec::new())
+            .add_root_cert(der).build().await.unwrap()
+            .with_auth_timeout(timeout);
+        let err = client.connect(addr, "localhost", "token").await.unwrap_err();
+        assert_eq!(err, ClientError::AuthTimeout(timeout));
+        assert!(err.is_transport());
+    }
+
+    #[cfg(feature = "test-util")]
+    #[tokio::test]
+    async fn test_rejected_zero_rtt_hello_is_resent() {
+        use crate::test_util::{bind_server, root_cert};
+
+        let client = OrzattyClient::builder().bind_addr("127.0.0.1:0".parse().unwrap()).zero_rtt(true)
+            .add_root_cert(root_cert()).build().await.unwrap()
+            .with_auth_attempts(1);
+        // The first server hands out a session ticket; the second cannot
+        // resume it, so it turns down the `Hello` sent as 0-RTT data
+        for _ in 0..2 {
+            let server = bind_server().unwrap().with_authenticator(orzatty_server::auth::Anonymous);
+            let addr = server.local_addr().unwrap();
+            let (connected, accepted) = tokio::join!(
+                client.connect(addr, "localhost", "token"),
+                async { server.accept().await.unwrap().authenticate().await },
+            );
+            connected.unwrap();
+            accepted.unwrap();
+        }
+    }
+}
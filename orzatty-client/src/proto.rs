@@ -0,0 +1,44 @@
+//! Protocol Buffers encoding for `FrameType::Protobuf` payloads (see
+//! [`EasyClient::send_proto`](crate::easy::EasyClient::send_proto)).
+//!
+//! The crate ships no `.proto` schemas: `T` is any message generated by
+//! `prost-build` (or derived with `prost::Message`), and both peers agree on
+//! which message a channel carries, as they do for [`json`](crate::json).
+
+use crate::{ClientError, Result};
+use prost::Message;
+
+/// Encodes `value` in the protobuf wire format.
+pub fn encode<T: Message>(value: &T) -> Vec<u8> {
+    value.encode_to_vec()
+}
+
+/// Decodes a received protobuf message into `T`.
+pub fn decode<T: Message + Default>(payload: &[u8]) -> Result<T> {
+    T::decode(payload)
+        .map_err(|e| ClientError::InvalidMessage(format!("Failed to decode {}: {}", std::any::type_name::<T>(), e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    struct Score {
+        #[prost(string, tag = "1")]
+        player: String,
+        #[prost(uint32, tag = "2")]
+        points: u32,
+    }
+
+    #[test]
+    fn test_proto_round_trip() {
+        let score = Score { player: "alice".into(), points: 3 };
+        let bytes = encode(&score);
+        assert_eq!(bytes, b"\x0a\x05alice\x10\x03");
+        assert_eq!(decode::<Score>(&bytes).unwrap(), score);
+
+        // Truncated inside the string field
+        assert!(matches!(decode::<Score>(&bytes[..4]), Err(ClientError::InvalidMessage(_))));
+    }
+}
@@ -0,0 +1,106 @@
+//! Client-side rate limiting primitives.
+
+use std::time::{Duration, Instant};
+
+/// Token bucket that hands out reservations instead of rejecting.
+///
+/// Each `reserve` takes one token; when the bucket is empty the balance goes
+/// negative and the returned delay tells the caller how long to wait before
+/// its frame is within budget. Burst size equals one second of rate.
+pub(crate) struct TokenBucket {
+    rate: f64,
+    capacity: f64,
+    tokens: f64,
+    last: Instant,
+}
+
+impl TokenBucket {
+    /// A bucket for `per_sec` frames a second; 0 is taken as 1, since a
+    /// bucket that never refills would hold its channel back for good.
+    pub(crate) fn new(per_sec: u32) -> Self {
+        let rate = per_sec.max(1) as f64;
+        Self {
+            rate,
+            capacity: rate,
+            tokens: rate,
+            last: Instant::now(),
+        }
+    }
+
+    /// Takes one token and returns how long the caller must wait to honor the rate.
+    pub(crate) fn reserve(&mut self) -> Duration {
+        self.reserve_at(Instant::now())
+    }
+
+    /// Whether a token can be taken without going over budget. Does not
+    /// take it: call [`take`](Self::take) once the frame is actually sent.
+    pub(crate) fn available(&mut self) -> bool {
+        self.available_at(Instant::now())
+    }
+
+    /// Takes one token, after [`available`](Self::available) said there was one.
+    pub(crate) fn take(&mut self) {
+        self.tokens -= 1.0;
+    }
+
+    fn reserve_at(&mut self, now: Instant) -> Duration {
+        self.refill(now);
+        self.tokens -= 1.0;
+
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / self.rate)
+        }
+    }
+
+    fn available_at(&mut self, now: Instant) -> bool {
+        self.refill(now);
+        self.tokens >= 1.0
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.last).as_secs_f64();
+        self.last = now;
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.capacity);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bucket_delays_after_burst() {
+        let mut bucket = TokenBucket::new(10);
+        let start = bucket.last;
+
+        // One second worth of burst is free
+        for _ in 0..10 {
+            assert_eq!(bucket.reserve_at(start), Duration::ZERO);
+        }
+
+        // Then each frame is spaced by 1/rate
+        assert_eq!(bucket.reserve_at(start), Duration::from_millis(100));
+        assert_eq!(bucket.reserve_at(start), Duration::from_millis(200));
+
+        // Refills over time
+        let later = start + Duration::from_secs(2);
+        assert_eq!(bucket.reserve_at(later), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_available_never_borrows() {
+        let mut bucket = TokenBucket::new(2);
+        let start = bucket.last;
+        for _ in 0..2 {
+            assert!(bucket.available_at(start));
+            bucket.take();
+        }
+        assert!(!bucket.available_at(start));
+        assert!(!bucket.available_at(start));
+
+        // A refused attempt leaves no debt behind
+        assert!(bucket.available_at(start + Duration::from_millis(500)));
+    }
+}
@@ -0,0 +1,74 @@
+//! Reconnection policy.
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+/// How [`OrzattyClient::connect_with_retry`](crate::OrzattyClient::connect_with_retry)
+/// spaces out its attempts.
+///
+/// The delay before retry `n` (0-based) is `base_delay * 2^n`, capped at
+/// `max_delay`, with the upper half randomized so that many clients dropped at
+/// once do not reconnect in lockstep.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Retries after the first attempt; `0` means a single attempt.
+    pub max_retries: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 5,
+            base_delay: Duration::from_millis(200),
+            max_delay: Duration::from_secs(10),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Delay to wait before retry number `retry` (0-based).
+    pub fn backoff(&self, retry: u32) -> Duration {
+        self.backoff_with(retry, random_unit())
+    }
+
+    fn backoff_with(&self, retry: u32, unit: f64) -> Duration {
+        let exp = self.base_delay.saturating_mul(2u32.saturating_pow(retry.min(31)));
+        let capped = exp.min(self.max_delay);
+        // Equal jitter: half fixed, half random
+        capped / 2 + capped.mul_f64(unit / 2.0)
+    }
+}
+
+/// A value in `[0, 1)` from the std per-process random hasher keys.
+fn random_unit() -> f64 {
+    let bits = RandomState::new().build_hasher().finish();
+    (bits >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_grows_and_caps() {
+        let policy = RetryPolicy {
+            max_retries: 10,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(1),
+        };
+
+        assert_eq!(policy.backoff_with(0, 0.0), Duration::from_millis(50));
+        assert_eq!(policy.backoff_with(0, 1.0), Duration::from_millis(100));
+        assert_eq!(policy.backoff_with(2, 0.0), Duration::from_millis(200));
+        assert_eq!(policy.backoff_with(9, 1.0), Duration::from_secs(1));
+        assert_eq!(policy.backoff_with(u32::MAX, 0.5), Duration::from_millis(750));
+
+        for retry in 0..12 {
+            let d = policy.backoff(retry);
+            assert!(d <= policy.max_delay);
+        }
+    }
+}
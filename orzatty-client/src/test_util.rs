@@ -0,0 +1,130 @@
+//! In-process client/server pairs for end-to-end tests.
+//!
+//! [`pair`] binds an [`OrzattyServer`] on a free localhost port with a
+//! throwaway self-signed certificate, connects an [`EasyClient`] to it and
+//! hands back both ends of the authenticated connection, so a test can
+//! exercise auth, framing and channel routing without a server binary:
+//!
+//! ```ignore
+//! let Loopback { client, mut server, .. } = test_util::pair().await?;
+//! server.on(1, |_header, payload| Some(payload)); // echo channel 1
+//! tokio::spawn(server.run());
+//! let reply = client.request(1, b"ping", Duration::from_secs(1)).await?;
+//! assert_eq!(&reply[..], b"ping");
+//! ```
+
+use std::sync::OnceLock;
+
+use futures::future::{self, Either};
+use orzatty_server::auth::Anonymous;
+use orzatty_server::dev_cert::{generate_dev_cert, CertChain};
+use orzatty_server::{OrzattyServer, ServerConnection};
+
+use crate::easy::{EasyClient, EasyClientConfig};
+use crate::{ClientError, OrzattyClient, Result};
+
+/// Token presented by the client of [`pair`].
+pub const TEST_TOKEN: &str = "test-token";
+
+/// Both ends of one authenticated connection.
+pub struct Loopback {
+    /// The client, with its actors running.
+    pub client: EasyClient,
+    /// The server's end, authenticated but not yet serving: register
+    /// handlers, then drive it with [`ServerConnection::run`].
+    pub server: ServerConnection,
+    /// The listening server. Keep it alive for as long as the connection is
+    /// used; more clients can connect to its
+    /// [`local_addr`](OrzattyServer::local_addr).
+    pub listener: OrzattyServer,
+}
+
+/// The self-signed certificate, and its private key, presented by every
+/// [`bind_server`] of this process. Generated once.
+fn test_cert() -> &'static (CertChain, rustls::PrivateKey) {
+    static CERT: OnceLock<(CertChain, rustls::PrivateKey)> = OnceLock::new();
+    CERT.get_or_init(|| {
+        generate_dev_cert(vec!["localhost".into(), "127.0.0.1".into()], 1)
+            .expect("Cannot generate test certificate")
+    })
+}
+
+/// The certificate [`bind_server`] presents, in DER, for a client of your own
+/// to trust with [`OrzattyClientBuilder::add_root_cert`](crate::OrzattyClientBuilder::add_root_cert).
+pub fn root_cert() -> &'static [u8] {
+    &test_cert().0[0].0
+}
+
+/// A server on `127.0.0.1` at a free port, with a self-signed certificate
+/// for `localhost` and `127.0.0.1` (see [`root_cert`]). Like any
+/// [`OrzattyServer`] it refuses every client until an authenticator is added.
+pub fn bind_server() -> Result<OrzattyServer> {
+    let (chain, key) = test_cert();
+    OrzattyServer::bind("127.0.0.1:0".parse().unwrap(), chain.clone(), key.clone())
+    .map_err(|e| ClientError::Config(format!("Cannot bind test server: {}", e)))
+}
+
+/// A client connected with [`TEST_TOKEN`] to a server that lets everyone in
+/// as `anonymous`.
+pub async fn pair() -> Result<Loopback> {
+    pair_with(bind_server()?.with_authenticator(Anonymous), TEST_TOKEN, EasyClientConfig::default()).await
+}
+
+/// Connects a client configured with `config` to `listener`, presenting
+/// `token`, and accepts it on the server side. The client trusts
+/// [`root_cert`] and verifies the server's certificate against it.
+///
+/// Fails with the client's error if either side refuses, e.g.
+/// [`ClientError::AuthRejected`] for a token the listener's authenticators
+/// turn down.
+pub async fn pair_with(listener: OrzattyServer, token: &str, config: EasyClientConfig) -> Result<Loopback> {
+    let addr = listener.local_addr()
+        .map_err(|e| ClientError::Config(format!("Test server has no address: {}", e)))?
+        .to_string();
+    let client = OrzattyClient::builder().add_root_cert(root_cert()).build().await?;
+    let connect = Box::pin(EasyClient::connect_with_client(&client, &addr, token, config));
+    let accept = Box::pin(async {
+        let pending = listener.accept().await.ok_or_else(|| anyhow::anyhow!("Test server closed"))?;
+        pending.authenticate().await
+    });
+    // A client that fails before reaching the server would leave `accept` waiting forever
+    let (client, server) = match future::select(connect, accept).await {
+        Either::Left((client, accept)) => (client?, accept.await),
+        Either::Right((server, connect)) => (connect.await?, server),
+    };
+    let server = server.map_err(|e| ClientError::ConnectFailed(format!("Test server refused the client: {}", e)))?;
+    Ok(Loopback { client, server, listener })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use orzatty_server::AuthDecision;
+
+    #[tokio::test]
+    async fn test_pair_echoes_through_channel_handler() {
+        let Loopback { client, mut server, listener: _listener } = pair().await.unwrap();
+        assert_eq!(server.identity(), "anonymous");
+        assert_eq!(client.alpn_protocol(), Some(orzatty_core::DEFAULT_ALPN.to_vec()));
+        assert_eq!(server.alpn_protocol(), client.alpn_protocol());
+        server.on(1, |_header, payload| Some(payload));
+        tokio::spawn(server.run());
+
+        let reply = client.request(1, b"ping", Duration::from_secs(5)).await.unwrap();
+        assert_eq!(&reply[..], b"ping");
+        // Channels without a server handler get no reply
+        let silent = client.request(2, b"ping", Duration::from_millis(200)).await;
+        assert_eq!(silent, Err(ClientError::RequestTimeout(Duration::from_millis(200))));
+    }
+
+    #[tokio::test]
+    async fn test_pair_with_rejected_token() {
+        let listener = bind_server().unwrap().with_auth_fn(|token: &str| match token {
+            "good" => AuthDecision::Accept { identity: "tester".into() },
+            _ => AuthDecision::Reject { reason: "bad token".into() },
+        });
+        let err = pair_with(listener, "bad", EasyClientConfig::default()).await.err().unwrap();
+        assert!(matches!(err, ClientError::AuthRejected(_)), "{:?}", err);
+    }
+}
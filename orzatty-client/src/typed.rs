@@ -0,0 +1,83 @@
+//! rkyv encoding for typed channels (see [`EasyClient::send_typed`](crate::easy::EasyClient::send_typed)).
+//!
+//! Also usable directly, e.g. to decode payloads received through `on_any_ctx`.
+
+use crate::{ClientError, Result};
+use rkyv::de::deserializers::SharedDeserializeMap;
+use rkyv::ser::serializers::AllocSerializer;
+use rkyv::validation::validators::DefaultValidator;
+use rkyv::{AlignedVec, Archive, CheckBytes, Deserialize, Serialize};
+
+/// Serializes `value` into an rkyv archive.
+pub fn encode<T>(value: &T) -> Result<AlignedVec>
+where
+    T: Serialize<AllocSerializer<256>>,
+{
+    rkyv::to_bytes::<_, 256>(value)
+        .map_err(|e| ClientError::InvalidMessage(format!("Failed to serialize {}: {:?}", std::any::type_name::<T>(), e)))
+}
+
+/// Validates a received archive and deserializes it.
+///
+/// The archive is checked with `check_bytes` first, so a malformed or hostile
+/// payload yields an error instead of undefined behavior.
+pub fn decode<T>(payload: &[u8]) -> Result<T>
+where
+    T: Archive,
+    T::Archived: for<'a> CheckBytes<DefaultValidator<'a>> + Deserialize<T, SharedDeserializeMap>,
+{
+    // Copy into an aligned buffer: the payload may sit at any offset
+    let mut aligned = AlignedVec::with_capacity(payload.len());
+    aligned.extend_from_slice(payload);
+    rkyv::from_bytes::<T>(&aligned)
+        .map_err(|e| ClientError::InvalidMessage(format!("Failed to decode {}: {:?}", std::any::type_name::<T>(), e)))
+}
+
+/// Validates `bytes` as an archived `T` and returns a reference into them,
+/// skipping deserialization and copies: the zero-copy read path for hot
+/// messages like `PlayerUpdate`.
+///
+/// The result borrows `bytes`, so the buffer holding the payload must stay
+/// alive (and unmodified) for as long as the reference is used; copy fields
+/// out, or [`decode`], to keep data beyond that. `bytes` must also be aligned
+/// for `T::Archived`: a misaligned slice fails validation rather than
+/// being copied.
+pub fn access_archived<T>(bytes: &[u8]) -> Result<&T::Archived>
+where
+    T: Archive,
+    T::Archived: for<'a> CheckBytes<DefaultValidator<'a>>,
+{
+    rkyv::check_archived_root::<T>(bytes)
+        .map_err(|e| ClientError::InvalidMessage(format!("Invalid archived {}: {}", std::any::type_name::<T>(), e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use orzatty_core::protocol::PlayerUpdate;
+
+    #[test]
+    fn test_player_update_round_trip() {
+        let update = PlayerUpdate { id: 7, pos_x: 1.5, pos_y: -2.0, velocity: [0.1, 0.2, 0.3], status: 1 };
+        let bytes = encode(&update).unwrap();
+        // Unaligned copy, as a payload sliced out of a read buffer may be
+        let mut shifted = vec![0u8];
+        shifted.extend_from_slice(&bytes);
+        assert_eq!(decode::<PlayerUpdate>(&shifted[1..]).unwrap(), update);
+
+        assert!(decode::<PlayerUpdate>(&bytes[..bytes.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_access_archived_in_place() {
+        let update = PlayerUpdate { id: 7, pos_x: 1.5, pos_y: -2.0, velocity: [0.1, 0.2, 0.3], status: 1 };
+        let bytes = encode(&update).unwrap();
+        let archived = access_archived::<PlayerUpdate>(&bytes).unwrap();
+        assert_eq!((archived.id, archived.pos_x, archived.status), (7, 1.5, 1));
+
+        // Unlike `decode`, no realigning copy is made
+        let mut shifted = vec![0u8];
+        shifted.extend_from_slice(&bytes);
+        assert!(access_archived::<PlayerUpdate>(&shifted[1..]).is_err());
+    }
+}
@@ -0,0 +1,98 @@
+//! Heap allocations per decoded frame, counted by a wrapping global allocator.
+//!
+//! Complements the `framing` timings: a consumer that keeps its payloads (no
+//! `recycle`) should cost no more than splitting them off the read buffer,
+//! and one that hands them back should settle at no allocations at all.
+//! Run with `cargo bench -p orzatty-core --bench allocations`.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::hint::black_box;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use bytes::BytesMut;
+use orzatty_core::frame::FrameBuilder;
+use orzatty_core::FrameDecoder;
+
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static ALLOCATED_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED_BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED_BYTES.fetch_add(new_size, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+/// Bytes `Framer` reads from the stream at a time.
+const READ_SIZE: usize = 4096;
+
+/// Frames per measured stream.
+const FRAMES: usize = 1024;
+
+/// Streams decoded before measuring, so a recycling decoder's pool is warm.
+const WARMUP: usize = 4;
+
+/// `count` frames of `payload_len` bytes each, back to back.
+fn stream(payload_len: usize, count: usize) -> Vec<u8> {
+    let payload = vec![0xAB; payload_len];
+    let frame = FrameBuilder::new(&payload).channel(1).to_bytes().unwrap();
+    frame.repeat(count)
+}
+
+/// Decodes `bytes` in [`READ_SIZE`] reads. Without `recycle`, payloads are
+/// held in `kept` until the stream is done, as a consumer queueing them would.
+fn decode(decoder: &mut FrameDecoder, bytes: &[u8], recycle: bool, kept: &mut Vec<BytesMut>) {
+    for read in bytes.chunks(READ_SIZE) {
+        decoder.push(read);
+        while let Some((_header, payload)) = decoder.next_frame().unwrap() {
+            if recycle {
+                decoder.recycle(black_box(payload));
+            } else {
+                kept.push(payload);
+            }
+        }
+    }
+    black_box(&kept);
+    kept.clear();
+}
+
+/// Allocations and bytes allocated while decoding one stream.
+fn measure(decoder: &mut FrameDecoder, bytes: &[u8], recycle: bool) -> (usize, usize) {
+    let mut kept = Vec::with_capacity(FRAMES);
+    for _ in 0..WARMUP {
+        decode(decoder, bytes, recycle, &mut kept);
+    }
+    let (count, size) = (ALLOCATIONS.load(Ordering::Relaxed), ALLOCATED_BYTES.load(Ordering::Relaxed));
+    decode(decoder, bytes, recycle, &mut kept);
+    (ALLOCATIONS.load(Ordering::Relaxed) - count, ALLOCATED_BYTES.load(Ordering::Relaxed) - size)
+}
+
+fn main() {
+    println!("{:>8}  {:>9}  {:>12}  {:>12}", "payload", "consumer", "allocs/frame", "bytes/frame");
+    for payload_len in [8, 63, 256, 1024, 4096, 16 * 1024] {
+        let bytes = stream(payload_len, FRAMES);
+        for (name, recycle) in [("keeps", false), ("recycles", true)] {
+            let (count, size) = measure(&mut FrameDecoder::new(), &bytes, recycle);
+            println!(
+                "{:>8}  {:>9}  {:>12.2}  {:>12.1}",
+                payload_len, name, count as f64 / FRAMES as f64, size as f64 / FRAMES as f64,
+            );
+        }
+    }
+}
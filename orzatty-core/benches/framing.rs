@@ -0,0 +1,120 @@
+//! Framing throughput, without a socket.
+//!
+//! `Framer` is a read loop around `FrameDecoder`, so feeding the decoder
+//! chunks of [`READ_SIZE`] bytes (what `Framer` asks the stream for) measures
+//! the parser as the QUIC path drives it. Run with `cargo bench -p orzatty-core`.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use orzatty_core::frame::{FrameBuilder, FrameFlags};
+use orzatty_core::FrameDecoder;
+
+/// Bytes `Framer` reads from the stream at a time.
+const READ_SIZE: usize = 4096;
+
+/// Roughly one QUIC packet's worth of stream data.
+const PACKET_SIZE: usize = 1200;
+
+/// Frames per benchmarked stream.
+const FRAMES: usize = 1024;
+
+/// `count` frames of `payload_len` bytes each, back to back.
+fn stream(payload_len: usize, count: usize) -> Vec<u8> {
+    let payload = vec![0xAB; payload_len];
+    let frame = FrameBuilder::new(&payload).channel(1).to_bytes().unwrap();
+    frame.repeat(count)
+}
+
+/// Feeds `bytes` to `decoder` in `chunk`-sized reads, draining complete
+/// frames after each. Returns how many frames came out.
+fn decode(decoder: &mut FrameDecoder, bytes: &[u8], chunk: usize, recycle: bool) -> usize {
+    let mut frames = 0;
+    for read in bytes.chunks(chunk) {
+        decoder.push(read);
+        while let Some((header, payload)) = decoder.next_frame().unwrap() {
+            black_box(header);
+            if recycle {
+                decoder.recycle(payload);
+            }
+            frames += 1;
+        }
+    }
+    frames
+}
+
+/// Many frames per read: the small-frame path, where header parsing and
+/// per-frame allocation dominate.
+fn coalesced(c: &mut Criterion) {
+    let mut group = c.benchmark_group("coalesced");
+    group.throughput(Throughput::Elements(FRAMES as u64));
+    for payload_len in [8, 32, 63, 256, 1024] {
+        let bytes = stream(payload_len, FRAMES);
+        group.bench_with_input(BenchmarkId::new("fresh", payload_len), &bytes, |b, bytes| {
+            b.iter(|| decode(&mut FrameDecoder::new(), bytes, READ_SIZE, false));
+        });
+        // A long-lived reader recycling payloads, as `EasyClient` does
+        let mut decoder = FrameDecoder::new();
+        group.bench_with_input(BenchmarkId::new("recycled", payload_len), &bytes, |b, bytes| {
+            b.iter(|| decode(&mut decoder, bytes, READ_SIZE, true));
+        });
+    }
+    group.finish();
+}
+
+/// Frames larger than a read, arriving a packet at a time.
+fn split(c: &mut Criterion) {
+    let mut group = c.benchmark_group("split");
+    for payload_len in [16 * 1024, 256 * 1024] {
+        let bytes = stream(payload_len, 16);
+        group.throughput(Throughput::Bytes(bytes.len() as u64));
+        let mut decoder = FrameDecoder::new();
+        group.bench_with_input(BenchmarkId::from_parameter(payload_len), &bytes, |b, bytes| {
+            b.iter(|| decode(&mut decoder, bytes, PACKET_SIZE, true));
+        });
+    }
+    group.finish();
+}
+
+/// One message sent as `FRAGMENTED` frames and reassembled by the decoder.
+fn reassembled(c: &mut Criterion) {
+    let mut group = c.benchmark_group("reassembled");
+    for fragments in [4, 64] {
+        let piece = vec![0xCD; 1024];
+        let mut bytes = Vec::new();
+        for i in 0..fragments {
+            let mut builder = FrameBuilder::new(&piece).channel(1).stream(7);
+            if i + 1 < fragments {
+                builder = builder.flags(FrameFlags::FRAGMENTED);
+            }
+            bytes.extend(builder.to_bytes().unwrap());
+        }
+        group.throughput(Throughput::Bytes(bytes.len() as u64));
+        let mut decoder = FrameDecoder::new();
+        group.bench_with_input(BenchmarkId::from_parameter(fragments), &bytes, |b, bytes| {
+            b.iter(|| assert_eq!(decode(&mut decoder, bytes, READ_SIZE, true), 1));
+        });
+    }
+    group.finish();
+}
+
+/// Encoding small frames into a reused buffer, as the writer batches them.
+fn encode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("encode");
+    group.throughput(Throughput::Elements(FRAMES as u64));
+    for payload_len in [8, 32, 63] {
+        let payload = vec![0xAB; payload_len];
+        let mut buf = Vec::with_capacity(FRAMES * (payload_len + 8));
+        group.bench_with_input(BenchmarkId::from_parameter(payload_len), &payload, |b, payload| {
+            b.iter(|| {
+                buf.clear();
+                for _ in 0..FRAMES {
+                    FrameBuilder::new(payload).channel(1).header().encode_with_payload(payload, &mut buf).unwrap();
+                }
+                black_box(buf.len())
+            });
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, coalesced, split, reassembled, encode);
+criterion_main!(benches);
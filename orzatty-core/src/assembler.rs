@@ -0,0 +1,300 @@
+//! Indexed multi-frame messages, reassembled whatever order they arrive in.
+//!
+//! `FRAGMENTED` frames are joined by the decoder in arrival order, so the
+//! fragments of one message must not interleave with another message on the
+//! same channel and stream. Indexed messages lift that restriction: each
+//! fragment opens with a [`FragmentHeader`] naming its message, its position
+//! and the fragment count, and an [`Assembler`] puts the pieces back together
+//! as they come, e.g. spread over datagrams or several streams.
+//!
+//! The first header byte has no free flag bit, so the fragment header travels
+//! at the start of the payload, as three varints. Both peers agree out of
+//! band on which channels carry indexed messages, just as for sequenced
+//! channels (see [`sequence`](crate::sequence)).
+//!
+//! The assembler keeps no clock: callers pass the current time as a
+//! `Duration` since any fixed instant, so it also runs in `no_std` builds.
+
+extern crate alloc;
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::time::Duration;
+
+use crate::decoder::DEFAULT_MAX_FRAME_LEN;
+use crate::error::Error;
+use crate::frame::{decode_varint, encode_varint, FrameHeader, MAX_VARINT};
+
+/// Default for [`Assembler::with_timeout`].
+pub const DEFAULT_REASSEMBLY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Largest encoded [`FragmentHeader`]: three 8-byte varints.
+pub const MAX_FRAGMENT_HEADER_LEN: usize = 3 * 8;
+
+/// The prefix of each fragment of an indexed message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FragmentHeader {
+    /// Tells apart the messages in flight on one channel and stream.
+    pub message_id: u64,
+    /// Position of the fragment in its message, from `0`.
+    pub index: u32,
+    /// Number of fragments in the message; at least 1.
+    pub total: u32,
+}
+
+impl FragmentHeader {
+    /// Encodes the header into `buf` and returns the bytes written.
+    pub fn encode(&self, buf: &mut [u8]) -> Result<usize, Error> {
+        if self.message_id > MAX_VARINT {
+            return Err(Error::InvalidVarInt);
+        }
+        let mut offset = encode_varint(self.message_id, buf)?;
+        offset += encode_varint(self.index as u64, &mut buf[offset..])?;
+        offset += encode_varint(self.total as u64, &mut buf[offset..])?;
+        Ok(offset)
+    }
+
+    /// Appends the header followed by `data` to `out`: a complete fragment
+    /// payload, ready to be framed.
+    pub fn encode_with_data(&self, data: &[u8], out: &mut Vec<u8>) -> Result<(), Error> {
+        let mut head = [0u8; MAX_FRAGMENT_HEADER_LEN];
+        let n = self.encode(&mut head)?;
+        out.reserve(n + data.len());
+        out.extend_from_slice(&head[..n]);
+        out.extend_from_slice(data);
+        Ok(())
+    }
+
+    /// Decodes the header at the start of a fragment payload. Returns it and
+    /// its length; the fragment's data follows.
+    ///
+    /// Fails with [`Error::InvalidFragment`] if `index` is not below `total`
+    /// or either does not fit a `u32`.
+    pub fn decode(buf: &[u8]) -> Result<(Self, usize), Error> {
+        let (message_id, a) = decode_varint(buf)?;
+        let (index, b) = decode_varint(&buf[a..])?;
+        let (total, c) = decode_varint(&buf[a + b..])?;
+        let (Ok(index), Ok(total)) = (u32::try_from(index), u32::try_from(total)) else {
+            return Err(Error::InvalidFragment);
+        };
+        if index >= total {
+            return Err(Error::InvalidFragment);
+        }
+        Ok((Self { message_id, index, total }, a + b + c))
+    }
+}
+
+/// Splits `data` into the fragments of message `message_id`, each carrying
+/// at most `max_len` bytes of it. Empty data still makes one (empty)
+/// fragment.
+///
+/// Fails with [`Error::InvalidFragment`] if `max_len` is zero or the message
+/// would need more than `u32::MAX` fragments.
+pub fn split(message_id: u64, data: &[u8], max_len: usize) -> Result<impl Iterator<Item = (FragmentHeader, &[u8])>, Error> {
+    if max_len == 0 {
+        return Err(Error::InvalidFragment);
+    }
+    let total = u32::try_from(data.len().div_ceil(max_len).max(1)).map_err(|_| Error::InvalidFragment)?;
+    let pieces = data.chunks(max_len).chain(data.is_empty().then_some(data));
+    Ok(pieces.enumerate().map(move |(index, piece)| {
+        (FragmentHeader { message_id, index: index as u32, total }, piece)
+    }))
+}
+
+/// A message still missing fragments.
+struct Partial {
+    total: u32,
+    // When its first fragment arrived
+    started: Duration,
+    len: usize,
+    fragments: BTreeMap<u32, Vec<u8>>,
+}
+
+/// Collects the fragments of indexed messages and returns each message once
+/// all of its fragments are in.
+///
+/// Messages are keyed by channel, `stream_id` and message id, so any number
+/// can be in flight at once. One that has not completed within the timeout
+/// of its first fragment is dropped and reported with
+/// [`Error::ReassemblyTimeout`].
+pub struct Assembler {
+    timeout: Duration,
+    max_message_len: usize,
+    partial: BTreeMap<(u32, u64, u64), Partial>,
+}
+
+impl Assembler {
+    pub fn new() -> Self {
+        Self {
+            timeout: DEFAULT_REASSEMBLY_TIMEOUT,
+            max_message_len: DEFAULT_MAX_FRAME_LEN,
+            partial: BTreeMap::new(),
+        }
+    }
+
+    /// Sets how long a message may take to complete, from its first
+    /// fragment.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sets the largest reassembled message. A message growing past it is
+    /// dropped with [`Error::FrameTooLarge`].
+    pub fn with_max_message_len(mut self, bytes: usize) -> Self {
+        self.max_message_len = bytes;
+        self
+    }
+
+    /// Adds the fragment in the payload of a frame with `header`. Returns the
+    /// message id and the whole message once this was its last missing
+    /// fragment, `None` until then.
+    ///
+    /// Fragments arrive in any order; duplicates are ignored. A fragment for
+    /// a message that is past its timeout drops the message and fails with
+    /// [`Error::ReassemblyTimeout`]; one whose `total` disagrees with earlier
+    /// fragments fails with [`Error::InvalidFragment`] and leaves the message
+    /// as it was.
+    pub fn push(&mut self, header: &FrameHeader, payload: &[u8], now: Duration) -> Result<Option<(u64, Vec<u8>)>, Error> {
+        let (fragment, head_len) = FragmentHeader::decode(payload)?;
+        let data = &payload[head_len..];
+        let key = (header.channel_id, header.stream_id, fragment.message_id);
+
+        let partial = self.partial.entry(key).or_insert_with(|| Partial {
+            total: fragment.total,
+            started: now,
+            len: 0,
+            fragments: BTreeMap::new(),
+        });
+        if now.saturating_sub(partial.started) > self.timeout {
+            self.partial.remove(&key);
+            return Err(Self::timed_out(key));
+        }
+        if partial.total != fragment.total {
+            return Err(Error::InvalidFragment);
+        }
+        if partial.fragments.contains_key(&fragment.index) {
+            return Ok(None);
+        }
+        let len = partial.len + data.len();
+        if len > self.max_message_len {
+            self.partial.remove(&key);
+            return Err(Error::FrameTooLarge { len: len as u64, max: self.max_message_len });
+        }
+        partial.len = len;
+        partial.fragments.insert(fragment.index, data.to_vec());
+        if partial.fragments.len() < partial.total as usize {
+            return Ok(None);
+        }
+
+        let Some(partial) = self.partial.remove(&key) else { return Ok(None) };
+        let mut message = Vec::with_capacity(partial.len);
+        for piece in partial.fragments.into_values() {
+            message.extend_from_slice(&piece);
+        }
+        Ok(Some((fragment.message_id, message)))
+    }
+
+    /// Drops one message that has been waiting longer than the timeout and
+    /// fails with [`Error::ReassemblyTimeout`] naming it; `Ok(())` once none
+    /// is left. Call it periodically, until it succeeds, so messages whose
+    /// fragments never arrive do not pile up.
+    pub fn evict_expired(&mut self, now: Duration) -> Result<(), Error> {
+        let expired = self.partial.iter()
+            .find(|(_, partial)| now.saturating_sub(partial.started) > self.timeout)
+            .map(|(key, _)| *key);
+        match expired {
+            Some(key) => {
+                self.partial.remove(&key);
+                Err(Self::timed_out(key))
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Number of messages still missing fragments.
+    pub fn pending(&self) -> usize {
+        self.partial.len()
+    }
+
+    fn timed_out((channel_id, stream_id, message_id): (u32, u64, u64)) -> Error {
+        Error::ReassemblyTimeout { channel_id, stream_id, message_id }
+    }
+}
+
+impl Default for Assembler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::FrameBuilder;
+
+    fn payloads(message_id: u64, data: &[u8], max_len: usize) -> Vec<Vec<u8>> {
+        split(message_id, data, max_len).unwrap()
+            .map(|(fragment, piece)| {
+                let mut out = Vec::new();
+                fragment.encode_with_data(piece, &mut out).unwrap();
+                out
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_interleaved_messages_reassembled() {
+        let header = FrameBuilder::new(&[]).channel(1).stream(4).header();
+        let mut assembler = Assembler::new();
+        let first = payloads(7, b"hello world", 4);
+        let second = payloads(8, b"abcdef", 4);
+        assert_eq!(first.len(), 3);
+
+        let now = Duration::ZERO;
+        assert_eq!(assembler.push(&header, &first[2], now), Ok(None));
+        assert_eq!(assembler.push(&header, &second[1], now), Ok(None));
+        assert_eq!(assembler.push(&header, &first[0], now), Ok(None));
+        assert_eq!(assembler.push(&header, &first[0], now), Ok(None)); // Duplicate
+        assert_eq!(assembler.push(&header, &second[0], now), Ok(Some((8, b"abcdef".to_vec()))));
+        assert_eq!(assembler.pending(), 1);
+        assert_eq!(assembler.push(&header, &first[1], now), Ok(Some((7, b"hello world".to_vec()))));
+        assert_eq!(assembler.pending(), 0);
+
+        // A single-fragment message, even an empty one, completes at once
+        assert_eq!(assembler.push(&header, &payloads(9, b"", 4)[0], now), Ok(Some((9, Vec::new()))));
+    }
+
+    #[test]
+    fn test_stalled_messages_evicted() {
+        let header = FrameBuilder::new(&[]).channel(2).stream(0).header();
+        let mut assembler = Assembler::new().with_timeout(Duration::from_secs(1));
+        let message = payloads(1, b"0123456789", 5);
+        let late = payloads(2, b"0123456789", 5);
+
+        assembler.push(&header, &message[0], Duration::ZERO).unwrap();
+        assembler.push(&header, &late[0], Duration::from_millis(900)).unwrap();
+        assert_eq!(assembler.evict_expired(Duration::from_millis(1500)),
+            Err(Error::ReassemblyTimeout { channel_id: 2, stream_id: 0, message_id: 1 }));
+        assert_eq!(assembler.evict_expired(Duration::from_millis(1500)), Ok(()));
+        assert_eq!(assembler.push(&header, &late[1], Duration::from_secs(3)),
+            Err(Error::ReassemblyTimeout { channel_id: 2, stream_id: 0, message_id: 2 }));
+        assert_eq!(assembler.pending(), 0);
+    }
+
+    #[test]
+    fn test_malformed_fragments_rejected() {
+        let mut buf = [0u8; MAX_FRAGMENT_HEADER_LEN];
+        let n = FragmentHeader { message_id: 1, index: 2, total: 2 }.encode(&mut buf).unwrap();
+        assert_eq!(FragmentHeader::decode(&buf[..n]), Err(Error::InvalidFragment));
+
+        let header = FrameBuilder::new(&[]).channel(1).header();
+        let mut assembler = Assembler::new().with_max_message_len(6);
+        assembler.push(&header, &payloads(3, b"abcd", 2)[0], Duration::ZERO).unwrap();
+        // Same message id, different fragment count
+        assert_eq!(assembler.push(&header, &payloads(3, b"abcdef", 2)[1], Duration::ZERO), Err(Error::InvalidFragment));
+        assert_eq!(assembler.push(&header, &payloads(4, b"abcdefgh", 4)[1], Duration::ZERO), Ok(None));
+        assert_eq!(assembler.push(&header, &payloads(4, b"abcdefgh", 4)[0], Duration::ZERO),
+            Err(Error::FrameTooLarge { len: 8, max: 6 }));
+        assert!(split(1, b"x", 0).is_err());
+    }
+}
@@ -1,19 +1,120 @@
+use bitflags::bitflags;
 use rkyv::{Archive, Deserialize, Serialize};
 extern crate alloc;
 use alloc::string::String;
+use alloc::vec::Vec;
+
+/// Length of the nonce in [`AuthMessage::Challenge`].
+pub const NONCE_LEN: usize = 32;
+
+bitflags! {
+    /// Optional protocol features, negotiated during the auth handshake.
+    ///
+    /// The client lists what it supports in [`AuthMessage::Hello`]; the server
+    /// answers with the intersection in [`AuthMessage::Ok`], and neither side
+    /// uses a feature outside it. Unknown bits are ignored, so new features
+    /// can be added without a version bump.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct Capabilities: u32 {
+        /// Inflates `FrameType::Compressed` payloads.
+        const COMPRESSION = 1 << 0;
+        /// Sends and receives unreliable datagram frames.
+        const DATAGRAMS = 1 << 1;
+        /// Reassembles `FRAGMENTED` messages.
+        const FRAGMENTATION = 1 << 2;
+        /// Verifies `CHECKSUM` trailers.
+        const CHECKSUM = 1 << 3;
+    }
+}
+
+impl Capabilities {
+    /// The features both sides support: what the server sends back in `Ok`.
+    pub fn negotiate(self, peer: Capabilities) -> Capabilities {
+        self & peer
+    }
+}
+
+/// What the server tells a client about its session in [`AuthMessage::Ok`].
+#[derive(Archive, Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[archive(check_bytes)]
+pub struct SessionInfo {
+    /// The identity the server authenticated the client as.
+    pub identity: String,
+    /// The client's id on the server, unique among its connections.
+    pub connection_id: u64,
+    /// Application-defined session context from the server (e.g. an
+    /// assigned region); empty unless the server sets some.
+    pub metadata: Vec<u8>,
+}
 
 #[derive(Archive, Serialize, Deserialize, Debug, PartialEq)]
 #[archive(check_bytes)]
 #[repr(C)]
 pub enum AuthMessage {
-    /// Client sends this to authenticate.
+    /// Client sends this to authenticate. The server refuses versions other
+    /// than its own [`PROTOCOL_VERSION`](crate::PROTOCOL_VERSION) with `Fail`.
     Hello { 
         token: String, 
+        protocol_version: u16,
+        /// [`Capabilities`] bits the client supports.
+        capabilities: u32,
+        /// Application-defined session context (e.g. user id, region,
+        /// client version), handed to the server as is; may be empty.
+        metadata: Vec<u8>,
     },
     /// Server responds with this if authentication succeeds.
-    Ok,
+    Ok {
+        /// The negotiated [`Capabilities`] bits, in effect for the session.
+        capabilities: u32,
+        session: SessionInfo,
+    },
     /// Server responds with this if authentication fails.
     Fail { 
         reason: String, 
     },
+    /// Server may answer `Hello` with this instead of `Ok`/`Fail`, asking the
+    /// client to prove it holds the shared secret.
+    Challenge {
+        nonce: [u8; NONCE_LEN],
+    },
+    /// Client answer to `Challenge`: HMAC-SHA256 of the nonce keyed with the
+    /// shared secret. The server then sends `Ok` or `Fail`.
+    Proof {
+        hmac: Vec<u8>,
+    },
+}
+
+/// Computes the [`AuthMessage::Proof`] for a challenge nonce.
+#[cfg(feature = "auth-hmac")]
+pub fn challenge_proof(secret: &[u8], nonce: &[u8; NONCE_LEN]) -> Vec<u8> {
+    use hmac::Mac;
+    let mut mac = hmac::Hmac::<sha2::Sha256>::new_from_slice(secret)
+        .expect("HMAC accepts keys of any length");
+    mac.update(nonce);
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// Checks a proof against the nonce in constant time.
+#[cfg(feature = "auth-hmac")]
+pub fn verify_proof(secret: &[u8], nonce: &[u8; NONCE_LEN], proof: &[u8]) -> bool {
+    use hmac::Mac;
+    let mut mac = hmac::Hmac::<sha2::Sha256>::new_from_slice(secret)
+        .expect("HMAC accepts keys of any length");
+    mac.update(nonce);
+    mac.verify_slice(proof).is_ok()
+}
+
+#[cfg(all(test, feature = "auth-hmac"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_proof_verifies_only_with_same_secret_and_nonce() {
+        let nonce = [7u8; NONCE_LEN];
+        let proof = challenge_proof(b"secret", &nonce);
+        assert_eq!(proof.len(), 32);
+        assert!(verify_proof(b"secret", &nonce, &proof));
+        assert!(!verify_proof(b"other", &nonce, &proof));
+        assert!(!verify_proof(b"secret", &[8u8; NONCE_LEN], &proof));
+    }
 }
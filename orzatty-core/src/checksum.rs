@@ -0,0 +1,64 @@
+//! CRC32 trailers for frames flagged [`FrameFlags::CHECKSUM`](crate::frame::FrameFlags::CHECKSUM).
+//!
+//! QUIC already protects every byte in transit; the trailer is for frames
+//! that cross a non-QUIC hop (relay, proxy, log replay) on the way.
+
+use crate::error::Error;
+
+/// Size of the trailer appended after the payload.
+pub const CHECKSUM_LEN: usize = 4;
+
+// Lookup table for the reflected IEEE polynomial (as in zlib/Ethernet)
+const TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// CRC-32/IEEE of `data`.
+pub fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0u32, |crc, &b| TABLE[((crc ^ b as u32) & 0xFF) as usize] ^ (crc >> 8))
+}
+
+/// The big-endian trailer for `payload`.
+pub fn trailer(payload: &[u8]) -> [u8; CHECKSUM_LEN] {
+    crc32(payload).to_be_bytes()
+}
+
+/// Verifies the trailer at the end of `body` and returns the payload before it.
+pub fn strip(body: &[u8]) -> Result<&[u8], Error> {
+    let split = body.len().checked_sub(CHECKSUM_LEN).ok_or(Error::ChecksumMismatch)?;
+    let (payload, sum) = body.split_at(split);
+    if sum != trailer(payload) {
+        return Err(Error::ChecksumMismatch);
+    }
+    Ok(payload)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crc32_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(crc32(b""), 0);
+
+        let mut body = b"payload".to_vec();
+        body.extend_from_slice(&trailer(b"payload"));
+        assert_eq!(strip(&body), Ok(&b"payload"[..]));
+        body[0] ^= 1;
+        assert_eq!(strip(&body), Err(Error::ChecksumMismatch));
+        assert_eq!(strip(&[0, 0]), Err(Error::ChecksumMismatch));
+    }
+}
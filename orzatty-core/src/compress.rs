@@ -0,0 +1,72 @@
+//! zstd compression for `FrameType::Compressed` payloads.
+//!
+//! A compressed payload is the original frame type byte followed by the zstd
+//! stream: `[original_type_byte][zstd bytes]`. The header's `frame_type` is
+//! `Compressed` and its `length` covers both.
+
+use crate::frame::{FrameHeader, FrameType};
+use std::io;
+
+/// zstd level used when the caller has no preference.
+pub const DEFAULT_LEVEL: i32 = 3;
+
+/// Compresses `payload` for a frame described by `header`.
+///
+/// Returns the `Compressed` header (same channel/stream/flags, new length)
+/// and the body to send after it.
+pub fn wrap(header: FrameHeader, payload: &[u8], level: i32) -> io::Result<(FrameHeader, Vec<u8>)> {
+    let mut body = Vec::with_capacity(1 + payload.len() / 2);
+    body.push(header.frame_type as u8);
+    body.extend_from_slice(&zstd::bulk::compress(payload, level)?);
+
+    let header = FrameHeader {
+        frame_type: FrameType::Compressed,
+        length: body.len() as u64,
+        ..header
+    };
+    Ok((header, body))
+}
+
+/// Reverses [`wrap`]: returns the original frame type and payload.
+///
+/// Fails if the payload would inflate beyond `max_len` (compression bombs).
+pub fn unwrap(body: &[u8], max_len: usize) -> io::Result<(FrameType, Vec<u8>)> {
+    let (&type_byte, compressed) = body.split_first()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "empty compressed payload"))?;
+    let frame_type = FrameType::try_from_bits(type_byte)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    if frame_type == FrameType::Compressed {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "nested compressed payload"));
+    }
+    Ok((frame_type, zstd::bulk::decompress(compressed, max_len)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::FrameFlags;
+
+    #[test]
+    fn test_wrap_unwrap_round_trip() {
+        let header = FrameHeader {
+            flags: FrameFlags::PRIORITY,
+            frame_type: FrameType::Utf8Text,
+            channel_id: 9,
+            stream_id: 4,
+            length: 0,
+        };
+        let text = "hello hello hello hello hello hello".repeat(20);
+
+        let (wrapped, body) = wrap(header, text.as_bytes(), DEFAULT_LEVEL).unwrap();
+        assert_eq!(wrapped.frame_type, FrameType::Compressed);
+        assert_eq!(wrapped.channel_id, 9);
+        assert_eq!(wrapped.length as usize, body.len());
+        assert!(body.len() < text.len());
+
+        let (frame_type, payload) = unwrap(&body, 1024).unwrap();
+        assert_eq!(frame_type, FrameType::Utf8Text);
+        assert_eq!(payload, text.as_bytes());
+
+        assert!(unwrap(&body, 16).is_err());
+    }
+}
@@ -0,0 +1,55 @@
+use rkyv::{Archive, Deserialize, Serialize};
+extern crate alloc;
+use alloc::string::String;
+
+/// First channel id handed out by the server for [`ControlMessage::OpenChannel`].
+///
+/// Ids below it are left to the application (hardcoded channels), so the two
+/// ranges never collide.
+pub const DYNAMIC_CHANNEL_BASE: u32 = 1 << 24;
+
+/// Session control messages.
+///
+/// Sent as `RkyvAligned` frames on channel 0 with the `CONTROL` flag set; they
+/// are handled by the protocol layer and never reach application handlers.
+#[derive(Archive, Serialize, Deserialize, Debug, PartialEq)]
+#[archive(check_bytes)]
+#[repr(C)]
+pub enum ControlMessage {
+    /// Client asks the server for a fresh channel id.
+    OpenChannel {
+        /// Echoed back in the reply so concurrent requests can be told apart.
+        request_id: u32,
+    },
+    /// Server reply to `OpenChannel`.
+    ChannelOpened {
+        request_id: u32,
+        channel_id: u32,
+    },
+    /// Client releases a server-assigned channel id for reuse.
+    CloseChannel {
+        channel_id: u32,
+    },
+    /// Liveness probe; either side may send it. Answered with a `Pong`
+    /// carrying the same `ts`.
+    Ping {
+        /// Sender-defined timestamp (e.g. microseconds since connect), opaque to the peer.
+        ts: u64,
+    },
+    /// Reply to `Ping`. The sender measures round-trip time from its own `ts`.
+    Pong {
+        ts: u64,
+    },
+    /// The sender is shutting down on purpose. Sent last on the session
+    /// stream, right before the QUIC connection is closed with `code`.
+    Close {
+        /// Application-defined; `0` means a normal shutdown.
+        code: u16,
+        reason: String,
+        /// BLAKE3 digest of every byte the sender wrote on the session stream
+        /// before this frame, for the receiver to check against what it
+        /// read. Filled in by senders built with the `stream-digest`
+        /// feature; `None` skips the check.
+        digest: Option<[u8; 32]>,
+    },
+}
@@ -0,0 +1,990 @@
+//! Transport-agnostic frame decoding.
+//!
+//! `FrameDecoder` buffers arbitrary byte chunks (as delivered by QUIC,
+//! WebTransport or a test) and yields whole frames once they are complete.
+//! It has no IO of its own, so it works in `no_std` and WASM builds; the
+//! QUIC `Framer` is a read loop around it.
+//!
+//! Messages split into [`FRAGMENTED`](FrameFlags::FRAGMENTED) frames are
+//! reassembled here and returned as a single frame.
+//!
+//! A header declaring `length = 0` is a valid frame with an empty payload,
+//! usable as a bare signal on a channel, and is returned like any other.
+//! Receivers that never expect one can refuse them with
+//! [`with_reject_empty`](FrameDecoder::with_reject_empty), so a peer cannot
+//! keep them busy with a stream of 4-byte frames.
+//!
+//! Besides the global [`max_frame_len`](FrameDecoder::with_max_frame_len),
+//! each channel can get a tighter cap with
+//! [`set_channel_limit`](FrameDecoder::set_channel_limit), checked against the
+//! header before any of the payload is buffered.
+//!
+//! Fragmented messages in progress are capped as a whole too, by count
+//! ([`with_max_pending_messages`](FrameDecoder::with_max_pending_messages))
+//! and by buffered bytes
+//! ([`with_max_reassembly_bytes`](FrameDecoder::with_max_reassembly_bytes)),
+//! so a peer cannot open one message per `stream_id` and never finish them.
+
+extern crate alloc;
+use alloc::collections::{BTreeMap, BTreeSet};
+
+use bytes::{Buf, BytesMut};
+
+#[cfg(feature = "stream-digest")]
+use crate::digest::StreamDigest;
+use crate::error::Error;
+use crate::frame::{FrameFlags, FrameHeader, FrameType, TYPE_MASK};
+use crate::pool::BufferPool;
+
+/// Default cap on the payload length a header may declare.
+pub const DEFAULT_MAX_FRAME_LEN: usize = 16 * 1024 * 1024;
+
+/// Default cap on fragmented messages being reassembled at once.
+pub const DEFAULT_MAX_PENDING_MESSAGES: usize = 1024;
+
+/// Default cap on the payload bytes held by fragmented messages being
+/// reassembled, all together.
+pub const DEFAULT_MAX_REASSEMBLY_BYTES: usize = 64 * 1024 * 1024;
+
+/// Default capacity above which a drained buffer is released.
+pub const DEFAULT_HIGH_WATER_MARK: usize = 64 * 1024;
+
+/// What a [`FrameDecoder`] does with a frame over its channel's limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LimitAction {
+    /// Discard the payload as it arrives, without buffering it, and go on
+    /// with the next frame. A fragmented message is dropped whole.
+    Skip,
+    /// Fail with [`Error::ChannelFrameTooLarge`] as soon as the header (or,
+    /// for a fragmented message, the fragment crossing the limit) is decoded.
+    Fail,
+}
+
+/// Reassembles frames from a byte stream that may split or coalesce them.
+pub struct FrameDecoder {
+    buffer: BytesMut,
+    initial_capacity: usize,
+    // Once drained, the buffer is reset only if it grew beyond this
+    high_water_mark: usize,
+    // Largest capacity the buffer reached since it was last replaced
+    peak_capacity: usize,
+    // Declared payload lengths above this are refused before buffering
+    max_frame_len: usize,
+    pool: BufferPool,
+    // Set by the first `recycle`; until then payloads are all split off the
+    // read buffer, as copying them into pooled buffers only pays off for a
+    // caller that hands them back
+    recycling: bool,
+    // Max bytes discarded in one go while resyncing; `None` means strict mode
+    resync_limit: Option<usize>,
+    // Reject overlong varints in headers
+    canonical: bool,
+    // Reject headers declaring an empty payload
+    reject_empty: bool,
+    // Return frames of unknown type instead of failing on them
+    unknown_types: bool,
+    // Check that `Utf8Text` payloads are valid UTF-8 before returning them
+    validate_utf8: bool,
+    // Per-channel payload caps, below `max_frame_len`
+    channel_limits: BTreeMap<u32, (usize, LimitAction)>,
+    // Payload bytes of a skipped frame still to be discarded
+    discard: u64,
+    // Fragmented messages being skipped until their final fragment
+    dropping: BTreeSet<(u32, u64)>,
+    oversized_frames: u64,
+    skip_run: usize,
+    skipped_bytes: u64,
+    corrupt_frames: u64,
+    // Messages still missing fragments, keyed by (channel_id, stream_id);
+    // holds the first fragment's header and the payload so far
+    partial: BTreeMap<(u32, u64), (FrameHeader, BytesMut)>,
+    // Payload bytes held in `partial`, and the caps on it
+    reassembly_bytes: usize,
+    max_pending_messages: usize,
+    max_reassembly_bytes: usize,
+    // Every byte consumed from the buffer, in order
+    #[cfg(feature = "stream-digest")]
+    digest: StreamDigest,
+}
+
+impl FrameDecoder {
+    /// Create a new decoder with default buffer capacity.
+    pub fn new() -> Self {
+        Self::with_capacity(4096)
+    }
+
+    /// Create a new decoder with custom initial buffer capacity.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            buffer: BytesMut::with_capacity(capacity),
+            initial_capacity: capacity,
+            high_water_mark: DEFAULT_HIGH_WATER_MARK.max(capacity),
+            peak_capacity: capacity,
+            max_frame_len: DEFAULT_MAX_FRAME_LEN,
+            pool: BufferPool::new(),
+            recycling: false,
+            resync_limit: None,
+            canonical: false,
+            reject_empty: false,
+            unknown_types: false,
+            validate_utf8: false,
+            channel_limits: BTreeMap::new(),
+            discard: 0,
+            dropping: BTreeSet::new(),
+            oversized_frames: 0,
+            skip_run: 0,
+            skipped_bytes: 0,
+            corrupt_frames: 0,
+            partial: BTreeMap::new(),
+            reassembly_bytes: 0,
+            max_pending_messages: DEFAULT_MAX_PENDING_MESSAGES,
+            max_reassembly_bytes: DEFAULT_MAX_REASSEMBLY_BYTES,
+            #[cfg(feature = "stream-digest")]
+            digest: StreamDigest::new(),
+        }
+    }
+
+    /// Sets the capacity above which a drained buffer is released and
+    /// replaced with one of the initial capacity. Below it, the buffer is reset
+    /// in place and its allocation reused.
+    pub fn with_high_water_mark(mut self, bytes: usize) -> Self {
+        self.high_water_mark = bytes.max(self.initial_capacity);
+        self
+    }
+
+    /// Sets the largest payload a frame may declare. A header above it fails
+    /// with [`Error::FrameTooLarge`] as soon as it is decoded, before any of
+    /// the payload is buffered. Also caps the total size of a reassembled
+    /// fragmented message.
+    pub fn with_max_frame_len(mut self, bytes: usize) -> Self {
+        self.max_frame_len = bytes;
+        self
+    }
+
+    /// Caps how many fragmented messages may be in progress at once, across
+    /// all channels and streams (default [`DEFAULT_MAX_PENDING_MESSAGES`]).
+    /// A first fragment beyond it fails with [`Error::ReassemblyLimit`].
+    pub fn with_max_pending_messages(mut self, messages: usize) -> Self {
+        self.max_pending_messages = messages;
+        self
+    }
+
+    /// Caps the payload bytes held by all fragmented messages in progress
+    /// together (default [`DEFAULT_MAX_REASSEMBLY_BYTES`]). A fragment that
+    /// would go beyond it fails with [`Error::ReassemblyLimit`].
+    pub fn with_max_reassembly_bytes(mut self, bytes: usize) -> Self {
+        self.max_reassembly_bytes = bytes;
+        self
+    }
+
+    /// Enables lenient mode: instead of failing on a corrupt header, the
+    /// decoder discards bytes one at a time until a plausible header appears
+    /// and resumes there.
+    ///
+    /// A header is plausible if its reserved bits are clear, its type is known and
+    /// its length is within [`with_max_frame_len`](Self::with_max_frame_len).
+    /// Decoding fails with [`Error::ResyncFailed`] once more than `max_skip`
+    /// consecutive bytes would be discarded. Strict mode (the default) returns
+    /// the header's error instead: [`Error::FrameTooLarge`],
+    /// [`Error::InvalidFrameType`] or [`Error::InvalidVarInt`].
+    pub fn with_resync(mut self, max_skip: usize) -> Self {
+        self.resync_limit = Some(max_skip);
+        self
+    }
+
+    /// Rejects headers whose varints are not minimally encoded, with
+    /// [`Error::InvalidVarInt`] (see [`FrameHeader::decode_canonical`]).
+    /// Off by default; enable it for a strictly canonical wire format.
+    pub fn with_canonical_varints(mut self) -> Self {
+        self.canonical = true;
+        self
+    }
+
+    /// Rejects headers declaring a zero-length payload with
+    /// [`Error::EmptyFrame`], as soon as they are decoded. Off by default:
+    /// empty frames are valid on the wire. A `CHECKSUM` frame is never
+    /// empty on the wire, since its length covers the trailer.
+    pub fn with_reject_empty(mut self) -> Self {
+        self.reject_empty = true;
+        self
+    }
+
+    /// Returns frames whose type bits name no known [`FrameType`] as
+    /// [`FrameType::Unknown`], payload untouched, instead of failing with
+    /// [`Error::InvalidFrameType`]. Lets a receiver skip frame types added by
+    /// a newer peer and keep reading the stream. In resync mode such headers
+    /// then count as plausible.
+    pub fn with_unknown_types(mut self) -> Self {
+        self.unknown_types = true;
+        self
+    }
+
+    /// Checks that every [`FrameType::Utf8Text`] payload, reassembled if
+    /// fragmented, is valid UTF-8 before returning it; one that is not fails
+    /// with [`Error::InvalidUtf8`]. The frame is consumed either way, so
+    /// decoding can carry on past it. Off by default.
+    pub fn with_validate_utf8(mut self) -> Self {
+        self.validate_utf8 = true;
+        self
+    }
+
+    /// Caps the payload of frames on `channel_id` at `max_bytes`, or, for a
+    /// fragmented message, its reassembled size. A frame over it is handled
+    /// per `action`. The global [`max_frame_len`](Self::max_frame_len) still
+    /// applies on top.
+    pub fn set_channel_limit(&mut self, channel_id: u32, max_bytes: usize, action: LimitAction) {
+        self.channel_limits.insert(channel_id, (max_bytes, action));
+    }
+
+    /// Removes the cap set with [`set_channel_limit`](Self::set_channel_limit).
+    pub fn clear_channel_limit(&mut self, channel_id: u32) {
+        self.channel_limits.remove(&channel_id);
+    }
+
+    /// The largest payload a frame may declare.
+    pub fn max_frame_len(&self) -> usize {
+        self.max_frame_len
+    }
+
+    /// Appends a chunk received from the transport.
+    pub fn push(&mut self, data: &[u8]) {
+        self.buffer.extend_from_slice(data);
+        self.peak_capacity = self.peak_capacity.max(self.buffer.capacity());
+    }
+
+    /// Hands a payload buffer back for reuse by later frames.
+    ///
+    /// Optional: payloads that are simply dropped are freed as usual. Recycling
+    /// is what keeps steady-state small-frame traffic allocation-free: from the
+    /// first call on, payloads of up to 4 KiB are copied into pooled buffers
+    /// rather than split off the read buffer, so it can be reused in place.
+    /// A caller that never recycles gets every payload zero-copy.
+    pub fn recycle(&mut self, payload: BytesMut) {
+        self.recycling = true;
+        self.pool.put(payload);
+    }
+
+    /// Returns a buffer the caller never saw to the pool, without turning
+    /// pooling on.
+    #[cfg(feature = "zstd")]
+    pub(crate) fn release(&mut self, buf: BytesMut) {
+        self.pool.put(buf);
+    }
+
+    /// Returns the next complete frame, or `Ok(None)` if more bytes are needed.
+    ///
+    /// Call repeatedly after each [`push`](Self::push): one chunk may hold
+    /// several frames. Fragments are held back until their final frame
+    /// arrives; the reassembled frame carries the first fragment's header,
+    /// without the `FRAGMENTED` flag and with the total length.
+    pub fn next_frame(&mut self) -> Result<Option<(FrameHeader, BytesMut)>, Error> {
+        loop {
+            let Some((mut header, mut payload)) = self.next_wire_frame()? else {
+                return Ok(None);
+            };
+            if header.flags.contains(FrameFlags::CHECKSUM) {
+                match header.strip_checksum(&payload).map(<[u8]>::len) {
+                    Ok(len) => {
+                        payload.truncate(len);
+                        header.length = len as u64;
+                    }
+                    Err(e) => {
+                        self.pool.put(payload);
+                        // Resync mode drops the damaged frame and carries on
+                        if self.resync_limit.is_none() {
+                            return Err(e);
+                        }
+                        self.corrupt_frames += 1;
+                        continue;
+                    }
+                }
+            }
+            if let Some((header, payload)) = self.reassemble(header, payload)? {
+                if let Err(e) = self.check_utf8(&header, &payload) {
+                    self.pool.put(payload);
+                    return Err(e);
+                }
+                return Ok(Some((header, payload)));
+            }
+        }
+    }
+
+    /// Fails a `Utf8Text` payload that is not valid UTF-8, if
+    /// [`with_validate_utf8`](Self::with_validate_utf8) is set.
+    pub(crate) fn check_utf8(&self, header: &FrameHeader, payload: &[u8]) -> Result<(), Error> {
+        if !self.validate_utf8 || header.frame_type != FrameType::Utf8Text {
+            return Ok(());
+        }
+        core::str::from_utf8(payload)
+            .map(|_| ())
+            .map_err(|e| Error::InvalidUtf8 { channel_id: header.channel_id, valid_up_to: e.valid_up_to() })
+    }
+
+    /// Joins `payload` to any earlier fragments of the same message.
+    ///
+    /// Returns the complete message, or `None` while fragments are missing.
+    fn reassemble(&mut self, header: FrameHeader, payload: BytesMut) -> Result<Option<(FrameHeader, BytesMut)>, Error> {
+        let key = (header.channel_id, header.stream_id);
+        let more = header.flags.contains(FrameFlags::FRAGMENTED);
+
+        if self.dropping.contains(&key) {
+            self.pool.put(payload);
+            if !more {
+                self.dropping.remove(&key);
+            }
+            return Ok(None);
+        }
+
+        let Some((first, mut message)) = self.partial.remove(&key) else {
+            if more {
+                if self.partial.len() >= self.max_pending_messages {
+                    self.pool.put(payload);
+                    return Err(self.reassembly_limit());
+                }
+                self.hold(key, header, payload)?;
+                return Ok(None);
+            }
+            return Ok(Some((header, payload)));
+        };
+        self.reassembly_bytes -= message.len();
+
+        let total = message.len() + payload.len();
+        if total > self.max_frame_len {
+            self.pool.put(payload);
+            return Err(Error::FrameTooLarge { len: total as u64, max: self.max_frame_len });
+        }
+        if let Some(&(max, action)) = self.channel_limits.get(&key.0) {
+            if total > max {
+                self.pool.put(payload);
+                self.pool.put(message);
+                if action == LimitAction::Fail {
+                    return Err(Error::ChannelFrameTooLarge { channel_id: key.0, len: total as u64, max });
+                }
+                self.oversized_frames += 1;
+                if more {
+                    self.dropping.insert(key);
+                }
+                return Ok(None);
+            }
+        }
+        message.extend_from_slice(&payload);
+        self.pool.put(payload);
+
+        if more {
+            self.hold(key, first, message)?;
+            return Ok(None);
+        }
+        let header = FrameHeader {
+            flags: first.flags - FrameFlags::FRAGMENTED,
+            length: message.len() as u64,
+            ..first
+        };
+        Ok(Some((header, message)))
+    }
+
+    /// Keeps an incomplete message in `partial`, unless that would exceed
+    /// [`max_reassembly_bytes`](Self::with_max_reassembly_bytes).
+    fn hold(&mut self, key: (u32, u64), first: FrameHeader, message: BytesMut) -> Result<(), Error> {
+        if self.reassembly_bytes + message.len() > self.max_reassembly_bytes {
+            self.pool.put(message);
+            return Err(self.reassembly_limit());
+        }
+        self.reassembly_bytes += message.len();
+        self.partial.insert(key, (first, message));
+        Ok(())
+    }
+
+    fn reassembly_limit(&self) -> Error {
+        Error::ReassemblyLimit { messages: self.partial.len(), bytes: self.reassembly_bytes }
+    }
+
+    /// Returns the next frame as it appeared on the wire, fragments included.
+    fn next_wire_frame(&mut self) -> Result<Option<(FrameHeader, BytesMut)>, Error> {
+        loop {
+            if self.discard > 0 {
+                let n = self.discard.min(self.buffer.len() as u64);
+                self.consume(n as usize);
+                self.discard -= n;
+                if self.buffer.is_empty() {
+                    self.reset_buffer();
+                }
+            }
+
+            // We need at least 1 byte to start decoding header
+            if self.buffer.is_empty() {
+                return Ok(None);
+            }
+
+            if self.resync_limit.is_none() {
+                let frame = self.take_frame(self.decode_header())?;
+                // A skipped frame leaves its payload to discard; carry on past it
+                if frame.is_none() && self.discard > 0 {
+                    continue;
+                }
+                return Ok(frame);
+            }
+
+            // The first byte alone can rule a position out, before waiting on varints
+            if !self.plausible_first_byte(self.buffer[0]) {
+                self.skip_byte()?;
+                continue;
+            }
+            match self.decode_header() {
+                Ok((header, _)) if header.length > self.max_frame_len as u64 => self.skip_byte()?,
+                Err(Error::InvalidVarInt) | Err(Error::InvalidFrameType(_)) => self.skip_byte()?,
+                decoded => {
+                    let frame = self.take_frame(decoded)?;
+                    if frame.is_none() && self.discard > 0 {
+                        continue;
+                    }
+                    return Ok(frame);
+                }
+            }
+        }
+    }
+
+    fn take_frame(&mut self, decoded: Result<(FrameHeader, usize), Error>) -> Result<Option<(FrameHeader, BytesMut)>, Error> {
+        let (header, head_len) = match decoded {
+            Ok(decoded) => decoded,
+            // Not enough bytes for header
+            Err(Error::IncompleteInput { .. }) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        if header.length > self.max_frame_len as u64 {
+            return Err(Error::FrameTooLarge { len: header.length, max: self.max_frame_len });
+        }
+        if header.length == 0 && self.reject_empty {
+            return Err(Error::EmptyFrame);
+        }
+        if let Some(&(max, action)) = self.channel_limits.get(&header.channel_id) {
+            if header.length > max as u64 {
+                if action == LimitAction::Fail {
+                    return Err(Error::ChannelFrameTooLarge { channel_id: header.channel_id, len: header.length, max });
+                }
+                // Dropped as it arrives, along with the rest of its message
+                let key = (header.channel_id, header.stream_id);
+                if let Some((_, message)) = self.partial.remove(&key) {
+                    self.reassembly_bytes -= message.len();
+                    self.pool.put(message);
+                }
+                if header.flags.contains(FrameFlags::FRAGMENTED) {
+                    self.dropping.insert(key);
+                } else {
+                    self.dropping.remove(&key);
+                }
+                self.oversized_frames += 1;
+                self.consume(head_len);
+                self.discard = header.length;
+                self.skip_run = 0;
+                return Ok(None);
+            }
+        }
+
+        let payload_len = header.length as usize;
+        if self.buffer.len() < head_len + payload_len {
+            // We have the header but not the full payload
+            return Ok(None);
+        }
+        self.consume(head_len);
+
+        // For a recycling caller, small payloads are copied into a pooled
+        // buffer so the read buffer stays uniquely owned; otherwise, and for
+        // large ones, they are split off (zero-copy)
+        #[cfg(feature = "stream-digest")]
+        self.digest.update(&self.buffer[..payload_len]);
+        let pooled = if self.recycling { self.pool.take(payload_len) } else { None };
+        let payload = match pooled {
+            Some(mut buf) => {
+                buf.extend_from_slice(&self.buffer[..payload_len]);
+                self.buffer.advance(payload_len);
+                buf
+            }
+            None => self.buffer.split_to(payload_len),
+        };
+
+        if self.buffer.is_empty() {
+            self.reset_buffer();
+        }
+        self.skip_run = 0;
+        Ok(Some((header, payload)))
+    }
+
+    fn decode_header(&self) -> Result<(FrameHeader, usize), Error> {
+        FrameHeader::decode_with(&self.buffer, self.canonical, self.unknown_types)
+    }
+
+    /// Reserved bits clear and a known frame type, unless unknown ones are
+    /// accepted.
+    fn plausible_first_byte(&self, byte: u8) -> bool {
+        let reserved = byte & !(FrameFlags::all().bits() | TYPE_MASK) != 0;
+        !reserved && (self.unknown_types || FrameType::try_from_bits(byte & TYPE_MASK).is_ok())
+    }
+
+    /// Discards one byte while resyncing.
+    fn skip_byte(&mut self) -> Result<(), Error> {
+        let limit = self.resync_limit.unwrap_or(0);
+        if self.skip_run >= limit {
+            return Err(Error::ResyncFailed { skipped: self.skip_run });
+        }
+        if self.skip_run == 0 {
+            self.corrupt_frames += 1;
+        }
+        self.consume(1);
+        self.skip_run += 1;
+        self.skipped_bytes += 1;
+        Ok(())
+    }
+
+    /// Drops the first `n` buffered bytes, hashing them into the stream
+    /// digest when there is one.
+    fn consume(&mut self, n: usize) {
+        #[cfg(feature = "stream-digest")]
+        self.digest.update(&self.buffer[..n]);
+        self.buffer.advance(n);
+    }
+
+    /// Called once the buffer is fully drained.
+    fn reset_buffer(&mut self) {
+        if self.peak_capacity > self.high_water_mark {
+            self.buffer = BytesMut::with_capacity(self.initial_capacity);
+            self.peak_capacity = self.initial_capacity;
+        } else {
+            // Keeps the allocation; the next `reserve` reclaims the consumed
+            // prefix in place as long as no split-off payload still shares it
+            self.buffer.clear();
+        }
+    }
+
+    /// Bytes buffered but not yet returned as a frame.
+    pub fn buffered(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Checks that the input ended on a frame boundary: call once the source
+    /// is exhausted. Fails with [`Error::TruncatedFrame`] if bytes of an
+    /// incomplete frame are still buffered, or a fragmented message is still
+    /// missing its final fragment; `buffered` then counts both.
+    pub fn finish(&self) -> Result<(), Error> {
+        match self.buffer.len() {
+            0 if self.discard == 0 && self.partial.is_empty() => Ok(()),
+            buffered => Err(Error::TruncatedFrame { buffered: buffered + self.reassembly_bytes }),
+        }
+    }
+
+    /// Consumes the decoder and returns the bytes not yet parsed, e.g. to
+    /// inspect what a peer sent before a truncated frame.
+    pub fn into_remaining(self) -> BytesMut {
+        self.buffer
+    }
+
+    /// Current capacity of the internal buffer.
+    pub fn capacity(&self) -> usize {
+        self.buffer.capacity()
+    }
+
+    /// Number of corrupt regions skipped in resync mode (each may span several bytes).
+    pub fn corrupt_frames(&self) -> u64 {
+        self.corrupt_frames
+    }
+
+    /// Total bytes discarded in resync mode.
+    pub fn skipped_bytes(&self) -> u64 {
+        self.skipped_bytes
+    }
+
+    /// Frames and fragmented messages dropped for exceeding their channel's
+    /// limit under [`LimitAction::Skip`].
+    pub fn oversized_frames(&self) -> u64 {
+        self.oversized_frames
+    }
+
+    /// Number of fragmented messages still waiting for their final frame.
+    pub fn pending_messages(&self) -> usize {
+        self.partial.len()
+    }
+
+    /// Number of idle payload buffers waiting to be reused.
+    pub fn pooled_buffers(&self) -> usize {
+        self.pool.idle()
+    }
+
+    /// BLAKE3 digest of every byte consumed so far: frames returned,
+    /// fragments held for reassembly, and bytes skipped or discarded. Bytes
+    /// of a frame not yet complete are left out, so taken right after
+    /// [`next_frame`](Self::next_frame) returns, it ends on that frame.
+    #[cfg(feature = "stream-digest")]
+    pub fn stream_digest(&self) -> [u8; 32] {
+        self.digest.finalize()
+    }
+}
+
+impl Default for FrameDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(channel_id: u32, payload: &[u8]) -> Vec<u8> {
+        let header = FrameHeader {
+            flags: FrameFlags::empty(),
+            frame_type: FrameType::RawBinary,
+            channel_id,
+            stream_id: 0,
+            length: 0,
+        };
+        let mut out = Vec::new();
+        header.encode_with_payload(payload, &mut out).unwrap();
+        out
+    }
+
+    #[test]
+    fn test_split_and_coalesced_chunks() {
+        let mut bytes = frame(1, b"first");
+        bytes.extend(frame(2, b"second"));
+
+        let mut decoder = FrameDecoder::new();
+        // Byte-at-a-time delivery still yields both frames, in order
+        let mut frames = Vec::new();
+        for byte in &bytes {
+            decoder.push(core::slice::from_ref(byte));
+            while let Some(frame) = decoder.next_frame().unwrap() {
+                frames.push(frame);
+            }
+        }
+        assert_eq!(frames.len(), 2);
+        assert_eq!((frames[0].0.channel_id, &frames[0].1[..]), (1, &b"first"[..]));
+        assert_eq!((frames[1].0.channel_id, &frames[1].1[..]), (2, &b"second"[..]));
+        assert_eq!(decoder.buffered(), 0);
+
+        // One chunk holding both
+        decoder.push(&bytes);
+        assert!(decoder.next_frame().unwrap().is_some());
+        assert!(decoder.next_frame().unwrap().is_some());
+        assert!(decoder.next_frame().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_empty_frames_allowed_unless_rejected() {
+        let bytes = [frame(1, b""), frame(2, b"")].concat();
+
+        let mut decoder = FrameDecoder::new();
+        decoder.push(&bytes);
+        for channel_id in [1, 2] {
+            let (header, payload) = decoder.next_frame().unwrap().unwrap();
+            assert_eq!((header.channel_id, header.length, payload.len()), (channel_id, 0, 0));
+        }
+        assert_eq!(decoder.next_frame(), Ok(None));
+
+        let mut strict = FrameDecoder::new().with_reject_empty();
+        strict.push(&bytes);
+        assert_eq!(strict.next_frame(), Err(Error::EmptyFrame));
+    }
+
+    #[test]
+    fn test_utf8_text_validated_on_request() {
+        use crate::frame::FrameBuilder;
+        let text = |payload: &[u8]| {
+            let mut out = Vec::new();
+            FrameBuilder::new(payload).channel(3).frame_type(FrameType::Utf8Text).header()
+                .encode_with_payload(payload, &mut out).unwrap();
+            out
+        };
+        let bytes = [text("héllo".as_bytes()), text(b"ok\xff"), frame(1, b"\xff"), text(b"fine")].concat();
+
+        // Off by default: text frames pass through unchecked
+        let mut decoder = FrameDecoder::new();
+        decoder.push(&bytes);
+        for _ in 0..4 {
+            assert!(decoder.next_frame().unwrap().is_some());
+        }
+
+        let mut decoder = FrameDecoder::new().with_validate_utf8();
+        decoder.push(&bytes);
+        assert_eq!(&decoder.next_frame().unwrap().unwrap().1[..], "héllo".as_bytes());
+        assert_eq!(decoder.next_frame(), Err(Error::InvalidUtf8 { channel_id: 3, valid_up_to: 2 }));
+        // Only text frames are checked, and decoding goes on past a bad one
+        assert_eq!(&decoder.next_frame().unwrap().unwrap().1[..], b"\xff");
+        assert_eq!(&decoder.next_frame().unwrap().unwrap().1[..], b"fine");
+
+        // A character split across fragments is checked once reassembled
+        let mut split = Vec::new();
+        let e_acute = "é".as_bytes();
+        for (piece, flags) in [(&e_acute[..1], FrameFlags::FRAGMENTED), (&e_acute[1..], FrameFlags::empty())] {
+            FrameBuilder::new(piece).channel(3).frame_type(FrameType::Utf8Text).flags(flags).header()
+                .encode_with_payload(piece, &mut split).unwrap();
+        }
+        decoder.push(&split);
+        assert_eq!(&decoder.next_frame().unwrap().unwrap().1[..], e_acute);
+    }
+
+    #[test]
+    fn test_oversized_frame() {
+        let mut decoder = FrameDecoder::new().with_max_frame_len(4);
+        decoder.push(&frame(1, b"too long"));
+        assert_eq!(decoder.next_frame(), Err(Error::FrameTooLarge { len: 8, max: 4 }));
+    }
+
+    #[test]
+    fn test_fragments_reassembled() {
+        let fragment = |channel_id, stream_id, more: bool, payload: &[u8]| {
+            let header = FrameHeader {
+                flags: if more { FrameFlags::FRAGMENTED } else { FrameFlags::empty() },
+                frame_type: FrameType::RkyvAligned,
+                channel_id,
+                stream_id,
+                length: 0,
+            };
+            let mut out = Vec::new();
+            header.encode_with_payload(payload, &mut out).unwrap();
+            out
+        };
+
+        let mut decoder = FrameDecoder::new();
+        decoder.push(&fragment(1, 9, true, b"hel"));
+        // Unrelated frame on another channel, same stream id, in between
+        decoder.push(&fragment(2, 9, false, b"other"));
+        decoder.push(&fragment(1, 9, true, b"lo "));
+        decoder.push(&fragment(1, 9, false, b"world"));
+
+        let (header, payload) = decoder.next_frame().unwrap().unwrap();
+        assert_eq!((header.channel_id, &payload[..]), (2, &b"other"[..]));
+        let (header, payload) = decoder.next_frame().unwrap().unwrap();
+        assert_eq!(&payload[..], b"hello world");
+        assert_eq!(header.length, 11);
+        assert_eq!(header.frame_type, FrameType::RkyvAligned);
+        assert!(!header.flags.contains(FrameFlags::FRAGMENTED));
+        assert_eq!(decoder.pending_messages(), 0);
+
+        // The reassembled size counts against the frame limit
+        let mut decoder = FrameDecoder::new().with_max_frame_len(8);
+        decoder.push(&fragment(1, 0, true, b"12345"));
+        decoder.push(&fragment(1, 0, false, b"6789"));
+        assert_eq!(decoder.next_frame(), Err(Error::FrameTooLarge { len: 9, max: 8 }));
+        assert_eq!(decoder.pending_messages(), 0);
+    }
+
+    #[test]
+    fn test_pending_reassembly_capped() {
+        use crate::frame::FrameBuilder;
+        let fragment = |stream_id, more: bool, payload: &[u8]| {
+            let flags = if more { FrameFlags::FRAGMENTED } else { FrameFlags::empty() };
+            FrameBuilder::new(payload).channel(1).stream(stream_id).flags(flags).to_bytes().unwrap()
+        };
+
+        // One open message per stream id, up to the count
+        let mut decoder = FrameDecoder::new().with_max_pending_messages(2);
+        decoder.push(&[fragment(1, true, b"a"), fragment(2, true, b"b"), fragment(3, true, b"c")].concat());
+        assert_eq!(decoder.next_frame(), Err(Error::ReassemblyLimit { messages: 2, bytes: 2 }));
+        // Finishing a message frees its slot
+        decoder.push(&[fragment(1, false, b"!"), fragment(3, true, b"c")].concat());
+        assert_eq!(&decoder.next_frame().unwrap().unwrap().1[..], b"a!");
+        assert_eq!(decoder.next_frame(), Ok(None));
+        assert_eq!(decoder.pending_messages(), 2);
+
+        // Bytes held across all messages, each well under the frame limit
+        let mut decoder = FrameDecoder::new().with_max_reassembly_bytes(10);
+        decoder.push(&[fragment(1, true, b"1234"), fragment(2, true, b"5678"), fragment(1, true, b"90x")].concat());
+        assert_eq!(decoder.next_frame(), Err(Error::ReassemblyLimit { messages: 1, bytes: 4 }));
+        assert_eq!(decoder.pending_messages(), 1);
+    }
+
+    #[test]
+    fn test_finish_reports_unfinished_message() {
+        use crate::frame::FrameBuilder;
+        let mut decoder = FrameDecoder::new();
+        decoder.push(&FrameBuilder::new(b"head").channel(1).flags(FrameFlags::FRAGMENTED).to_bytes().unwrap());
+        assert_eq!(decoder.next_frame(), Ok(None));
+        // Every wire frame is complete, but the message is not
+        assert_eq!(decoder.buffered(), 0);
+        assert_eq!(decoder.finish(), Err(Error::TruncatedFrame { buffered: 4 }));
+
+        decoder.push(&FrameBuilder::new(b"tail").channel(1).to_bytes().unwrap());
+        assert_eq!(&decoder.next_frame().unwrap().unwrap().1[..], b"headtail");
+        assert_eq!(decoder.finish(), Ok(()));
+    }
+
+    #[cfg(feature = "stream-digest")]
+    #[test]
+    fn test_stream_digest_ends_on_frame_boundary() {
+        let digest = |bytes: &[u8]| {
+            let mut digest = StreamDigest::new();
+            digest.update(bytes);
+            digest.finalize()
+        };
+        let (first, second) = (frame(1, b"first"), frame(2, b"second"));
+
+        let mut decoder = FrameDecoder::new();
+        decoder.push(&first);
+        decoder.push(&second[..3]);
+        assert!(decoder.next_frame().unwrap().is_some());
+        assert_eq!(decoder.next_frame(), Ok(None));
+        // The half-read second frame is not in it yet
+        assert_eq!(decoder.stream_digest(), digest(&first));
+
+        decoder.push(&second[3..]);
+        assert!(decoder.next_frame().unwrap().is_some());
+        assert_eq!(decoder.stream_digest(), digest(&[first, second].concat()));
+    }
+
+    #[test]
+    fn test_small_payloads_reuse_pooled_buffers() {
+        let mut decoder = FrameDecoder::new();
+
+        // Split off the read buffer, so not worth keeping
+        decoder.push(&frame(1, b"first"));
+        let (_, payload) = decoder.next_frame().unwrap().unwrap();
+        assert_eq!(&payload[..], b"first");
+        decoder.recycle(payload);
+        assert_eq!(decoder.pooled_buffers(), 0);
+
+        // Now copied into a pooled buffer, which comes back for the next frame
+        decoder.push(&frame(1, b"second"));
+        let (_, payload) = decoder.next_frame().unwrap().unwrap();
+        assert_eq!(&payload[..], b"second");
+        let ptr = payload.as_ptr();
+        decoder.recycle(payload);
+        assert_eq!(decoder.pooled_buffers(), 1);
+
+        decoder.push(&frame(1, b"third"));
+        let (_, payload) = decoder.next_frame().unwrap().unwrap();
+        assert_eq!(&payload[..], b"third");
+        assert_eq!(payload.as_ptr(), ptr);
+    }
+
+    #[test]
+    fn test_payloads_zero_copy_without_recycling() {
+        let mut decoder = FrameDecoder::new();
+        let first = frame(1, b"one");
+        decoder.push(&[first.clone(), frame(1, b"two")].concat());
+        let (_, one) = decoder.next_frame().unwrap().unwrap();
+        let (_, two) = decoder.next_frame().unwrap().unwrap();
+        // Both views into the same read buffer, the second header between them
+        let header_len = first.len() - one.len();
+        assert_eq!(two.as_ptr(), one.as_ptr().wrapping_add(one.len() + header_len));
+    }
+
+    #[test]
+    fn test_drained_buffer_respects_high_water_mark() {
+        let mut decoder = FrameDecoder::new().with_high_water_mark(8192);
+
+        decoder.push(&frame(1, &[7u8; 16 * 1024]));
+        let (_, payload) = decoder.next_frame().unwrap().unwrap();
+        assert_eq!(payload.len(), 16 * 1024);
+
+        // Grew past the mark, so the drained buffer was replaced
+        assert_eq!(decoder.buffered(), 0);
+        assert_eq!(decoder.capacity(), 4096);
+    }
+
+    #[test]
+    fn test_resync_skips_corrupt_bytes() {
+        let mut decoder = FrameDecoder::new().with_resync(16);
+
+        decoder.push(&[0xFF; 5]);
+        decoder.push(&frame(1, b"after"));
+        let (header, payload) = decoder.next_frame().unwrap().unwrap();
+        assert_eq!(header.channel_id, 1);
+        assert_eq!(&payload[..], b"after");
+        assert_eq!(decoder.corrupt_frames(), 1);
+        assert_eq!(decoder.skipped_bytes(), 5);
+    }
+
+    #[test]
+    fn test_resync_gives_up_past_limit() {
+        let mut decoder = FrameDecoder::new().with_resync(4);
+        decoder.push(&[0xFF; 8]);
+        assert_eq!(decoder.next_frame(), Err(Error::ResyncFailed { skipped: 4 }));
+
+        // Strict mode rejects the unknown frame type instead of skipping it
+        let mut strict = FrameDecoder::new();
+        strict.push(&[0x07, 0x00, 0x00, 0x00]);
+        assert_eq!(strict.next_frame(), Err(Error::InvalidFrameType(0x07)));
+    }
+
+    #[test]
+    fn test_unknown_types_passed_through() {
+        let mut decoder = FrameDecoder::new().with_unknown_types();
+        decoder.push(&[0x06, 0x02, 0x00, 0x03, 0xAA, 0xBB, 0xCC]);
+        decoder.push(&frame(1, b"known"));
+
+        let (header, payload) = decoder.next_frame().unwrap().unwrap();
+        assert_eq!((header.frame_type, header.channel_id), (FrameType::Unknown, 2));
+        assert_eq!(&payload[..], &[0xAA, 0xBB, 0xCC]);
+        let (header, payload) = decoder.next_frame().unwrap().unwrap();
+        assert_eq!((header.frame_type, &payload[..]), (FrameType::RawBinary, &b"known"[..]));
+    }
+
+    #[test]
+    fn test_channel_limit_skips_without_buffering() {
+        let mut decoder = FrameDecoder::new();
+        decoder.set_channel_limit(1, 4, LimitAction::Skip);
+
+        // Header of an oversized frame, then its payload in pieces
+        let big = frame(1, &[0xEE; 1000]);
+        decoder.push(&big[..100]);
+        assert_eq!(decoder.next_frame(), Ok(None));
+        assert_eq!(decoder.buffered(), 0);
+        decoder.push(&big[100..]);
+        decoder.push(&frame(1, b"ok"));
+        decoder.push(&frame(2, b"other channel"));
+        assert_eq!(&decoder.next_frame().unwrap().unwrap().1[..], b"ok");
+        assert_eq!(&decoder.next_frame().unwrap().unwrap().1[..], b"other channel");
+        assert_eq!(decoder.oversized_frames(), 1);
+
+        // A fragmented message is dropped whole once it crosses the limit
+        use crate::frame::FrameBuilder;
+        for (i, piece) in [b"abc", b"def", b"ghi"].iter().enumerate() {
+            let mut builder = FrameBuilder::new(*piece).channel(1).stream(3);
+            if i < 2 {
+                builder = builder.flags(FrameFlags::FRAGMENTED);
+            }
+            decoder.push(&builder.to_bytes().unwrap());
+        }
+        decoder.push(&frame(1, b"next"));
+        assert_eq!(&decoder.next_frame().unwrap().unwrap().1[..], b"next");
+        assert_eq!(decoder.oversized_frames(), 2);
+
+        decoder.set_channel_limit(1, 4, LimitAction::Fail);
+        decoder.push(&frame(1, b"too long"));
+        assert_eq!(decoder.next_frame(), Err(Error::ChannelFrameTooLarge { channel_id: 1, len: 8, max: 4 }));
+    }
+
+    #[test]
+    fn test_checksum_verified_and_stripped() {
+        let checked = |channel_id, payload: &[u8]| {
+            let header = FrameHeader {
+                flags: FrameFlags::CHECKSUM,
+                frame_type: FrameType::RawBinary,
+                channel_id,
+                stream_id: 0,
+                length: 0,
+            };
+            let mut out = Vec::new();
+            header.encode_with_payload(payload, &mut out).unwrap();
+            out
+        };
+        let mut damaged = checked(2, b"flipped");
+        *damaged.last_mut().unwrap() ^= 0x01;
+
+        let mut decoder = FrameDecoder::new();
+        decoder.push(&checked(1, b"intact"));
+        decoder.push(&damaged);
+        decoder.push(&frame(3, b"plain"));
+        let (header, payload) = decoder.next_frame().unwrap().unwrap();
+        assert_eq!((header.length, &payload[..]), (6, &b"intact"[..]));
+        assert!(header.flags.contains(FrameFlags::CHECKSUM));
+        assert_eq!(decoder.next_frame(), Err(Error::ChecksumMismatch));
+        // The damaged frame was consumed; the stream carries on
+        assert_eq!(&decoder.next_frame().unwrap().unwrap().1[..], b"plain");
+
+        let mut resync = FrameDecoder::new().with_resync(16);
+        resync.push(&damaged);
+        resync.push(&frame(3, b"plain"));
+        assert_eq!(resync.next_frame().unwrap().unwrap().0.channel_id, 3);
+        assert_eq!(resync.corrupt_frames(), 1);
+    }
+}
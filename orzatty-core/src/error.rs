@@ -1,4 +1,5 @@
 use core::fmt;
+use core::time::Duration;
 
 /// Errors that can occur when processing Orzatty frames.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -11,6 +12,37 @@ pub enum Error {
     InvalidFrameType(u8),
     /// The VarInt encoding is invalid (e.g., overflows 64 bits or is malformed).
     InvalidVarInt,
+    /// A frame declared a payload longer than the receiver accepts.
+    FrameTooLarge { len: u64, max: usize },
+    /// A frame exceeded the limit set for its channel with
+    /// `FrameDecoder::set_channel_limit`.
+    ChannelFrameTooLarge { channel_id: u32, len: u64, max: usize },
+    /// A flag name did not match any known `FrameFlags` flag.
+    UnknownFlag,
+    /// Resync mode discarded its limit of bytes without finding a plausible header.
+    ResyncFailed { skipped: usize },
+    /// A `CHECKSUM` frame's CRC32 trailer did not match its payload.
+    ChecksumMismatch,
+    /// The stream ended in the middle of a frame or fragmented message, with
+    /// `buffered` bytes of it received.
+    TruncatedFrame { buffered: usize },
+    /// No complete frame arrived within the given deadline.
+    ReadTimeout(Duration),
+    /// A frame declared an empty payload to a decoder set to reject them.
+    EmptyFrame,
+    /// A fragment header was malformed (index not below the total, or a
+    /// total of zero) or disagreed with earlier fragments of its message.
+    InvalidFragment,
+    /// An indexed message was dropped after waiting too long for its
+    /// missing fragments.
+    ReassemblyTimeout { channel_id: u32, stream_id: u64, message_id: u64 },
+    /// A `Utf8Text` frame's payload was not valid UTF-8; the bytes before
+    /// `valid_up_to` were.
+    InvalidUtf8 { channel_id: u32, valid_up_to: usize },
+    /// A fragment would have taken the decoder past its cap on fragmented
+    /// messages in progress, which then held `messages` messages and
+    /// `bytes` payload bytes.
+    ReassemblyLimit { messages: usize, bytes: usize },
 }
 
 impl fmt::Display for Error {
@@ -24,6 +56,30 @@ impl fmt::Display for Error {
                 write!(f, "Invalid frame type: {:#04x}", t),
             Error::InvalidVarInt => 
                 write!(f, "Invalid VarInt encoding"),
+            Error::FrameTooLarge { len, max } =>
+                write!(f, "Frame too large: declared {} bytes, limit is {}", len, max),
+            Error::ChannelFrameTooLarge { channel_id, len, max } =>
+                write!(f, "Frame too large for channel {}: {} bytes, limit is {}", channel_id, len, max),
+            Error::UnknownFlag =>
+                write!(f, "Unknown frame flag name"),
+            Error::ResyncFailed { skipped } =>
+                write!(f, "Resync gave up after skipping {} bytes", skipped),
+            Error::ChecksumMismatch =>
+                write!(f, "Frame checksum mismatch"),
+            Error::TruncatedFrame { buffered } =>
+                write!(f, "Stream closed with partial frame data ({} bytes buffered)", buffered),
+            Error::ReadTimeout(t) =>
+                write!(f, "No complete frame within {:?}", t),
+            Error::EmptyFrame =>
+                write!(f, "Empty frame rejected"),
+            Error::InvalidFragment =>
+                write!(f, "Invalid fragment header"),
+            Error::ReassemblyTimeout { channel_id, stream_id, message_id } =>
+                write!(f, "Message {} on channel {}, stream {} timed out waiting for fragments", message_id, channel_id, stream_id),
+            Error::InvalidUtf8 { channel_id, valid_up_to } =>
+                write!(f, "Text frame on channel {} is not valid UTF-8 after {} bytes", channel_id, valid_up_to),
+            Error::ReassemblyLimit { messages, bytes } =>
+                write!(f, "Too much pending reassembly ({} messages, {} bytes)", messages, bytes),
         }
     }
 }
@@ -1,5 +1,54 @@
+//! The frame header wire format.
+//!
+//! Every frame is a header followed by `length` payload bytes:
+//!
+//! ```text
+//! +------------+----------------+---------------+---------------+---------+
+//! | flags|type | channel_id     | stream_id     | length        | payload |
+//! | 1 byte     | varint, 1-8 B  | varint, 1-8 B | varint, 1-8 B | length  |
+//! +------------+----------------+---------------+---------------+---------+
+//! ```
+//!
+//! Varints are QUIC-style (RFC 9000 §16): the top two bits of the first byte
+//! give the length (1, 2, 4 or 8 bytes), the rest hold a big-endian value up
+//! to [`MAX_VARINT`]. The first byte packs the [`FrameFlags`] and the
+//! [`FrameType`]:
+//!
+//! ```text
+//! |    7    |    6     |    5     |     4      |    3     |  2..0  |
+//! | CONTROL | PRIORITY | DATAGRAM | FRAGMENTED | CHECKSUM |  type  |
+//! ```
+//!
+//! The type field ([`TYPE_MASK`]) has room for 8 types, 0-5 assigned.
+//! `CHECKSUM` appends a 4-byte CRC32 trailer to the payload, counted in
+//! `length`.
+//!
+//! # Versions
+//!
+//! - **2** (current [`PROTOCOL_VERSION`](crate::PROTOCOL_VERSION)): bits 5, 4
+//!   and 3 carry `DATAGRAM`, `FRAGMENTED` and `CHECKSUM`; the type field
+//!   shrank to 3 bits. Every header bit is now assigned, so later per-frame
+//!   options have to live in the payload, `stream_id`, or a negotiated
+//!   capability instead.
+//! - **1**: bits 7 and 6 as above, bit 5 reserved, and a 5-bit type field
+//!   (`0x1F`).
+//!
+//! A frame with no flags and a type below 8, such as the handshake's
+//! `AuthMessage::Hello`, encodes the same under both, so a peer still reads
+//! the other's version and can refuse it.
+
 use bitflags::bitflags;
+use bytes::BufMut;
+use crate::checksum::{self, CHECKSUM_LEN};
 use crate::error::Error;
+extern crate alloc;
+use alloc::vec::Vec;
+
+/// Bits of the first header byte that hold the [`FrameType`]; the rest are [`FrameFlags`].
+pub const TYPE_MASK: u8 = 0x07;
+
+/// Largest possible encoded header: type byte plus three 8-byte varints.
+pub const MAX_HEADER_LEN: usize = 1 + 3 * 8;
 
 /// The type of data contained in the frame payload.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -11,16 +60,45 @@ pub enum FrameType {
     RkyvAligned = 0x01,
     /// UTF-8 encoded text.
     Utf8Text = 0x02,
+    /// zstd-compressed payload: `[original_type_byte][zstd bytes]`.
+    /// See the `compress` module (`zstd` feature).
+    Compressed = 0x03,
+    /// A UTF-8 JSON document, for peers that do not speak rkyv.
+    /// See the client's `serde` feature.
+    Json = 0x04,
+    /// A Protocol Buffers message. Which message type a channel carries is
+    /// up to the application; see the client's `prost` feature.
+    Protobuf = 0x05,
     /// Reserved for future extensions or custom commands.
+    ///
+    /// Produced by the lenient `From<u8>` and by
+    /// [`FrameHeader::decode_lenient`] for type bits this version does not
+    /// know. Never encoded: a header carrying it fails to encode.
     Unknown = 0xFF,
 }
 
+impl FrameType {
+    /// Strict conversion: the known types only. Any other value, including
+    /// the reserved type bits, is [`Error::InvalidFrameType`].
+    pub fn try_from_bits(bits: u8) -> Result<Self, Error> {
+        match FrameType::from(bits) {
+            FrameType::Unknown => Err(Error::InvalidFrameType(bits)),
+            known => Ok(known),
+        }
+    }
+}
+
+/// Lenient conversion: unrecognized values become [`FrameType::Unknown`].
+/// Use [`FrameType::try_from_bits`] to reject them instead.
 impl From<u8> for FrameType {
     fn from(value: u8) -> Self {
         match value {
             0x00 => FrameType::RawBinary,
             0x01 => FrameType::RkyvAligned,
             0x02 => FrameType::Utf8Text,
+            0x03 => FrameType::Compressed,
+            0x04 => FrameType::Json,
+            0x05 => FrameType::Protobuf,
             _ => FrameType::Unknown,
         }
     }
@@ -29,9 +107,9 @@ impl From<u8> for FrameType {
 bitflags! {
     /// Header flags for controlling frame processing.
     ///
-    /// Layout (3 bits used):
-    /// | 7 | 6 | 5 | 4 | 3 |  2  |  1  |  0  |
-    /// | - | - | - | - | - | Res | Pri | Ctl |
+    /// Layout (top 5 bits of the first header byte; the low 3 hold the type):
+    /// |  7  |  6  |  5  |  4  |  3  | 2..0 |
+    /// | Ctl | Pri | Dgm | Frg | Crc | Type |
     #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
     pub struct FrameFlags: u8 {
         /// Control Message (Ping, Pong, Close). 
@@ -41,18 +119,60 @@ bitflags! {
         /// High Priority.
         /// Should be processed immediately, bypassing normal queues if possible.
         const PRIORITY = 0b0100_0000; // Bit 6
-        
-        // Bit 5 is reserved for future use
+
+        /// Sent as an unreliable QUIC datagram rather than on a stream.
+        /// May be dropped, duplicated or arrive out of order.
+        const DATAGRAM = 0b0010_0000; // Bit 5
+
+        /// More fragments of this message follow.
+        /// Set on every fragment except the last; fragments of one message
+        /// share `channel_id` and `stream_id` and are joined in arrival order.
+        const FRAGMENTED = 0b0001_0000; // Bit 4
+
+        /// The payload is followed by a 4-byte big-endian CRC32 of it, which
+        /// `length` includes. Opt-in integrity for frames that cross non-QUIC
+        /// hops; see the `checksum` module.
+        const CHECKSUM = 0b0000_1000; // Bit 3
+    }
+}
+
+impl FrameFlags {
+    /// Names of the set flags (e.g. `"CONTROL"`, `"PRIORITY"`), for logging.
+    pub fn names(&self) -> impl Iterator<Item = &'static str> {
+        self.iter_names().map(|(name, _)| name)
+    }
+}
+
+impl core::str::FromStr for FrameFlags {
+    type Err = Error;
+
+    /// Parses a comma-separated list of flag names, case-insensitively.
+    /// An empty string yields no flags.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut flags = FrameFlags::empty();
+        for name in s.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+            let (_, flag) = FrameFlags::all()
+                .iter_names()
+                .find(|(known, _)| known.eq_ignore_ascii_case(name))
+                .ok_or(Error::UnknownFlag)?;
+            flags |= flag;
+        }
+        Ok(flags)
     }
 }
 
 /// The wire-format header for an Orzatty Frame.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct FrameHeader {
     pub flags: FrameFlags,
     pub frame_type: FrameType,
     pub channel_id: u32, // Application-level multiplexing
+    /// Protocol-level stream identifier. Opaque to the framing layer: it is
+    /// *not* required to match the QUIC transport stream the frame travels on,
+    /// so applications may use it as a logical id (e.g. a game object id).
     pub stream_id: u64,
+    /// Bytes following the header, including the CRC32 trailer of a
+    /// `CHECKSUM` frame.
     pub length: u64,
 }
 
@@ -62,15 +182,21 @@ impl FrameHeader {
     pub fn encode(&self, buf: &mut [u8]) -> Result<usize, Error> {
         let mut offset = 0;
         
-        if buf.len() < 1 {
+        if buf.is_empty() {
             return Err(Error::BufferTooSmall { needed: 1, available: buf.len() });
         }
 
-        let type_bits = (self.frame_type as u8) & 0x1F;
+        if self.frame_type == FrameType::Unknown {
+            return Err(Error::InvalidFrameType(self.frame_type as u8));
+        }
+        let type_bits = (self.frame_type as u8) & TYPE_MASK;
         let mut first_byte = type_bits;
         
         if self.flags.contains(FrameFlags::CONTROL) { first_byte |= 0b1000_0000; }
         if self.flags.contains(FrameFlags::PRIORITY) { first_byte |= 0b0100_0000; }
+        if self.flags.contains(FrameFlags::DATAGRAM) { first_byte |= 0b0010_0000; }
+        if self.flags.contains(FrameFlags::FRAGMENTED) { first_byte |= 0b0001_0000; }
+        if self.flags.contains(FrameFlags::CHECKSUM) { first_byte |= 0b0000_1000; }
         
         buf[offset] = first_byte;
         offset += 1;
@@ -82,18 +208,101 @@ impl FrameHeader {
         Ok(offset)
     }
 
+    /// Exactly how many bytes [`encode`](Self::encode) writes for this
+    /// header: the type byte plus its three varints. At most
+    /// [`MAX_HEADER_LEN`].
+    pub fn encoded_len(&self) -> usize {
+        1 + varint_len(self.channel_id as u64) + varint_len(self.stream_id) + varint_len(self.length)
+    }
+
+    /// Appends the encoded header followed by `payload` to `out`, so the whole
+    /// frame can go out in a single write.
+    ///
+    /// The encoded `length` is always `payload.len()`, whatever `self.length`
+    /// says, plus the trailer if `CHECKSUM` is set; the trailer is appended too.
+    pub fn encode_with_payload(&self, payload: &[u8], out: &mut Vec<u8>) -> Result<(), Error> {
+        out.reserve(MAX_HEADER_LEN + payload.len() + CHECKSUM_LEN);
+        self.put_with_payload(payload, out)
+    }
+
+    /// [`encode_with_payload`](Self::encode_with_payload) into a `BytesMut`.
+    #[cfg(feature = "quinn")]
+    pub fn encode_with_payload_bytes(&self, payload: &[u8], out: &mut bytes::BytesMut) -> Result<(), Error> {
+        out.reserve(MAX_HEADER_LEN + payload.len() + CHECKSUM_LEN);
+        self.put_with_payload(payload, out)
+    }
+
+    /// Shared body of the `encode_with_payload` variants; the caller reserves.
+    fn put_with_payload(&self, payload: &[u8], out: &mut impl BufMut) -> Result<(), Error> {
+        let header = self.framing(payload);
+        let mut head = [0u8; MAX_HEADER_LEN];
+        let n = header.encode(&mut head)?;
+        out.put_slice(&head[..n]);
+        out.put_slice(payload);
+        if self.flags.contains(FrameFlags::CHECKSUM) {
+            out.put_slice(&checksum::trailer(payload));
+        }
+        Ok(())
+    }
+
+    /// This header with `length` set for `payload` and any trailer.
+    fn framing(&self, payload: &[u8]) -> FrameHeader {
+        let trailer = if self.flags.contains(FrameFlags::CHECKSUM) { CHECKSUM_LEN } else { 0 };
+        FrameHeader { length: (payload.len() + trailer) as u64, ..*self }
+    }
+
+    /// Returns the payload of a frame body, checking and dropping the CRC32
+    /// trailer if `CHECKSUM` is set. Other bodies are returned unchanged.
+    pub fn strip_checksum<'a>(&self, body: &'a [u8]) -> Result<&'a [u8], Error> {
+        if self.flags.contains(FrameFlags::CHECKSUM) {
+            checksum::strip(body)
+        } else {
+            Ok(body)
+        }
+    }
+
     /// Decodes the header from a byte buffer.
+    ///
+    /// Fails with [`Error::InvalidFrameType`] if the type bits do not name a
+    /// known [`FrameType`].
     pub fn decode(buf: &[u8]) -> Result<(Self, usize), Error> {
+        Self::decode_with(buf, false, false)
+    }
+
+    /// Like [`decode`](Self::decode), but type bits reserved for future
+    /// types decode as [`FrameType::Unknown`] instead of failing. The rest of
+    /// the header is still read, so a frame sent by a newer peer can be
+    /// skipped by its length.
+    pub fn decode_lenient(buf: &[u8]) -> Result<(Self, usize), Error> {
+        Self::decode_with(buf, false, true)
+    }
+
+    /// Like [`decode`](Self::decode), but also fails with
+    /// [`Error::InvalidVarInt`] if any varint is overlong (see
+    /// [`decode_varint_canonical`]), so every header has exactly one encoding.
+    pub fn decode_canonical(buf: &[u8]) -> Result<(Self, usize), Error> {
+        Self::decode_with(buf, true, false)
+    }
+
+    pub(crate) fn decode_with(buf: &[u8], canonical: bool, lenient_type: bool) -> Result<(Self, usize), Error> {
         if buf.is_empty() { 
             return Err(Error::IncompleteInput { needed_min: 1, available: 0 }); 
         }
+        let decode_varint = if canonical { decode_varint_canonical } else { decode_varint };
         
         let first_byte = buf[0];
         let mut flags = FrameFlags::empty();
         if first_byte & 0b1000_0000 != 0 { flags |= FrameFlags::CONTROL; }
         if first_byte & 0b0100_0000 != 0 { flags |= FrameFlags::PRIORITY; }
+        if first_byte & 0b0010_0000 != 0 { flags |= FrameFlags::DATAGRAM; }
+        if first_byte & 0b0001_0000 != 0 { flags |= FrameFlags::FRAGMENTED; }
+        if first_byte & 0b0000_1000 != 0 { flags |= FrameFlags::CHECKSUM; }
         
-        let frame_type = FrameType::from(first_byte & 0x1F);
+        let frame_type = if lenient_type {
+            FrameType::from(first_byte & TYPE_MASK)
+        } else {
+            FrameType::try_from_bits(first_byte & TYPE_MASK)?
+        };
         
         let mut offset = 1;
         
@@ -117,10 +326,182 @@ impl FrameHeader {
     }
 }
 
+/// Builds frames back to back in a caller-provided buffer, without allocating.
+///
+/// For `no_std` targets that cannot use `Vec` or `BytesMut`:
+///
+/// ```
+/// # use orzatty_core::frame::{FrameFlags, FrameHeader, FrameType, FrameWriter};
+/// let mut buf = [0u8; 64];
+/// let mut writer = FrameWriter::new(&mut buf);
+/// let header = FrameHeader { flags: FrameFlags::empty(), frame_type: FrameType::RawBinary, channel_id: 1, stream_id: 0, length: 0 };
+/// writer.write_frame(&header, b"hello")?;
+/// writer.write_frame(&header, b"world")?;
+/// // transmit(writer.written());
+/// # Ok::<(), orzatty_core::Error>(())
+/// ```
+pub struct FrameWriter<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+}
+
+impl<'a> FrameWriter<'a> {
+    pub fn new(buf: &'a mut [u8]) -> Self {
+        Self { buf, len: 0 }
+    }
+
+    /// Appends one frame, like [`FrameHeader::encode_with_payload`], and
+    /// returns its size.
+    ///
+    /// If it does not fit, nothing is written and the error says how many
+    /// bytes the frame needs against how many are left.
+    pub fn write_frame(&mut self, header: &FrameHeader, payload: &[u8]) -> Result<usize, Error> {
+        let header = header.framing(payload);
+        let mut head = [0u8; MAX_HEADER_LEN];
+        let n = header.encode(&mut head)?;
+        let checksum = header.flags.contains(FrameFlags::CHECKSUM);
+        let needed = n + header.length as usize;
+        let available = self.remaining();
+        if needed > available {
+            return Err(Error::BufferTooSmall { needed, available });
+        }
+
+        let out = &mut self.buf[self.len..self.len + needed];
+        out[..n].copy_from_slice(&head[..n]);
+        out[n..n + payload.len()].copy_from_slice(payload);
+        if checksum {
+            out[n + payload.len()..].copy_from_slice(&checksum::trailer(payload));
+        }
+        self.len += needed;
+        Ok(needed)
+    }
+
+    /// The frames written so far.
+    pub fn written(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Bytes still free in the buffer.
+    pub fn remaining(&self) -> usize {
+        self.buf.len() - self.len
+    }
+
+    /// Starts over at the beginning of the buffer, e.g. after a send.
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+}
+
+/// Builds one frame around a payload, deriving `length` from it.
+///
+/// Defaults to a `RawBinary` frame on channel 0, stream 0, with no flags:
+///
+/// ```
+/// # use orzatty_core::frame::{FrameBuilder, FrameType};
+/// let frame = FrameBuilder::new(b"hello")
+///     .channel(7)
+///     .frame_type(FrameType::Utf8Text)
+///     .priority()
+///     .to_bytes()?;
+/// # Ok::<(), orzatty_core::Error>(())
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct FrameBuilder<'a> {
+    header: FrameHeader,
+    payload: &'a [u8],
+}
+
+impl<'a> FrameBuilder<'a> {
+    pub fn new(payload: &'a [u8]) -> Self {
+        Self {
+            header: FrameHeader {
+                flags: FrameFlags::empty(),
+                frame_type: FrameType::RawBinary,
+                channel_id: 0,
+                stream_id: 0,
+                length: 0,
+            },
+            payload,
+        }
+    }
+
+    pub fn channel(mut self, channel_id: u32) -> Self {
+        self.header.channel_id = channel_id;
+        self
+    }
+
+    pub fn stream(mut self, stream_id: u64) -> Self {
+        self.header.stream_id = stream_id;
+        self
+    }
+
+    pub fn frame_type(mut self, frame_type: FrameType) -> Self {
+        self.header.frame_type = frame_type;
+        self
+    }
+
+    /// Adds `flags` to those already set.
+    pub fn flags(mut self, flags: FrameFlags) -> Self {
+        self.header.flags |= flags;
+        self
+    }
+
+    pub fn priority(self) -> Self {
+        self.flags(FrameFlags::PRIORITY)
+    }
+
+    pub fn control(self) -> Self {
+        self.flags(FrameFlags::CONTROL)
+    }
+
+    pub fn datagram(self) -> Self {
+        self.flags(FrameFlags::DATAGRAM)
+    }
+
+    /// Appends a CRC32 trailer (see [`FrameFlags::CHECKSUM`]).
+    pub fn checksum(self) -> Self {
+        self.flags(FrameFlags::CHECKSUM)
+    }
+
+    /// The header as it goes on the wire, `length` included.
+    pub fn header(&self) -> FrameHeader {
+        let trailer = if self.header.flags.contains(FrameFlags::CHECKSUM) { CHECKSUM_LEN } else { 0 };
+        FrameHeader { length: (self.payload.len() + trailer) as u64, ..self.header }
+    }
+
+    /// Writes the frame at the start of `buf` and returns its size; see
+    /// [`FrameWriter::write_frame`].
+    pub fn build_into(&self, buf: &mut [u8]) -> Result<usize, Error> {
+        FrameWriter::new(buf).write_frame(&self.header, self.payload)
+    }
+
+    /// The encoded frame: header, payload and any trailer.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        let mut out = Vec::new();
+        self.header.encode_with_payload(self.payload, &mut out)?;
+        Ok(out)
+    }
+}
+
 // Minimal VarInt implementation (QUIC-style: 2 bits length, 6/14/30/62 bits value)
-fn encode_varint(v: u64, buf: &mut [u8]) -> Result<usize, Error> {
+
+/// Encodes `v` as a QUIC variable-length integer, the shortest form that
+/// fits, and returns the bytes written. Values above [`MAX_VARINT`] fail
+/// with [`Error::InvalidVarInt`].
+pub fn encode_varint(v: u64, buf: &mut [u8]) -> Result<usize, Error> {
+    if v > MAX_VARINT {
+        return Err(Error::InvalidVarInt);
+    }
     if v <= 63 {
-        if buf.len() < 1 { return Err(Error::BufferTooSmall { needed: 1, available: 0 }); }
+        if buf.is_empty() { return Err(Error::BufferTooSmall { needed: 1, available: 0 }); }
         buf[0] = v as u8;
         Ok(1)
     } else if v <= 16383 {
@@ -141,7 +522,20 @@ fn encode_varint(v: u64, buf: &mut [u8]) -> Result<usize, Error> {
     }
 }
 
-fn decode_varint(buf: &[u8]) -> Result<(u64, usize), Error> {
+/// How many bytes [`encode_varint`] writes for `v`: 1, 2, 4 or 8. Values
+/// too large to encode count as 8.
+pub fn varint_len(v: u64) -> usize {
+    match v {
+        0..=63 => 1,
+        64..=16383 => 2,
+        16384..=1073741823 => 4,
+        _ => 8,
+    }
+}
+
+/// Decodes a QUIC variable-length integer from the start of `buf`, in any
+/// of its lengths. Returns the value and the bytes read.
+pub fn decode_varint(buf: &[u8]) -> Result<(u64, usize), Error> {
     if buf.is_empty() { return Err(Error::IncompleteInput { needed_min: 1, available: 0 }); }
     let first = buf[0];
     let prefix = first >> 6;
@@ -162,6 +556,40 @@ fn decode_varint(buf: &[u8]) -> Result<(u64, usize), Error> {
     Ok((res, length))
 }
 
+/// Like the header's varint decoding, but rejects non-minimal encodings with
+/// [`Error::InvalidVarInt`]: `5` must take one byte, not two, four or eight.
+/// Returns the value and the bytes read.
+pub fn decode_varint_canonical(buf: &[u8]) -> Result<(u64, usize), Error> {
+    let (value, len) = decode_varint(buf)?;
+    if len != varint_len(value) {
+        return Err(Error::InvalidVarInt);
+    }
+    Ok((value, len))
+}
+
+/// Largest value a varint can hold (62 bits).
+pub const MAX_VARINT: u64 = (1 << 62) - 1;
+
+/// Encodes a signed value as a zigzag varint, so small magnitudes of either
+/// sign stay short (`-1` takes one byte, not eight). Returns the bytes written.
+///
+/// The 62-bit varint range leaves 61 bits of magnitude: values outside
+/// `-2^61..2^61` (including `i64::MIN`) fail with [`Error::InvalidVarInt`].
+pub fn encode_varint_signed(v: i64, buf: &mut [u8]) -> Result<usize, Error> {
+    let zigzag = ((v << 1) ^ (v >> 63)) as u64;
+    if zigzag > MAX_VARINT {
+        return Err(Error::InvalidVarInt);
+    }
+    encode_varint(zigzag, buf)
+}
+
+/// Decodes a zigzag varint written by [`encode_varint_signed`]. Returns the
+/// value and the bytes read.
+pub fn decode_varint_signed(buf: &[u8]) -> Result<(i64, usize), Error> {
+    let (zigzag, len) = decode_varint(buf)?;
+    Ok(((zigzag >> 1) as i64 ^ -((zigzag & 1) as i64), len))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -192,6 +620,70 @@ mod tests {
         assert_eq!(written, read_bytes);
     }
     
+    #[test]
+    fn test_every_type_byte() {
+        for byte in 0..=u8::MAX {
+            let known = (byte & TYPE_MASK) <= FrameType::Protobuf as u8;
+            match FrameHeader::decode(&[byte, 0, 0, 0]) {
+                Ok((header, _)) => {
+                    assert!(known, "accepted {:#04x}", byte);
+                    assert_eq!(header.frame_type as u8, byte & TYPE_MASK);
+                    assert_eq!(header.flags.bits(), byte & !TYPE_MASK);
+                }
+                Err(e) => {
+                    assert!(!known, "rejected {:#04x}", byte);
+                    assert_eq!(e, Error::InvalidFrameType(byte & TYPE_MASK));
+                }
+            }
+            assert_eq!(FrameType::try_from_bits(byte).is_ok(), byte <= FrameType::Protobuf as u8);
+        }
+
+        let unknown = FrameHeader { flags: FrameFlags::empty(), frame_type: FrameType::Unknown, channel_id: 1, stream_id: 0, length: 0 };
+        assert_eq!(unknown.encode(&mut [0u8; 8]), Err(Error::InvalidFrameType(0xFF)));
+
+        // Leniently, a reserved type still yields the rest of the header
+        let (header, len) = FrameHeader::decode_lenient(&[0x07 | 0x40, 3, 0, 5]).unwrap();
+        assert_eq!(header, FrameHeader { flags: FrameFlags::PRIORITY, frame_type: FrameType::Unknown, channel_id: 3, stream_id: 0, length: 5 });
+        assert_eq!(len, 4);
+    }
+
+    #[test]
+    fn test_frame_writer_fills_slice() {
+        let header = FrameHeader { flags: FrameFlags::CHECKSUM, frame_type: FrameType::Utf8Text, channel_id: 9, stream_id: 1, length: 0 };
+        let mut buf = [0u8; 24];
+        let mut writer = FrameWriter::new(&mut buf);
+        assert_eq!(writer.write_frame(&header, b"first"), Ok(13));
+        assert_eq!(writer.write_frame(&header, b"second!"), Err(Error::BufferTooSmall { needed: 15, available: 11 }));
+        assert_eq!(writer.len(), 13);
+        assert_eq!(writer.write_frame(&header, b"nxt"), Ok(11));
+        assert_eq!(writer.remaining(), 0);
+
+        let mut expected = Vec::new();
+        header.encode_with_payload(b"first", &mut expected).unwrap();
+        header.encode_with_payload(b"nxt", &mut expected).unwrap();
+        assert_eq!(writer.written(), &expected[..]);
+
+        writer.clear();
+        assert!(writer.is_empty());
+    }
+
+    #[test]
+    fn test_frame_builder_derives_length() {
+        let builder = FrameBuilder::new(b"hello").channel(7).stream(9).frame_type(FrameType::Utf8Text).priority().checksum();
+        let frame = builder.to_bytes().unwrap();
+        let (header, n) = FrameHeader::decode(&frame).unwrap();
+        assert_eq!(header, builder.header());
+        assert_eq!(header.flags, FrameFlags::PRIORITY | FrameFlags::CHECKSUM);
+        assert_eq!((header.channel_id, header.stream_id), (7, 9));
+        assert_eq!(header.length as usize, 5 + CHECKSUM_LEN);
+        assert_eq!(header.strip_checksum(&frame[n..]).unwrap(), b"hello");
+
+        let mut buf = [0u8; 32];
+        assert_eq!(builder.build_into(&mut buf), Ok(frame.len()));
+        assert_eq!(&buf[..frame.len()], &frame[..]);
+        assert_eq!(builder.build_into(&mut buf[..4]), Err(Error::BufferTooSmall { needed: frame.len(), available: 4 }));
+    }
+
     #[test]
     fn test_varint() {
         let mut buf = [0u8; 8];
@@ -203,6 +695,104 @@ mod tests {
         assert_eq!(n, read);
     }
 
+    #[test]
+    fn test_overlong_varints_rejected() {
+        // 5 in the 2-, 4- and 8-byte forms, then the smallest value of each
+        // class one size up
+        let overlong: [&[u8]; 6] = [
+            &[0x40, 0x05],
+            &[0x80, 0, 0, 0x05],
+            &[0xC0, 0, 0, 0, 0, 0, 0, 0x05],
+            &[0x80, 0, 0x3F, 0xFF],
+            &[0xC0, 0, 0, 0, 0x3F, 0xFF, 0xFF, 0xFF],
+            &[0xC0, 0, 0, 0, 0, 0, 0, 0],
+        ];
+        for bytes in overlong {
+            let (value, len) = decode_varint(bytes).unwrap();
+            assert_eq!(len, bytes.len());
+            assert_eq!(decode_varint_canonical(bytes), Err(Error::InvalidVarInt), "{} in {} bytes", value, len);
+        }
+
+        let mut buf = [0u8; 8];
+        for value in [0, 63, 64, 16383, 16384, 1073741823, 1073741824, MAX_VARINT] {
+            let n = encode_varint(value, &mut buf).unwrap();
+            assert_eq!(decode_varint_canonical(&buf[..n]), Ok((value, n)));
+        }
+
+        // Channel id 1 written in two bytes
+        let header = [FrameType::RawBinary as u8, 0x40, 0x01, 0x00, 0x00];
+        assert!(FrameHeader::decode(&header).is_ok());
+        assert_eq!(FrameHeader::decode_canonical(&header), Err(Error::InvalidVarInt));
+    }
+
+    #[test]
+    fn test_encoded_len_matches_encode() {
+        let mut buf = [0u8; MAX_HEADER_LEN];
+        // Both edges of every varint size class
+        let values = [0, 63, 64, 16383, 16384, 1073741823, 1073741824, MAX_VARINT];
+        for &channel_id in &[0, 63, 64, 16383, 16384, 1073741823, 1073741824, u32::MAX] {
+            for &stream_id in &values {
+                for &length in &values {
+                    let header = FrameHeader {
+                        flags: FrameFlags::PRIORITY,
+                        frame_type: FrameType::RawBinary,
+                        channel_id,
+                        stream_id,
+                        length,
+                    };
+                    let n = header.encode(&mut buf).unwrap();
+                    assert_eq!(header.encoded_len(), n, "{:?}", header);
+                }
+            }
+        }
+        assert_eq!(varint_len(MAX_VARINT), 8);
+    }
+
+    #[test]
+    fn test_signed_varint() {
+        let mut buf = [0u8; 8];
+        let limit = 1i64 << 61;
+        for (val, len) in [(0, 1), (-1, 1), (1, 1), (-32, 1), (32, 2), (-8192, 2), (-limit, 8), (limit - 1, 8)] {
+            let n = encode_varint_signed(val, &mut buf).unwrap();
+            assert_eq!(n, len, "length of {}", val);
+            assert_eq!(decode_varint_signed(&buf[..n]).unwrap(), (val, n));
+        }
+
+        // Beyond 61 bits of magnitude there is no room in a varint
+        assert_eq!(encode_varint_signed(i64::MIN, &mut buf), Err(Error::InvalidVarInt));
+        assert_eq!(encode_varint_signed(limit, &mut buf), Err(Error::InvalidVarInt));
+    }
+
+    #[test]
+    fn test_flag_names_round_trip() {
+        let flags = FrameFlags::CONTROL | FrameFlags::PRIORITY;
+        let mut names = flags.names();
+        assert_eq!(names.next(), Some("CONTROL"));
+        assert_eq!(names.next(), Some("PRIORITY"));
+        assert_eq!(names.next(), None);
+
+        assert_eq!("control, Priority".parse::<FrameFlags>(), Ok(flags));
+        assert_eq!("".parse::<FrameFlags>(), Ok(FrameFlags::empty()));
+        assert_eq!("CONTROL,BOGUS".parse::<FrameFlags>(), Err(Error::UnknownFlag));
+    }
+
+    #[test]
+    fn test_encode_with_payload() {
+        let header = FrameHeader {
+            flags: FrameFlags::empty(),
+            frame_type: FrameType::RawBinary,
+            channel_id: 5,
+            stream_id: 70000,
+            length: 999, // overridden by the payload length
+        };
+        let mut out = vec![0xAA];
+        header.encode_with_payload(b"abc", &mut out).unwrap();
+
+        let (decoded, n) = FrameHeader::decode(&out[1..]).unwrap();
+        assert_eq!(decoded, FrameHeader { length: 3, ..header });
+        assert_eq!(&out[1 + n..], b"abc");
+    }
+
     #[test]
     fn test_errors() {
         let mut buf = [0u8; 1];
@@ -212,4 +802,69 @@ mod tests {
             _ => panic!("Should have failed with BufferTooSmall"),
         }
     }
+
+    mod proptests {
+        use super::*;
+        use proptest::prelude::*;
+
+        /// Varint values biased towards the 1/2/4/8-byte size class edges.
+        fn varint() -> impl Strategy<Value = u64> {
+            prop_oneof![
+                prop::sample::select(vec![
+                    0, 63, 64, 16383, 16384, 1073741823, 1073741824, MAX_VARINT,
+                ]),
+                0..=MAX_VARINT,
+            ]
+        }
+
+        /// Every type a header can carry: whatever the strict conversion
+        /// accepts, so a newly added variant is covered without listing it.
+        fn frame_type() -> impl Strategy<Value = FrameType> {
+            let known: Vec<_> = (0..=u8::MAX).filter_map(|bits| FrameType::try_from_bits(bits).ok()).collect();
+            prop::sample::select(known)
+        }
+
+        fn header() -> impl Strategy<Value = FrameHeader> {
+            (
+                any::<u8>(),
+                frame_type(),
+                prop_oneof![
+                    prop::sample::select(vec![0u32, 63, 64, 16383, 16384, 1073741823, 1073741824, u32::MAX]),
+                    any::<u32>(),
+                ],
+                varint(),
+                varint(),
+            ).prop_map(|(flag_bits, frame_type, channel_id, stream_id, length)| {
+                let flags = FrameFlags::from_bits_truncate(flag_bits);
+                FrameHeader { flags, frame_type, channel_id, stream_id, length }
+            })
+        }
+
+        proptest! {
+            #[test]
+            fn prop_header_round_trip(h in header()) {
+                let mut buf = [0u8; MAX_HEADER_LEN];
+                let written = h.encode(&mut buf).unwrap();
+                let (decoded, read) = FrameHeader::decode(&buf[..written]).unwrap();
+
+                prop_assert_eq!(written, h.encoded_len());
+                prop_assert_eq!(decoded, h);
+                prop_assert_eq!(read, written);
+            }
+
+            #[test]
+            fn prop_varint_round_trip(v in varint()) {
+                let mut buf = [0u8; 8];
+                let n = encode_varint(v, &mut buf).unwrap();
+                prop_assert_eq!(decode_varint(&buf[..n]).unwrap(), (v, n));
+            }
+
+            #[test]
+            fn prop_signed_varint_round_trip(v in -(1i64 << 61)..(1i64 << 61)) {
+                let mut buf = [0u8; 8];
+                let n = encode_varint_signed(v, &mut buf).unwrap();
+                prop_assert_eq!(decode_varint_signed(&buf[..n]).unwrap(), (v, n));
+            }
+        }
+    }
 }
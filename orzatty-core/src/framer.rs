@@ -4,38 +4,118 @@
 //! managing buffering for fragmentation and coalescing.
 
 use crate::frame::FrameHeader;
-use crate::error::Error;
-use bytes::{BytesMut, Buf};
+#[cfg(feature = "zstd")]
+use crate::frame::FrameType;
+use crate::decoder::{FrameDecoder, LimitAction};
+use bytes::BytesMut;
 use quinn::RecvStream;
-use anyhow::{Result, anyhow};
+use std::time::Duration;
+use anyhow::Result;
+#[cfg(feature = "zstd")]
+use anyhow::anyhow;
+
+pub use crate::decoder::{DEFAULT_HIGH_WATER_MARK, DEFAULT_MAX_FRAME_LEN};
 
 /// Handles reading frames from a QUIC stream, managing buffering 
 /// for fragmentation and coalescing.
+///
+/// Parsing is done by a [`FrameDecoder`]; the framer only feeds it stream reads.
 pub struct Framer {
-    buffer: BytesMut,
+    decoder: FrameDecoder,
+    // Scratch space for stream reads, allocated once
+    read_buf: Vec<u8>,
+    #[cfg(feature = "zstd")]
+    decompress: bool,
 }
 
 impl Framer {
     /// Create a new Framer with default buffer capacity.
     pub fn new() -> Self {
-        Self {
-            buffer: BytesMut::with_capacity(4096),
-        }
+        Self::with_capacity(4096)
     }
 
     /// Create a new Framer with custom initial buffer capacity.
     pub fn with_capacity(capacity: usize) -> Self {
         Self {
-            buffer: BytesMut::with_capacity(capacity),
+            decoder: FrameDecoder::with_capacity(capacity),
+            read_buf: vec![0u8; 4096],
+            #[cfg(feature = "zstd")]
+            decompress: false,
         }
     }
 
+    /// See [`FrameDecoder::with_high_water_mark`].
+    pub fn with_high_water_mark(mut self, bytes: usize) -> Self {
+        self.decoder = self.decoder.with_high_water_mark(bytes);
+        self
+    }
+
+    /// See [`FrameDecoder::with_max_frame_len`]. Also caps inflated
+    /// `Compressed` payloads.
+    pub fn with_max_frame_len(mut self, bytes: usize) -> Self {
+        self.decoder = self.decoder.with_max_frame_len(bytes);
+        self
+    }
+
+    /// See [`FrameDecoder::with_resync`].
+    pub fn with_resync(mut self, max_skip: usize) -> Self {
+        self.decoder = self.decoder.with_resync(max_skip);
+        self
+    }
+
+    /// See [`FrameDecoder::with_reject_empty`].
+    pub fn with_reject_empty(mut self) -> Self {
+        self.decoder = self.decoder.with_reject_empty();
+        self
+    }
+
+    /// See [`FrameDecoder::with_unknown_types`].
+    pub fn with_unknown_types(mut self) -> Self {
+        self.decoder = self.decoder.with_unknown_types();
+        self
+    }
+
+    /// See [`FrameDecoder::with_validate_utf8`]. Also checks `Utf8Text`
+    /// frames inflated from `Compressed` ones.
+    pub fn with_validate_utf8(mut self) -> Self {
+        self.decoder = self.decoder.with_validate_utf8();
+        self
+    }
+
+    /// See [`FrameDecoder::set_channel_limit`].
+    pub fn set_channel_limit(&mut self, channel_id: u32, max_bytes: usize, action: LimitAction) {
+        self.decoder.set_channel_limit(channel_id, max_bytes, action);
+    }
+
+    /// See [`FrameDecoder::clear_channel_limit`].
+    pub fn clear_channel_limit(&mut self, channel_id: u32) {
+        self.decoder.clear_channel_limit(channel_id);
+    }
+
+    /// When enabled, `Compressed` frames are inflated before being returned:
+    /// the caller sees the original frame type, length and payload.
+    #[cfg(feature = "zstd")]
+    pub fn with_decompress(mut self, enabled: bool) -> Self {
+        self.decompress = enabled;
+        self
+    }
+
+    /// Hands a payload buffer back for reuse by later frames.
+    ///
+    /// Optional: payloads that are simply dropped are freed as usual. Recycling
+    /// is what keeps steady-state small-frame traffic allocation-free.
+    pub fn recycle(&mut self, payload: BytesMut) {
+        self.decoder.recycle(payload);
+    }
+
     /// Reads from the stream and tries to return the next complete frame payload.
     /// 
     /// Returns:
     /// - `Ok(Some((Header, BytesMut)))`: A complete frame.
     /// - `Ok(None)`: Stream finished cleanly.
-    /// - `Err`: IO error or protocol violation.
+    /// - `Err`: IO error or protocol violation. A stream that ends mid-frame
+    ///   yields [`Error::TruncatedFrame`](crate::Error::TruncatedFrame); the
+    ///   leftover bytes are available from [`into_remaining`](Self::into_remaining).
     pub async fn read_frame(&mut self, stream: &mut RecvStream) -> Result<Option<(FrameHeader, BytesMut)>> {
         loop {
             // 1. Try to parse a frame from the current buffer
@@ -44,86 +124,105 @@ impl Framer {
             }
 
             // 2. If no complete frame, read more data from the network
-            // We reserve space to avoid frequent allocations
-            if self.buffer.capacity() < 1024 {
-                self.buffer.reserve(4096);
-            }
-
-            // Read into a temporary buffer and extend BytesMut
             // Quinn's RecvStream::read returns Result<Option<usize>>
-            let mut temp_buf = vec![0u8; 4096];
-            match stream.read(&mut temp_buf).await? {
+            match stream.read(&mut self.read_buf).await? {
                 Some(0) => {
                     // unexpected EOF if we have partial data
-                    if !self.buffer.is_empty() {
-                         return Err(anyhow!("Stream closed with partial frame data"));
-                    }
+                    self.decoder.finish()?;
                     return Ok(None);
                 }
                 Some(n) => {
-                    // Extend the buffer with the read data
-                    self.buffer.extend_from_slice(&temp_buf[..n]);
+                    self.decoder.push(&self.read_buf[..n]);
                     // Loop continues to try parsing again
                     continue;
                 }
                 None => {
-                    if !self.buffer.is_empty() {
-                        return Err(anyhow!("Stream closed with partial frame data"));
-                    }
+                    self.decoder.finish()?;
                     return Ok(None);
                 }
             }
         }
     }
 
-    fn parse_frame(&mut self) -> Result<Option<(FrameHeader, BytesMut)>> {
-        // We need at least 1 byte to start decoding header
-        if self.buffer.is_empty() {
-            return Ok(None);
+    /// Like [`read_frame`](Self::read_frame), but fails with
+    /// [`Error::ReadTimeout`](crate::Error::ReadTimeout) if no complete frame
+    /// arrives within `timeout`, e.g. a peer that sends half a header and goes
+    /// silent.
+    ///
+    /// Bytes received before the deadline stay buffered, so the call can be
+    /// retried.
+    pub async fn read_frame_timeout(&mut self, stream: &mut RecvStream, timeout: Duration) -> Result<Option<(FrameHeader, BytesMut)>> {
+        match tokio::time::timeout(timeout, self.read_frame(stream)).await {
+            Ok(frame) => frame,
+            Err(_) => Err(crate::Error::ReadTimeout(timeout).into()),
         }
+    }
 
-        match FrameHeader::decode(&self.buffer) {
-            Ok((header, head_len)) => {
-                let payload_len = header.length as usize;
-                let total_len = head_len + payload_len;
-
-                // Check if we have the full payload
-                if self.buffer.len() >= total_len {
-                    // Advance buffer past header
-                    self.buffer.advance(head_len);
-                    
-                    // split_to returns the payload and advances state
-                    let payload = self.buffer.split_to(payload_len);
-                    
-                    Ok(Some((header, payload)))
-                } else {
-                    // We have the header but not the full payload
-                    Ok(None)
-                }
-            }
-            Err(Error::IncompleteInput { .. }) => {
-                // Not enough bytes for header
-                Ok(None)
-            }
-            Err(Error::BufferTooSmall { .. }) => {
-                 // Should not happen during decode, only encode
-                 Err(anyhow!("Unexpected BufferTooSmall error during frame decode"))
-            }
-            Err(e) => {
-                // Invalid data
-                Err(anyhow!("Frame header decode error: {}", e))
+    fn parse_frame(&mut self) -> Result<Option<(FrameHeader, BytesMut)>> {
+        let frame = self.decoder.next_frame()?;
+        #[cfg(feature = "zstd")]
+        if let Some((header, payload)) = frame {
+            if self.decompress && header.frame_type == FrameType::Compressed {
+                return self.inflate(header, payload).map(Some);
             }
+            return Ok(Some((header, payload)));
         }
+        Ok(frame)
+    }
+
+    #[cfg(feature = "zstd")]
+    fn inflate(&mut self, header: FrameHeader, payload: BytesMut) -> Result<(FrameHeader, BytesMut)> {
+        let (frame_type, data) = crate::compress::unwrap(&payload, self.decoder.max_frame_len())
+            .map_err(|e| anyhow!("Failed to decompress frame: {}", e))?;
+        self.decoder.release(payload);
+        let header = FrameHeader { frame_type, length: data.len() as u64, ..header };
+        self.decoder.check_utf8(&header, &data)?;
+        Ok((header, BytesMut::from(&data[..])))
     }
 
     /// Get the current buffer capacity (useful for debugging/monitoring).
     pub fn buffer_capacity(&self) -> usize {
-        self.buffer.capacity()
+        self.decoder.capacity()
     }
 
     /// Get the current buffer length (bytes waiting to be parsed).
     pub fn buffer_len(&self) -> usize {
-        self.buffer.len()
+        self.decoder.buffered()
+    }
+
+    /// Consumes the framer and returns the bytes not yet parsed, e.g. the
+    /// partial frame left behind by [`Error::TruncatedFrame`](crate::Error::TruncatedFrame).
+    pub fn into_remaining(self) -> BytesMut {
+        self.decoder.into_remaining()
+    }
+
+    /// Number of corrupt regions skipped in resync mode (each may span several bytes).
+    pub fn corrupt_frames(&self) -> u64 {
+        self.decoder.corrupt_frames()
+    }
+
+    /// Total bytes discarded in resync mode.
+    pub fn skipped_bytes(&self) -> u64 {
+        self.decoder.skipped_bytes()
+    }
+
+    /// See [`FrameDecoder::oversized_frames`].
+    pub fn oversized_frames(&self) -> u64 {
+        self.decoder.oversized_frames()
+    }
+
+    /// Number of idle payload buffers waiting to be reused.
+    pub fn pooled_buffers(&self) -> usize {
+        self.decoder.pooled_buffers()
+    }
+
+    /// BLAKE3 digest of the stream's bytes up to the end of the last frame
+    /// read; see [`FrameDecoder::stream_digest`].
+    ///
+    /// Compare against the sender's digest once the stream is finished.
+    #[cfg(feature = "stream-digest")]
+    pub fn stream_digest(&self) -> [u8; 32] {
+        self.decoder.stream_digest()
     }
 }
 
@@ -136,6 +235,8 @@ impl Default for Framer {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::error::Error;
+    use crate::frame::FrameType;
 
 
 
@@ -150,4 +251,96 @@ mod tests {
         let framer_custom = Framer::with_capacity(8192);
         assert_eq!(framer_custom.buffer_capacity(), 8192);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_single_buffer_frames_parse() {
+        let header = crate::frame::FrameHeader {
+            flags: crate::frame::FrameFlags::PRIORITY,
+            frame_type: crate::frame::FrameType::Utf8Text,
+            channel_id: 2,
+            stream_id: 16384,
+            length: 0,
+        };
+        let mut out = Vec::new();
+        header.encode_with_payload(b"one", &mut out).unwrap();
+        header.encode_with_payload(b"two!", &mut out).unwrap();
+
+        let mut framer = Framer::new();
+        framer.decoder.push(&out);
+        let (h1, p1) = framer.parse_frame().unwrap().unwrap();
+        let (h2, p2) = framer.parse_frame().unwrap().unwrap();
+        assert_eq!((h1.length, &p1[..]), (3, &b"one"[..]));
+        assert_eq!((h2.length, &p2[..]), (4, &b"two!"[..]));
+        assert_eq!(h2.stream_id, 16384);
+        assert!(framer.parse_frame().unwrap().is_none());
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn test_decompress_restores_original_frame() {
+        let original = crate::frame::FrameHeader {
+            flags: crate::frame::FrameFlags::empty(),
+            frame_type: FrameType::Utf8Text,
+            channel_id: 3,
+            stream_id: 0,
+            length: 0,
+        };
+        let text = b"state delta state delta state delta state delta";
+        let (header, body) = crate::compress::wrap(original, text, crate::compress::DEFAULT_LEVEL).unwrap();
+        let mut head = [0u8; 32];
+        let n = header.encode(&mut head).unwrap();
+
+        let mut framer = Framer::new().with_decompress(true);
+        framer.decoder.push(&head[..n]);
+        framer.decoder.push(&body);
+        let (decoded, payload) = framer.parse_frame().unwrap().unwrap();
+        assert_eq!(decoded.frame_type, FrameType::Utf8Text);
+        assert_eq!(decoded.channel_id, 3);
+        assert_eq!(decoded.length as usize, text.len());
+        assert_eq!(&payload[..], &text[..]);
+    }
+
+    #[test]
+    fn test_oversized_frame_rejected_before_buffering() {
+        let header = crate::frame::FrameHeader {
+            flags: crate::frame::FrameFlags::empty(),
+            frame_type: FrameType::RawBinary,
+            channel_id: 1,
+            stream_id: 0,
+            length: u64::MAX >> 2,
+        };
+        let mut head = [0u8; crate::frame::MAX_HEADER_LEN];
+        let n = header.encode(&mut head).unwrap();
+
+        let mut framer = Framer::new().with_max_frame_len(1024);
+        framer.decoder.push(&head[..n]);
+        let err = framer.parse_frame().unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<Error>(),
+            Some(&Error::FrameTooLarge { len: u64::MAX >> 2, max: 1024 })
+        );
+    }
+
+    #[test]
+    fn test_truncated_frame_keeps_remaining_bytes() {
+        let header = crate::frame::FrameHeader {
+            flags: crate::frame::FrameFlags::empty(),
+            frame_type: FrameType::RawBinary,
+            channel_id: 1,
+            stream_id: 0,
+            length: 0,
+        };
+        let mut out = Vec::new();
+        header.encode_with_payload(b"whole", &mut out).unwrap();
+        let whole = out.len();
+        header.encode_with_payload(b"cut short", &mut out).unwrap();
+
+        let mut framer = Framer::new();
+        framer.decoder.push(&out[..out.len() - 3]);
+        assert!(framer.parse_frame().unwrap().is_some());
+        assert!(framer.parse_frame().unwrap().is_none());
+        let buffered = out.len() - 3 - whole;
+        assert_eq!(framer.decoder.finish(), Err(Error::TruncatedFrame { buffered }));
+        assert_eq!(&framer.into_remaining()[..], &out[whole..out.len() - 3]);
+    }
+}
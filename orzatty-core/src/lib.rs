@@ -1,4 +1,4 @@
-#![cfg_attr(not(feature = "std"), no_std)]
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
 
 //! # Orzatty Core
 //! 
@@ -13,12 +13,39 @@ pub mod frame;
 pub mod protocol;
 pub mod error;
 pub mod auth;
+pub mod control;
+pub mod decoder;
+pub mod assembler;
+pub mod checksum;
+pub mod sequence;
+mod pool;
 
 #[cfg(feature = "quinn")]
 pub mod framer;
 
-pub use frame::{FrameHeader, FrameType, FrameFlags};
+#[cfg(feature = "stream-digest")]
+pub mod digest;
+
+#[cfg(feature = "zstd")]
+pub mod compress;
+
+/// Version of the wire format and handshake, sent in `AuthMessage::Hello`.
+///
+/// Bumped on every incompatible change; peers only talk to the same version.
+/// The header layout of each version is described in [`frame`].
+pub const PROTOCOL_VERSION: u16 = 2;
+
+/// ALPN protocol id clients offer and servers accept unless configured
+/// otherwise, so Orzatty can share a UDP port with other QUIC protocols
+/// (e.g. HTTP/3 on 443).
+pub const DEFAULT_ALPN: &[u8] = b"orzatty/1";
+
+pub use frame::{FrameBuilder, FrameHeader, FrameType, FrameFlags, FrameWriter};
+pub use decoder::FrameDecoder;
 pub use error::Error;
 
 #[cfg(feature = "quinn")]
 pub use framer::Framer;
+
+#[cfg(feature = "stream-digest")]
+pub use digest::StreamDigest;
@@ -0,0 +1,98 @@
+//! Size-classed freelist of payload buffers.
+//!
+//! Small frames dominate most workloads. Handing each one out as a slice of
+//! the decoder's read buffer keeps that allocation shared, which forces a fresh
+//! allocation the next time the read buffer grows. For a caller that recycles
+//! its payloads, copying small ones into recycled buffers instead keeps the
+//! read buffer uniquely owned, so it can be reset in place once drained.
+//! `cargo bench -p orzatty-core --bench allocations` counts the difference.
+
+extern crate alloc;
+use alloc::vec::Vec;
+
+use bytes::BytesMut;
+
+/// Buffer capacities handed out by the pool. Larger payloads bypass it.
+pub(crate) const SIZE_CLASSES: [usize; 4] = [64, 256, 1024, 4096];
+
+/// Upper bound on idle buffers kept per class.
+const MAX_PER_CLASS: usize = 32;
+
+pub(crate) struct BufferPool {
+    free: [Vec<BytesMut>; SIZE_CLASSES.len()],
+}
+
+impl BufferPool {
+    pub(crate) fn new() -> Self {
+        Self { free: Default::default() }
+    }
+
+    /// Smallest class able to hold `len` bytes.
+    fn class_for(len: usize) -> Option<usize> {
+        SIZE_CLASSES.iter().position(|&size| len <= size)
+    }
+
+    /// Returns an empty buffer with room for `len` bytes, or `None` if `len`
+    /// exceeds the largest size class.
+    pub(crate) fn take(&mut self, len: usize) -> Option<BytesMut> {
+        let class = Self::class_for(len)?;
+        Some(self.free[class].pop()
+            .unwrap_or_else(|| BytesMut::with_capacity(SIZE_CLASSES[class])))
+    }
+
+    /// Returns a buffer to the freelist. Only buffers of exactly a class's
+    /// capacity are kept, as the pool hands them out: anything else is most
+    /// likely a slice of a read buffer, and keeping it would pin the whole
+    /// allocation.
+    pub(crate) fn put(&mut self, mut buf: BytesMut) {
+        let cap = buf.capacity();
+        let Some(class) = SIZE_CLASSES.iter().position(|&size| cap == size) else {
+            return;
+        };
+        if self.free[class].len() >= MAX_PER_CLASS {
+            return;
+        }
+        buf.clear();
+        self.free[class].push(buf);
+    }
+
+    /// Number of idle buffers across all classes.
+    pub(crate) fn idle(&self) -> usize {
+        self.free.iter().map(Vec::len).sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_take_picks_smallest_class() {
+        let mut pool = BufferPool::new();
+        assert_eq!(pool.take(0).unwrap().capacity(), 64);
+        assert_eq!(pool.take(65).unwrap().capacity(), 256);
+        assert_eq!(pool.take(4096).unwrap().capacity(), 4096);
+        assert!(pool.take(4097).is_none());
+    }
+
+    #[test]
+    fn test_put_recycles_and_clears() {
+        let mut pool = BufferPool::new();
+        let mut buf = pool.take(10).unwrap();
+        buf.extend_from_slice(b"payload");
+        let ptr = buf.as_ptr();
+        pool.put(buf);
+        assert_eq!(pool.idle(), 1);
+
+        let reused = pool.take(10).unwrap();
+        assert!(reused.is_empty());
+        assert_eq!(reused.as_ptr(), ptr);
+        assert_eq!(pool.idle(), 0);
+
+        // Buffers the pool would not have handed out are not kept
+        pool.put(BytesMut::with_capacity(8));
+        pool.put(BytesMut::with_capacity(100));
+        pool.put(BytesMut::with_capacity(1 << 20));
+        assert_eq!(pool.idle(), 0);
+    }
+}
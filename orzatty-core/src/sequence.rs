@@ -0,0 +1,128 @@
+//! Per-channel sequence numbers, for spotting lost and reordered frames.
+//!
+//! The first header byte has no free flag bit, so a sequenced frame carries
+//! its number in the `stream_id` field, offset by [`SEQUENCE_BASE`]: `0` for
+//! the first frame, then one more per frame. Ids from `SEQUENCE_BASE` up are
+//! reserved for this, which marks a frame as numbered; any other `stream_id`
+//! on a sequenced channel (a stream index, an application id, a request
+//! correlation id) is left alone. Both peers agree out of band on which
+//! channels are sequenced, just as they agree on what each channel carries.
+//! Worth doing once a channel's frames can take different paths (several
+//! streams, datagrams); frames on a single stream already arrive in order.
+
+extern crate alloc;
+use alloc::collections::BTreeMap;
+
+/// Smallest `stream_id` that carries a sequence number: the top quarter of
+/// the varint range. Numbered ids all encode to 8 bytes.
+pub const SEQUENCE_BASE: u64 = 1 << 61;
+
+/// The `stream_id` carrying `sequence`. The reserved range holds 2^61
+/// numbers; past that the id no longer fits a varint.
+pub fn to_stream_id(sequence: u64) -> u64 {
+    SEQUENCE_BASE.saturating_add(sequence)
+}
+
+/// The sequence number a `stream_id` carries, or `None` if it is not a
+/// numbered one.
+pub fn from_stream_id(stream_id: u64) -> Option<u64> {
+    stream_id.checked_sub(SEQUENCE_BASE)
+}
+
+/// How a frame's sequence number relates to what came before on its channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SequenceCheck {
+    /// The number expected next.
+    InOrder,
+    /// Ahead of the expected number: `received - expected` frames are missing
+    /// or still on their way.
+    Gap { expected: u64, received: u64 },
+    /// Below the expected number: a frame overtaken by later ones, or a duplicate.
+    Late { expected: u64, received: u64 },
+}
+
+/// Tracks the next expected sequence number of each registered channel.
+#[derive(Debug, Clone, Default)]
+pub struct SequenceTracker {
+    expected: BTreeMap<u32, u64>,
+}
+
+impl SequenceTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts checking `channel_id`, expecting `0` next.
+    pub fn track(&mut self, channel_id: u32) {
+        self.expected.insert(channel_id, 0);
+    }
+
+    /// Stops checking `channel_id`.
+    pub fn untrack(&mut self, channel_id: u32) {
+        self.expected.remove(&channel_id);
+    }
+
+    pub fn is_tracked(&self, channel_id: u32) -> bool {
+        self.expected.contains_key(&channel_id)
+    }
+
+    /// Records the number carried by a frame's `stream_id`, if it carries
+    /// one (see [`from_stream_id`]). Returns `None` for unnumbered frames and
+    /// for channels that are not tracked.
+    pub fn observe_stream_id(&mut self, channel_id: u32, stream_id: u64) -> Option<SequenceCheck> {
+        self.observe(channel_id, from_stream_id(stream_id)?)
+    }
+
+    /// Records `sequence` as received on `channel_id`. Returns `None` for
+    /// channels that are not tracked.
+    ///
+    /// After a gap the numbers in between count as late when they arrive, so
+    /// each missing frame is reported at most twice: once in the gap, once
+    /// when (if) it shows up.
+    pub fn observe(&mut self, channel_id: u32, sequence: u64) -> Option<SequenceCheck> {
+        let expected = self.expected.get_mut(&channel_id)?;
+        let check = match sequence.cmp(expected) {
+            core::cmp::Ordering::Equal => SequenceCheck::InOrder,
+            core::cmp::Ordering::Greater => SequenceCheck::Gap { expected: *expected, received: sequence },
+            core::cmp::Ordering::Less => return Some(SequenceCheck::Late { expected: *expected, received: sequence }),
+        };
+        *expected = sequence.saturating_add(1);
+        Some(check)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::MAX_VARINT;
+
+    #[test]
+    fn test_gaps_and_late_frames() {
+        let mut tracker = SequenceTracker::new();
+        assert_eq!(tracker.observe(1, 0), None);
+
+        tracker.track(1);
+        assert_eq!(tracker.observe(1, 0), Some(SequenceCheck::InOrder));
+        assert_eq!(tracker.observe(1, 1), Some(SequenceCheck::InOrder));
+        assert_eq!(tracker.observe(1, 4), Some(SequenceCheck::Gap { expected: 2, received: 4 }));
+        assert_eq!(tracker.observe(1, 2), Some(SequenceCheck::Late { expected: 5, received: 2 }));
+        assert_eq!(tracker.observe(1, 4), Some(SequenceCheck::Late { expected: 5, received: 4 }));
+        assert_eq!(tracker.observe(1, 5), Some(SequenceCheck::InOrder));
+
+        // Unnumbered ids are not checked
+        assert_eq!(tracker.observe_stream_id(1, 77), None);
+        assert_eq!(tracker.observe_stream_id(1, to_stream_id(6)), Some(SequenceCheck::InOrder));
+
+        tracker.untrack(1);
+        assert!(!tracker.is_tracked(1));
+        assert_eq!(tracker.observe(1, 9), None);
+    }
+
+    #[test]
+    fn test_stream_id_round_trip() {
+        assert_eq!(to_stream_id(0), SEQUENCE_BASE);
+        assert_eq!(from_stream_id(to_stream_id(42)), Some(42));
+        assert_eq!(from_stream_id(SEQUENCE_BASE - 1), None);
+        assert_eq!(to_stream_id(MAX_VARINT - SEQUENCE_BASE), MAX_VARINT);
+    }
+}
@@ -0,0 +1,156 @@
+//! Pluggable token authentication.
+//!
+//! Authenticators are tried in order. Each one either decides (`Accept` /
+//! `Reject`) or passes the token on to the next, so different credential
+//! types (service tokens, user tokens, anonymous spectators) can be composed.
+
+use std::collections::HashSet;
+use std::sync::Arc;
+
+/// Outcome of a single authenticator.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AuthDecision {
+    /// Token accepted; `identity` names the authenticated session.
+    Accept { identity: String },
+    /// Token recognised but refused. Stops the chain.
+    Reject { reason: String },
+    /// Not this authenticator's kind of token; try the next one.
+    Pass,
+}
+
+/// A credential backend.
+pub trait Authenticator: Send + Sync {
+    fn authenticate(&self, token: &str) -> AuthDecision;
+}
+
+/// Any `Fn(&str) -> AuthDecision` closure works as an authenticator.
+impl<F> Authenticator for F
+where
+    F: Fn(&str) -> AuthDecision + Send + Sync,
+{
+    fn authenticate(&self, token: &str) -> AuthDecision {
+        self(token)
+    }
+}
+
+/// Accepts any token from a fixed set, passes on everything else.
+pub struct StaticTokens {
+    tokens: HashSet<String>,
+}
+
+impl StaticTokens {
+    pub fn new<I, S>(tokens: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self { tokens: tokens.into_iter().map(Into::into).collect() }
+    }
+}
+
+impl Authenticator for StaticTokens {
+    fn authenticate(&self, token: &str) -> AuthDecision {
+        if self.tokens.contains(token) {
+            AuthDecision::Accept { identity: format!("token:{}", token) }
+        } else {
+            AuthDecision::Pass
+        }
+    }
+}
+
+/// Rejects tokens from a fixed set (revoked or banned), passes on everything else.
+pub struct Denylist {
+    tokens: HashSet<String>,
+}
+
+impl Denylist {
+    pub fn new<I, S>(tokens: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self { tokens: tokens.into_iter().map(Into::into).collect() }
+    }
+}
+
+impl Authenticator for Denylist {
+    fn authenticate(&self, token: &str) -> AuthDecision {
+        if self.tokens.contains(token) {
+            AuthDecision::Reject { reason: "token revoked".into() }
+        } else {
+            AuthDecision::Pass
+        }
+    }
+}
+
+/// Accepts every token as an anonymous session. Put it last in a chain.
+pub struct Anonymous;
+
+impl Authenticator for Anonymous {
+    fn authenticate(&self, _token: &str) -> AuthDecision {
+        AuthDecision::Accept { identity: "anonymous".into() }
+    }
+}
+
+/// Ordered list of authenticators with fallthrough on `Pass`.
+#[derive(Clone, Default)]
+pub struct AuthChain {
+    authenticators: Vec<Arc<dyn Authenticator>>,
+}
+
+impl AuthChain {
+    pub fn new(authenticators: Vec<Arc<dyn Authenticator>>) -> Self {
+        Self { authenticators }
+    }
+
+    /// Runs the chain. The first `Accept` yields the session identity, the
+    /// first `Reject` fails with its reason, and if every authenticator
+    /// passes the token is refused.
+    pub fn authenticate(&self, token: &str) -> Result<String, String> {
+        for auth in &self.authenticators {
+            match auth.authenticate(token) {
+                AuthDecision::Accept { identity } => return Ok(identity),
+                AuthDecision::Reject { reason } => return Err(reason),
+                AuthDecision::Pass => continue,
+            }
+        }
+        Err("no authenticator accepted the token".into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_first_decision_wins() {
+        let chain = AuthChain::new(vec![
+            Arc::new(StaticTokens::new(["svc"])),
+            Arc::new(Anonymous),
+        ]);
+        assert_eq!(chain.authenticate("svc"), Ok("token:svc".into()));
+        assert_eq!(chain.authenticate("other"), Ok("anonymous".into()));
+
+        let chain = AuthChain::new(vec![Arc::new(Denylist::new(["svc"])), Arc::new(Anonymous)]);
+        assert_eq!(chain.authenticate("svc"), Err("token revoked".into()));
+    }
+
+    #[test]
+    fn test_all_pass_fails() {
+        let chain = AuthChain::new(vec![Arc::new(StaticTokens::new(["svc"]))]);
+        assert!(chain.authenticate("other").is_err());
+        assert!(AuthChain::default().authenticate("svc").is_err());
+    }
+
+    #[test]
+    fn test_closure_authenticator() {
+        let chain = AuthChain::new(vec![
+            Arc::new(|token: &str| match token.strip_prefix("user-") {
+                Some(name) => AuthDecision::Accept { identity: name.into() },
+                None => AuthDecision::Reject { reason: "unknown token".into() },
+            }),
+        ]);
+        assert_eq!(chain.authenticate("user-ana"), Ok("ana".into()));
+        assert_eq!(chain.authenticate("nope"), Err("unknown token".into()));
+    }
+}
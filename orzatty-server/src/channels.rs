@@ -0,0 +1,58 @@
+//! Per-connection allocation of server-assigned channel ids.
+
+use orzatty_core::control::DYNAMIC_CHANNEL_BASE;
+
+/// Hands out channel ids from [`DYNAMIC_CHANNEL_BASE`] upwards, reusing
+/// released ids first.
+pub struct ChannelAllocator {
+    next: u32,
+    free: Vec<u32>,
+}
+
+impl ChannelAllocator {
+    pub fn new() -> Self {
+        Self { next: DYNAMIC_CHANNEL_BASE, free: Vec::new() }
+    }
+
+    /// Returns `None` once the id space is exhausted.
+    pub fn allocate(&mut self) -> Option<u32> {
+        if let Some(id) = self.free.pop() {
+            return Some(id);
+        }
+        let id = self.next;
+        self.next = self.next.checked_add(1)?;
+        Some(id)
+    }
+
+    /// How many ids are handed out and not yet released.
+    pub fn open(&self) -> usize {
+        (self.next - DYNAMIC_CHANNEL_BASE) as usize - self.free.len()
+    }
+
+    /// Returns an id to the pool. Ids that were never handed out are ignored.
+    pub fn release(&mut self, channel_id: u32) {
+        if (DYNAMIC_CHANNEL_BASE..self.next).contains(&channel_id) && !self.free.contains(&channel_id) {
+            self.free.push(channel_id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_released_ids_are_recycled() {
+        let mut alloc = ChannelAllocator::new();
+        let a = alloc.allocate().unwrap();
+        let b = alloc.allocate().unwrap();
+        assert_eq!((a, b), (DYNAMIC_CHANNEL_BASE, DYNAMIC_CHANNEL_BASE + 1));
+
+        alloc.release(a);
+        alloc.release(a);
+        alloc.release(7); // not ours
+        assert_eq!(alloc.open(), 1);
+        assert_eq!(alloc.allocate(), Some(a));
+        assert_eq!(alloc.allocate(), Some(DYNAMIC_CHANNEL_BASE + 2));
+    }
+}
@@ -0,0 +1,30 @@
+//! Self-signed certificates for local development and tests (`dev-cert`
+//! feature).
+//!
+//! ⚠️ DEV ONLY: the key is generated in-process and never persisted, and the
+//! certificate is not issued by any CA. Clients must trust it explicitly (or
+//! run with verification disabled), which is never acceptable in production.
+
+use anyhow::Result;
+
+/// Certificate chain as handed to `quinn::ServerConfig::with_single_cert`.
+pub type CertChain = Vec<rustls::Certificate>;
+
+/// Generates a self-signed certificate valid for `sans` for `valid_days` days.
+///
+/// Each SAN is added as an IP address if it parses as one, otherwise as a DNS
+/// name, so the same call covers `localhost`, LAN hostnames and raw IPs.
+pub fn generate_dev_cert(sans: Vec<String>, valid_days: u32) -> Result<(CertChain, rustls::PrivateKey)> {
+    let mut params = rcgen::CertificateParams::new(sans);
+
+    // Backdate slightly to tolerate clock skew between client and server
+    let now = time::OffsetDateTime::now_utc();
+    params.not_before = now - time::Duration::hours(1);
+    params.not_after = now + time::Duration::days(valid_days.into());
+
+    let cert = rcgen::Certificate::from_params(params)?;
+    let chain = vec![rustls::Certificate(cert.serialize_der()?)];
+    let key = rustls::PrivateKey(cert.serialize_private_key_der());
+
+    Ok((chain, key))
+}
@@ -0,0 +1,212 @@
+//! Registry of connected clients, for pushing frames to them and for an
+//! operator's view of who is connected.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use orzatty_core::frame::FrameBuilder;
+use quinn::{Connection, VarInt};
+use tokio::sync::mpsc;
+
+use crate::channels::ChannelAllocator;
+use crate::DISCONNECTED_CODE;
+
+/// Identifies a client within one [`Hub`]. Never reused.
+pub type ConnectionId = u64;
+
+/// Frames a client may have waiting to be written before pushes to it are
+/// dropped.
+pub const PUSH_QUEUE_CAPACITY: usize = 256;
+
+/// The clients currently served, each with a queue onto its session stream.
+///
+/// Cheap to clone; all clones share the registry. Clients are added when
+/// [`ServerConnection::run`](crate::ServerConnection::run) starts and removed
+/// when it returns. A client whose connection died in between is pruned the
+/// next time a push finds its queue closed.
+#[derive(Clone, Default)]
+pub struct Hub {
+    clients: Arc<Mutex<HashMap<ConnectionId, Client>>>,
+    next_id: Arc<AtomicU64>,
+}
+
+struct Client {
+    // Queue onto the client's session stream
+    tx: mpsc::Sender<Vec<u8>>,
+    // Unset only for clients registered by tests, without a connection
+    peer: Option<Peer>,
+}
+
+/// The connection behind a registered client, for [`Hub::connections`]
+/// and [`Hub::disconnect`].
+pub(crate) struct Peer {
+    pub(crate) connection: Connection,
+    pub(crate) identity: String,
+    pub(crate) connected_at: SystemTime,
+    pub(crate) channels: Arc<Mutex<ChannelAllocator>>,
+}
+
+/// One client as seen by [`Hub::connections`], at the time of the call.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ConnectionInfo {
+    pub id: ConnectionId,
+    /// Identity returned by the authenticator that accepted the client.
+    pub identity: String,
+    pub remote_address: SocketAddr,
+    /// When the client authenticated.
+    pub connected_at: SystemTime,
+    /// UDP payload bytes received from the client, including QUIC overhead.
+    pub bytes_received: u64,
+    /// UDP payload bytes sent to the client, including QUIC overhead and
+    /// retransmissions.
+    pub bytes_sent: u64,
+    /// Channels the server has assigned to the client and not had back.
+    pub channels: usize,
+}
+
+impl Hub {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// How many clients are registered.
+    pub fn len(&self) -> usize {
+        self.clients.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Every registered client, in no particular order. The server-side
+    /// counterpart of the client's `stats`, for dashboards.
+    pub fn connections(&self) -> Vec<ConnectionInfo> {
+        self.clients.lock().unwrap().iter()
+            .filter_map(|(&id, client)| {
+                let peer = client.peer.as_ref()?;
+                let stats = peer.connection.stats();
+                Some(ConnectionInfo {
+                    id,
+                    identity: peer.identity.clone(),
+                    remote_address: peer.connection.remote_address(),
+                    connected_at: peer.connected_at,
+                    bytes_received: stats.udp_rx.bytes,
+                    bytes_sent: stats.udp_tx.bytes,
+                    channels: peer.channels.lock().unwrap().open(),
+                })
+            })
+            .collect()
+    }
+
+    /// Closes client `id`'s connection with [`DISCONNECTED_CODE`] and
+    /// `reason`, e.g. to kick an abusive client. Returns whether it was
+    /// registered; it leaves the hub once its
+    /// [`run`](crate::ServerConnection::run) returns.
+    pub fn disconnect(&self, id: ConnectionId, reason: &str) -> bool {
+        let clients = self.clients.lock().unwrap();
+        let Some(peer) = clients.get(&id).and_then(|client| client.peer.as_ref()) else { return false };
+        peer.connection.close(VarInt::from_u32(DISCONNECTED_CODE), reason.as_bytes());
+        true
+    }
+
+    /// Sends `data` on `channel_id` to every registered client and returns
+    /// how many it was queued for.
+    ///
+    /// Never waits: a client whose queue is full (it is not keeping up)
+    /// misses the frame, so one slow client cannot stall the rest.
+    pub fn broadcast(&self, channel_id: u32, data: &[u8]) -> usize {
+        self.push(channel_id, data, |_| true)
+    }
+
+    /// Like [`broadcast`](Self::broadcast), skipping client `except`, e.g.
+    /// the one that sent the message.
+    pub fn broadcast_except(&self, except: ConnectionId, channel_id: u32, data: &[u8]) -> usize {
+        self.push(channel_id, data, |id| id != except)
+    }
+
+    /// Sends `data` on `channel_id` to client `id` alone. Returns whether it
+    /// was queued.
+    pub fn send_to(&self, id: ConnectionId, channel_id: u32, data: &[u8]) -> bool {
+        self.push(channel_id, data, |to| to == id) == 1
+    }
+
+    fn push(&self, channel_id: u32, data: &[u8], to: impl Fn(ConnectionId) -> bool) -> usize {
+        let Ok(frame) = FrameBuilder::new(data).channel(channel_id).to_bytes() else { return 0 };
+        let mut queued = 0;
+        self.clients.lock().unwrap().retain(|&id, client| {
+            if !to(id) {
+                return true;
+            }
+            match client.tx.try_send(frame.clone()) {
+                Ok(()) => {
+                    queued += 1;
+                    true
+                }
+                Err(mpsc::error::TrySendError::Full(_)) => true,
+                // Its writer is gone: the connection died
+                Err(mpsc::error::TrySendError::Closed(_)) => false,
+            }
+        });
+        queued
+    }
+
+    /// Adds a client; it stays registered until the guard is dropped.
+    pub(crate) fn register(&self, tx: mpsc::Sender<Vec<u8>>, peer: Option<Peer>) -> Registration {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.clients.lock().unwrap().insert(id, Client { tx, peer });
+        Registration { hub: self.clone(), id }
+    }
+}
+
+/// Removes its client from the [`Hub`] when dropped.
+pub(crate) struct Registration {
+    hub: Hub,
+    id: ConnectionId,
+}
+
+impl Registration {
+    pub fn id(&self) -> ConnectionId {
+        self.id
+    }
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        self.hub.clients.lock().unwrap().remove(&self.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use orzatty_core::frame::FrameHeader;
+
+    #[test]
+    fn test_broadcast_prunes_dead_clients() {
+        let hub = Hub::new();
+        let (alive_tx, mut alive) = mpsc::channel(1);
+        let (dead_tx, dead) = mpsc::channel(1);
+        let sender = hub.register(alive_tx, None);
+        let _dead = hub.register(dead_tx, None);
+        drop(dead);
+
+        assert_eq!(hub.broadcast(3, b"hi"), 1);
+        assert_eq!(hub.len(), 1);
+        let frame = alive.try_recv().unwrap();
+        let (header, n) = FrameHeader::decode(&frame).unwrap();
+        assert_eq!((header.channel_id, &frame[n..]), (3, &b"hi"[..]));
+
+        // A full queue drops the frame but keeps the client
+        assert!(hub.send_to(sender.id(), 3, b"one"));
+        assert!(!hub.send_to(sender.id(), 3, b"two"));
+        assert_eq!(hub.broadcast_except(sender.id(), 3, b"three"), 0);
+        assert_eq!(hub.len(), 1);
+
+        drop(sender);
+        assert!(hub.is_empty());
+    }
+}
@@ -0,0 +1,1008 @@
+//! # Orzatty Server
+//!
+//! Server-side counterpart of `orzatty-client`: binds a QUIC endpoint, runs
+//! the auth handshake and serves authenticated connections, dispatching
+//! application frames to per-channel handlers. Control frames (channel
+//! allocation, ping, close) are answered by the library.
+//!
+//! ```ignore
+//! let server = OrzattyServer::bind(addr, cert_chain, key)?
+//!     .with_auth_fn(|token: &str| if token == "letmein" {
+//!         AuthDecision::Accept { identity: "admin".into() }
+//!     } else {
+//!         AuthDecision::Reject { reason: "bad token".into() }
+//!     });
+//! server.serve(|mut conn| async move {
+//!     conn.on(1, |_header, payload| Some(payload)); // echo channel 1
+//!     conn.broadcast_channel(2); // fan channel 2 out to every client
+//!     conn.run().await
+//! }).await?;
+//! ```
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use anyhow::{anyhow, Result};
+use bytes::Bytes;
+use orzatty_core::auth::{verify_proof, AuthMessage, Capabilities, SessionInfo};
+use orzatty_core::control::ControlMessage;
+use orzatty_core::frame::{FrameFlags, FrameHeader, FrameType};
+use orzatty_core::{Framer, DEFAULT_ALPN, PROTOCOL_VERSION};
+use quinn::{Connection, Endpoint};
+use tokio::sync::mpsc;
+
+pub mod auth;
+mod channels;
+#[cfg(any(test, feature = "dev-cert"))]
+pub mod dev_cert;
+pub mod hub;
+mod qpack;
+pub mod rate;
+pub mod webtransport;
+
+pub use auth::{AuthChain, AuthDecision, Authenticator};
+use channels::ChannelAllocator;
+pub use hub::{ConnectionId, ConnectionInfo, Hub};
+use hub::{Peer, Registration, PUSH_QUEUE_CAPACITY};
+use rate::Limiter;
+pub use rate::{RateLimit, RateLimitAction};
+use webtransport::Transport;
+pub use webtransport::WEBTRANSPORT_ALPN;
+
+/// Application close code used when a client fails authentication.
+pub const AUTH_FAILED_CODE: u32 = 1;
+
+/// Close code, in both the `ControlMessage::Close` and the QUIC close, used
+/// when a client exceeds its [`RateLimit`] under [`RateLimitAction::Close`].
+pub const RATE_LIMITED_CODE: u32 = 2;
+
+const RATE_LIMITED_REASON: &str = "rate limit exceeded";
+
+/// Application close code used when the server drops a client with
+/// [`Hub::disconnect`].
+pub const DISCONNECTED_CODE: u32 = 3;
+
+/// How long the server waits for each auth handshake message before
+/// dropping the connection.
+pub const AUTH_FRAME_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Features the server offers unless narrowed with
+/// [`OrzattyServer::with_capabilities`].
+pub const DEFAULT_CAPABILITIES: Capabilities = Capabilities::DATAGRAMS
+    .union(Capabilities::FRAGMENTATION)
+    .union(Capabilities::CHECKSUM);
+
+/// Handles a frame; a returned payload is sent back with the same header
+/// (channel, `stream_id`, type and flags), on the stream or as the datagram
+/// the frame came in on. Payloads refer to the buffer they were read into,
+/// so echoing one back copies nothing until it is written.
+type Handler = Box<dyn Fn(FrameHeader, Bytes) -> Option<Bytes> + Send + Sync>;
+type CloseHandler = Box<dyn Fn(u16, String) + Send + Sync>;
+/// Gets the digest a client sent in its `Close` and the one the server read.
+type DigestMismatchHandler = Box<dyn Fn([u8; 32], [u8; 32]) + Send + Sync>;
+/// Builds the server's session metadata from a client's identity and the
+/// metadata it sent.
+type SessionMetadata = Arc<dyn Fn(&str, &[u8]) -> Vec<u8> + Send + Sync>;
+
+/// A QUIC endpoint that authenticates Orzatty clients.
+pub struct OrzattyServer {
+    endpoint: Endpoint,
+    // Kept to rebuild the endpoint's config when the ALPN list changes
+    tls: rustls::ServerConfig,
+    authenticators: Vec<Arc<dyn Authenticator>>,
+    auth_secret: Option<Arc<[u8]>>,
+    capabilities: Capabilities,
+    hub: Hub,
+    rate_limit: Option<RateLimit>,
+    session_metadata: Option<SessionMetadata>,
+}
+
+impl OrzattyServer {
+    /// Binds a server endpoint to `addr` with the given certificate chain and key.
+    ///
+    /// With no authenticators added, every client is refused.
+    /// Only clients offering [`DEFAULT_ALPN`] complete the handshake; see
+    /// [`with_alpn_protocols`](Self::with_alpn_protocols).
+    pub fn bind(addr: SocketAddr, cert_chain: Vec<rustls::Certificate>, key: rustls::PrivateKey) -> Result<Self> {
+        let tls = Self::tls_builder()?
+            .with_no_client_auth()
+            .with_single_cert(cert_chain, key)?;
+        Self::with_tls(addr, tls)
+    }
+
+    /// Like [`bind`](Self::bind), but also requires mutual TLS: clients must
+    /// present a certificate chain that verifies against `client_roots`, or
+    /// the QUIC handshake fails before auth runs.
+    ///
+    /// The token exchange still follows; the verified chain is available via
+    /// [`ServerConnection::peer_certificates`].
+    pub fn bind_with_client_auth(
+        addr: SocketAddr,
+        cert_chain: Vec<rustls::Certificate>,
+        key: rustls::PrivateKey,
+        client_roots: rustls::RootCertStore,
+    ) -> Result<Self> {
+        let tls = Self::tls_builder()?
+            .with_client_cert_verifier(rustls::server::AllowAnyAuthenticatedClient::new(client_roots).boxed())
+            .with_single_cert(cert_chain, key)?;
+        Self::with_tls(addr, tls)
+    }
+
+    // Same settings as quinn's `with_single_cert` (QUIC needs TLS 1.3)
+    fn tls_builder() -> Result<rustls::ConfigBuilder<rustls::ServerConfig, rustls::WantsVerifier>> {
+        Ok(rustls::ServerConfig::builder()
+            .with_safe_default_cipher_suites()
+            .with_safe_default_kx_groups()
+            .with_protocol_versions(&[&rustls::version::TLS13])?)
+    }
+
+    fn with_tls(addr: SocketAddr, mut tls: rustls::ServerConfig) -> Result<Self> {
+        tls.max_early_data_size = u32::MAX;
+        tls.alpn_protocols = vec![DEFAULT_ALPN.to_vec()];
+        let endpoint = Endpoint::server(quinn::ServerConfig::with_crypto(Arc::new(tls.clone())), addr)?;
+        Ok(Self {
+            endpoint,
+            tls,
+            authenticators: Vec::new(),
+            auth_secret: None,
+            capabilities: DEFAULT_CAPABILITIES,
+            hub: Hub::new(),
+            rate_limit: None,
+            session_metadata: None,
+        })
+    }
+
+    /// Sets the ALPN protocol ids the server accepts, most preferred first
+    /// (default [`DEFAULT_ALPN`]). QUIC makes ALPN strict: a client offering
+    /// none of them, or no ALPN at all, fails the handshake. An empty list
+    /// accepts only clients that offer no ALPN.
+    ///
+    /// Applies to connections accepted from then on.
+    pub fn with_alpn_protocols(mut self, protocols: Vec<Vec<u8>>) -> Self {
+        self.tls.alpn_protocols = protocols;
+        self.endpoint.set_server_config(Some(quinn::ServerConfig::with_crypto(Arc::new(self.tls.clone()))));
+        self
+    }
+
+    /// Also accepts browsers, over WebTransport: adds [`WEBTRANSPORT_ALPN`]
+    /// to the ALPN list, and clients negotiating it open a WebTransport
+    /// session before the usual auth exchange, which then runs inside it.
+    ///
+    /// Browsers only accept a certificate from a trusted CA, or one pinned
+    /// with `serverCertificateHashes` (ECDSA, valid for at most 14 days).
+    pub fn with_webtransport(self) -> Self {
+        let mut protocols = self.tls.alpn_protocols.clone();
+        if !protocols.iter().any(|p| p == WEBTRANSPORT_ALPN) {
+            protocols.push(WEBTRANSPORT_ALPN.to_vec());
+        }
+        self.with_alpn_protocols(protocols)
+    }
+
+    /// Appends an authenticator to the chain; they are tried in the order added.
+    pub fn with_authenticator(mut self, auth: impl Authenticator + 'static) -> Self {
+        self.authenticators.push(Arc::new(auth));
+        self
+    }
+
+    /// Appends a closure that inspects each token and accepts, rejects with a
+    /// reason, or passes it on to the next authenticator.
+    pub fn with_auth_fn<F>(self, f: F) -> Self
+    where
+        F: Fn(&str) -> AuthDecision + Send + Sync + 'static,
+    {
+        self.with_authenticator(f)
+    }
+
+    /// Requires clients to answer an HMAC challenge keyed with `secret`
+    /// before their token is checked.
+    pub fn with_auth_secret(mut self, secret: impl Into<Vec<u8>>) -> Self {
+        self.auth_secret = Some(secret.into().into());
+        self
+    }
+
+    /// Sets the features offered to clients (default [`DEFAULT_CAPABILITIES`]).
+    /// Each session gets those the client also supports.
+    pub fn with_capabilities(mut self, capabilities: Capabilities) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+
+    /// Caps the frames and bytes each client may send, so one flooding
+    /// client cannot starve the rest. Unlimited by default.
+    pub fn with_rate_limit(mut self, limit: RateLimit) -> Self {
+        self.rate_limit = Some(limit);
+        self
+    }
+
+    /// Sets how the session metadata sent to each client in `AuthMessage::Ok`
+    /// is built, from its identity and the metadata it sent in its `Hello`
+    /// (see [`ServerConnection::client_metadata`]). Empty by default.
+    pub fn with_session_metadata<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&str, &[u8]) -> Vec<u8> + Send + Sync + 'static,
+    {
+        self.session_metadata = Some(Arc::new(f));
+        self
+    }
+
+    pub fn endpoint(&self) -> &Endpoint {
+        &self.endpoint
+    }
+
+    /// The clients this server is serving, for pushing frames to them from
+    /// outside a connection's handlers (e.g. a game tick).
+    pub fn hub(&self) -> &Hub {
+        &self.hub
+    }
+
+    /// The clients connected right now; see [`Hub::connections`].
+    pub fn connections(&self) -> Vec<ConnectionInfo> {
+        self.hub.connections()
+    }
+
+    /// Closes client `id`'s connection; see [`Hub::disconnect`].
+    pub fn disconnect(&self, id: ConnectionId, reason: &str) -> bool {
+        self.hub.disconnect(id, reason)
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.endpoint.local_addr()?)
+    }
+
+    /// Waits for the next incoming connection. Returns `None` once the
+    /// endpoint is closed.
+    ///
+    /// Call [`PendingConnection::authenticate`] on it, typically in a spawned
+    /// task so a slow handshake does not hold up other clients.
+    pub async fn accept(&self) -> Option<PendingConnection> {
+        let connecting = self.endpoint.accept().await?;
+        Some(PendingConnection {
+            connecting,
+            auth: AuthChain::new(self.authenticators.clone()),
+            secret: self.auth_secret.clone(),
+            capabilities: self.capabilities,
+            hub: self.hub.clone(),
+            rate_limit: self.rate_limit,
+            session_metadata: self.session_metadata.clone(),
+        })
+    }
+
+    /// Accept loop: authenticates each client in its own task and hands it to
+    /// `on_connect`. Failed handshakes and handler errors are logged with the
+    /// `tracing` feature and otherwise dropped.
+    pub async fn serve<F, Fut>(&self, on_connect: F) -> Result<()>
+    where
+        F: Fn(ServerConnection) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        let on_connect = Arc::new(on_connect);
+        while let Some(pending) = self.accept().await {
+            let on_connect = on_connect.clone();
+            tokio::spawn(async move {
+                #[cfg(feature = "tracing")]
+                let remote = pending.remote_address();
+                let result = match pending.authenticate().await {
+                    Ok(conn) => on_connect(conn).await,
+                    Err(e) => Err(e),
+                };
+                #[cfg(feature = "tracing")]
+                if let Err(e) = result {
+                    tracing::warn!(%remote, error = format!("{:#}", e), "client error");
+                }
+                #[cfg(not(feature = "tracing"))]
+                let _ = result;
+            });
+        }
+        Ok(())
+    }
+}
+
+/// An incoming connection that has not authenticated yet.
+pub struct PendingConnection {
+    connecting: quinn::Connecting,
+    auth: AuthChain,
+    secret: Option<Arc<[u8]>>,
+    capabilities: Capabilities,
+    hub: Hub,
+    rate_limit: Option<RateLimit>,
+    session_metadata: Option<SessionMetadata>,
+}
+
+impl PendingConnection {
+    pub fn remote_address(&self) -> SocketAddr {
+        self.connecting.remote_address()
+    }
+
+    /// Completes the QUIC handshake and runs the auth exchange.
+    ///
+    /// A rejected client is sent `AuthMessage::Fail` and its connection is
+    /// closed with [`AUTH_FAILED_CODE`]; the error carries the reason.
+    #[cfg_attr(feature = "tracing", tracing::instrument(
+        name = "auth",
+        skip_all,
+        fields(remote = %self.connecting.remote_address(), connection_id = tracing::field::Empty),
+        err,
+    ))]
+    pub async fn authenticate(self) -> Result<ServerConnection> {
+        let connection = self.connecting.await?;
+        let transport = if alpn_protocol(&connection).as_deref() == Some(WEBTRANSPORT_ALPN) {
+            Transport::WebTransport(Arc::new(webtransport::accept(connection).await?))
+        } else {
+            Transport::Quic(connection)
+        };
+        let (mut send, mut recv) = transport.accept_bi().await?;
+        if !transport.claim(&mut recv).await {
+            return Err(anyhow!("Expected a WebTransport stream of the session"));
+        }
+        let mut framer = Framer::new();
+
+        let Some(AuthMessage::Hello { token, protocol_version, capabilities, metadata }) = read_auth(&mut framer, &mut recv).await? else {
+            return Err(anyhow!("Expected Auth Hello"));
+        };
+        let verdict = if protocol_version != PROTOCOL_VERSION {
+            Err(format!("unsupported protocol version {} (server speaks {})", protocol_version, PROTOCOL_VERSION))
+        } else {
+            let proven = match &self.secret {
+                Some(secret) => {
+                    let nonce: [u8; 32] = rand::random();
+                    write_auth(&mut send, &AuthMessage::Challenge { nonce }).await?;
+                    matches!(
+                        read_auth(&mut framer, &mut recv).await?,
+                        Some(AuthMessage::Proof { hmac }) if verify_proof(secret, &nonce, &hmac)
+                    )
+                }
+                None => true,
+            };
+            if proven {
+                self.auth.authenticate(&token)
+            } else {
+                Err("challenge failed".to_string())
+            }
+        };
+        let capabilities = self.capabilities.negotiate(Capabilities::from_bits_truncate(capabilities));
+
+        match verdict {
+            Ok(identity) => {
+                // Pushes queue up until the session stream is accepted in `run`
+                let (session_tx, session_rx) = mpsc::channel(PUSH_QUEUE_CAPACITY);
+                let channels = Arc::new(Mutex::new(ChannelAllocator::new()));
+                let registration = self.hub.register(session_tx.clone(), Some(Peer {
+                    connection: transport.connection().clone(),
+                    identity: identity.clone(),
+                    connected_at: SystemTime::now(),
+                    channels: channels.clone(),
+                }));
+                let session = SessionInfo {
+                    identity: identity.clone(),
+                    connection_id: registration.id(),
+                    metadata: self.session_metadata.map(|f| f(&identity, &metadata)).unwrap_or_default(),
+                };
+                write_auth(&mut send, &AuthMessage::Ok { capabilities: capabilities.bits(), session }).await?;
+                #[cfg(feature = "tracing")]
+                {
+                    tracing::Span::current().record("connection_id", registration.id());
+                    tracing::info!(%identity, ?capabilities, "client authenticated");
+                }
+                Ok(ServerConnection {
+                    transport,
+                    identity,
+                    client_metadata: metadata,
+                    capabilities,
+                    registration,
+                    hub: self.hub,
+                    session: (session_tx, session_rx),
+                    channels,
+                    rate_limit: self.rate_limit,
+                    handlers: HashMap::new(),
+                    default_handler: None,
+                    close_handler: None,
+                    digest_mismatch_handler: None,
+                })
+            }
+            Err(reason) => {
+                write_auth(&mut send, &AuthMessage::Fail { reason: reason.clone() }).await?;
+                let _ = send.finish().await;
+                transport.connection().close(quinn::VarInt::from_u32(AUTH_FAILED_CODE), b"auth failed");
+                Err(anyhow!("Client rejected: {}", reason))
+            }
+        }
+    }
+}
+
+/// An authenticated client. Register handlers, then call [`run`](Self::run).
+pub struct ServerConnection {
+    transport: Transport,
+    identity: String,
+    client_metadata: Vec<u8>,
+    capabilities: Capabilities,
+    hub: Hub,
+    // Keeps the client in the hub until `run` returns
+    registration: Registration,
+    // Queue onto the session stream, fed by replies and hub pushes
+    session: (mpsc::Sender<Vec<u8>>, mpsc::Receiver<Vec<u8>>),
+    // Ids assigned through `OpenChannel`, also counted by the hub
+    channels: Arc<Mutex<ChannelAllocator>>,
+    rate_limit: Option<RateLimit>,
+    handlers: HashMap<u32, Handler>,
+    default_handler: Option<Handler>,
+    close_handler: Option<CloseHandler>,
+    digest_mismatch_handler: Option<DigestMismatchHandler>,
+}
+
+/// Handlers shared by the stream and datagram tasks of one connection.
+struct Routes {
+    handlers: HashMap<u32, Handler>,
+    default_handler: Option<Handler>,
+    close_handler: Option<CloseHandler>,
+    digest_mismatch_handler: Option<DigestMismatchHandler>,
+}
+
+impl ServerConnection {
+    /// Identity returned by the authenticator that accepted the client.
+    pub fn identity(&self) -> &str {
+        &self.identity
+    }
+
+    /// Session context the client sent in its `Hello`, as is; empty if it
+    /// sent none.
+    pub fn client_metadata(&self) -> &[u8] {
+        &self.client_metadata
+    }
+
+    /// Features agreed with this client during the handshake.
+    pub fn capabilities(&self) -> Capabilities {
+        self.capabilities
+    }
+
+    /// This client's id in the server's [`Hub`].
+    pub fn id(&self) -> ConnectionId {
+        self.registration.id()
+    }
+
+    /// The server's registry of connected clients, for pushing frames to
+    /// them, this one included.
+    pub fn hub(&self) -> &Hub {
+        &self.hub
+    }
+
+    /// The underlying QUIC connection. For a WebTransport client, streams
+    /// and datagrams on it also belong to HTTP/3.
+    pub fn connection(&self) -> &Connection {
+        self.transport.connection()
+    }
+
+    /// Whether the client connected over WebTransport (see
+    /// [`OrzattyServer::with_webtransport`]).
+    pub fn is_webtransport(&self) -> bool {
+        matches!(self.transport, Transport::WebTransport(_))
+    }
+
+    /// Certificate chain the client presented, leaf first. Only set on
+    /// servers bound with [`OrzattyServer::bind_with_client_auth`].
+    pub fn peer_certificates(&self) -> Option<Vec<rustls::Certificate>> {
+        peer_certificates(self.connection())
+    }
+
+    /// The ALPN protocol agreed with the client; `None` if it offered none.
+    pub fn alpn_protocol(&self) -> Option<Vec<u8>> {
+        alpn_protocol(self.connection())
+    }
+
+    /// Handles frames on `channel_id`. Returning `Some(reply)` sends `reply`
+    /// back with the same header, which is also how `EasyClient::request`
+    /// replies are produced.
+    pub fn on(&mut self, channel_id: u32, handler: impl Fn(FrameHeader, Bytes) -> Option<Bytes> + Send + Sync + 'static) {
+        self.handlers.insert(channel_id, Box::new(handler));
+    }
+
+    /// Relays every frame received on `channel_id` to all connected clients,
+    /// the sender included, via [`Hub::broadcast`]. Replaces any handler set
+    /// with [`on`](Self::on) for that channel.
+    pub fn broadcast_channel(&mut self, channel_id: u32) {
+        let hub = self.hub.clone();
+        self.on(channel_id, move |_header, payload| {
+            hub.broadcast(channel_id, &payload);
+            None
+        });
+    }
+
+    /// Handles frames on channels without an [`on`](Self::on) handler.
+    pub fn on_any(&mut self, handler: impl Fn(FrameHeader, Bytes) -> Option<Bytes> + Send + Sync + 'static) {
+        self.default_handler = Some(Box::new(handler));
+    }
+
+    /// Called with the code and reason of a client's `ControlMessage::Close`.
+    pub fn on_close(&mut self, handler: impl Fn(u16, String) + Send + Sync + 'static) {
+        self.close_handler = Some(Box::new(handler));
+    }
+
+    /// Called when a client's `ControlMessage::Close` carries a stream
+    /// digest that differs from the digest of the bytes the server read on
+    /// that stream before it: some were lost, duplicated or altered. Gets
+    /// the client's digest, then the server's. Without a handler the
+    /// mismatch is only logged, with the `tracing` feature. The close handler
+    /// runs either way.
+    #[cfg(feature = "stream-digest")]
+    pub fn on_digest_mismatch(&mut self, handler: impl Fn([u8; 32], [u8; 32]) + Send + Sync + 'static) {
+        self.digest_mismatch_handler = Some(Box::new(handler));
+    }
+
+    /// Serves the connection until the client closes it.
+    ///
+    /// Every bidirectional stream the client opens is read in its own task;
+    /// datagram frames are routed to the same handlers. The first one is the
+    /// client's session stream, which also carries frames pushed through the
+    /// [`Hub`]. Returns `Ok(())` when the client closes the connection on
+    /// purpose; the client leaves the hub either way.
+    ///
+    /// Under a [`RateLimit`] every frame read, on any stream or as a datagram,
+    /// is charged to one budget for the connection.
+    #[cfg_attr(feature = "tracing", tracing::instrument(
+        name = "connection",
+        skip_all,
+        fields(connection_id = self.registration.id(), identity = %self.identity),
+    ))]
+    pub async fn run(self) -> Result<()> {
+        let _registration = self.registration;
+        let mut session = Some(self.session);
+        let transport = self.transport;
+        let routes = Arc::new(Routes {
+            handlers: self.handlers,
+            default_handler: self.default_handler,
+            close_handler: self.close_handler,
+            digest_mismatch_handler: self.digest_mismatch_handler,
+        });
+        let channels = self.channels;
+        let rate_limit = self.rate_limit;
+        let limiter = rate_limit.map(|limit| Arc::new(Mutex::new(Limiter::new(&limit))));
+
+        // Without the capability, datagrams are left unread
+        if self.capabilities.contains(Capabilities::DATAGRAMS) {
+            let datagrams = transport.clone();
+            let datagram_routes = routes.clone();
+            let limiter = limiter.clone();
+            spawn_in_span(async move {
+                while let Ok(datagram) = datagrams.read_datagram().await {
+                    let Ok((mut header, head_len)) = FrameHeader::decode(&datagram) else { continue };
+                    let body = &datagram[head_len..];
+                    if header.length != body.len() as u64 {
+                        continue;
+                    }
+                    if let (Some(limit), Some(limiter)) = (rate_limit, &limiter) {
+                        if !limiter.lock().unwrap().charge(body.len()).is_zero() {
+                            // There is no stream here to announce the close on
+                            #[cfg(feature = "tracing")]
+                            tracing::warn!(channel_id = header.channel_id, action = ?limit.on_exceed, "datagram over rate limit");
+                            if limit.on_exceed == RateLimitAction::Close {
+                                datagrams.connection().close(quinn::VarInt::from_u32(RATE_LIMITED_CODE), RATE_LIMITED_REASON.as_bytes());
+                                break;
+                            }
+                            continue;
+                        }
+                    }
+                    let Ok(payload) = header.strip_checksum(body) else { continue };
+                    header.length = payload.len() as u64;
+                    if let Some(reply) = datagram_routes.route(header, datagram.slice_ref(payload)) {
+                        let mut frame = Vec::new();
+                        if header.encode_with_payload(&reply, &mut frame).is_ok() {
+                            let _ = datagrams.send_datagram(frame.into());
+                        }
+                    }
+                }
+            });
+        }
+
+        loop {
+            let (mut send, mut recv) = match transport.accept_bi().await {
+                Ok(streams) => streams,
+                // The client closed on purpose (e.g. after sending `Close`)
+                Err(quinn::ConnectionError::ApplicationClosed(_)) => return Ok(()),
+                Err(e) => return Err(e.into()),
+            };
+            let (tx, mut rx) = session.take().unwrap_or_else(|| mpsc::channel(PUSH_QUEUE_CAPACITY));
+            let routes = routes.clone();
+            let channels = channels.clone();
+            let limiter = limiter.clone();
+            let transport = transport.clone();
+            spawn_in_span(async move {
+                if !transport.claim(&mut recv).await {
+                    return;
+                }
+                // Written concurrently with reading, so a push never waits on a read
+                let writer = async {
+                    while let Some(frame) = rx.recv().await {
+                        if send.write_all(&frame).await.is_err() {
+                            break;
+                        }
+                    }
+                };
+                // Resolves to whether the client went over its rate limit
+                let reader = async {
+                    let mut framer = Framer::new();
+                    #[cfg(feature = "stream-digest")]
+                    let mut read = framer.stream_digest();
+                    while let Some((header, payload)) = framer.read_frame(&mut recv).await.unwrap_or(None) {
+                        // What the stream held before this frame, to check a `Close` against
+                        #[cfg(feature = "stream-digest")]
+                        let preceding = Some(std::mem::replace(&mut read, framer.stream_digest()));
+                        #[cfg(not(feature = "stream-digest"))]
+                        let preceding = None;
+                        #[cfg(feature = "tracing")]
+                        tracing::trace!(channel_id = header.channel_id, frame_type = ?header.frame_type, len = payload.len(), "frame received");
+                        if let (Some(limit), Some(limiter)) = (rate_limit, &limiter) {
+                            let wait = limiter.lock().unwrap().charge(payload.len());
+                            if !wait.is_zero() {
+                                #[cfg(feature = "tracing")]
+                                tracing::warn!(channel_id = header.channel_id, ?wait, action = ?limit.on_exceed, "over rate limit");
+                                match limit.on_exceed {
+                                    RateLimitAction::Throttle => tokio::time::sleep(wait).await,
+                                    RateLimitAction::Close => {
+                                        let close = ControlMessage::Close {
+                                            code: RATE_LIMITED_CODE as u16,
+                                            reason: RATE_LIMITED_REASON.into(),
+                                            digest: None,
+                                        };
+                                        if let Some(frame) = control_frame(&close) {
+                                            let _ = tx.send(frame).await;
+                                        }
+                                        return true;
+                                    }
+                                }
+                            }
+                        }
+                        let reply = if header.flags.contains(FrameFlags::CONTROL) {
+                            let reply = routes.handle_control(&payload, &channels, preceding);
+                            framer.recycle(payload);
+                            reply
+                        } else {
+                            let payload = payload.freeze();
+                            let reply = routes.route(header, payload.clone()).and_then(|reply| {
+                                let mut frame = Vec::new();
+                                header.encode_with_payload(&reply, &mut frame).ok()?;
+                                Some(frame)
+                            });
+                            // Reusable unless the handler kept the payload
+                            if let Ok(payload) = payload.try_into_mut() {
+                                framer.recycle(payload);
+                            }
+                            reply
+                        };
+                        if let Some(frame) = reply {
+                            let _ = tx.send(frame).await;
+                        }
+                    }
+                    false
+                };
+                let rate_limited = tokio::select! {
+                    rate_limited = reader => rate_limited,
+                    _ = writer => false,
+                };
+                // The client stopped sending: write what is queued, then
+                // finish the stream by dropping it
+                while let Ok(frame) = rx.try_recv() {
+                    if send.write_all(&frame).await.is_err() {
+                        break;
+                    }
+                }
+                if rate_limited {
+                    // Let the `Close` reach the client before the connection goes
+                    let _ = send.finish().await;
+                    transport.connection().close(quinn::VarInt::from_u32(RATE_LIMITED_CODE), RATE_LIMITED_REASON.as_bytes());
+                }
+            });
+        }
+    }
+}
+
+impl Routes {
+    /// Runs the handler for the frame's channel, isolating panics.
+    fn route(&self, header: FrameHeader, payload: Bytes) -> Option<Bytes> {
+        let handler = self.handlers.get(&header.channel_id).or(self.default_handler.as_ref())?;
+        let result = panic::catch_unwind(AssertUnwindSafe(|| handler(header, payload)));
+        #[cfg(feature = "tracing")]
+        if result.is_err() {
+            tracing::error!(channel_id = header.channel_id, "handler panicked");
+        }
+        result.ok().flatten()
+    }
+
+    /// Applies a control message; returns the encoded reply frame, if any.
+    /// `read` is the digest of the stream before the message, when kept.
+    fn handle_control(&self, payload: &[u8], channels: &Mutex<ChannelAllocator>, read: Option<[u8; 32]>) -> Option<Vec<u8>> {
+        // Copy into an aligned buffer: the payload may sit at any offset
+        let mut aligned = rkyv::AlignedVec::new();
+        aligned.extend_from_slice(payload);
+        let msg: ControlMessage = rkyv::from_bytes(&aligned).ok()?;
+
+        let reply = match msg {
+            ControlMessage::OpenChannel { request_id } => {
+                let channel_id = channels.lock().unwrap().allocate()?;
+                ControlMessage::ChannelOpened { request_id, channel_id }
+            }
+            ControlMessage::CloseChannel { channel_id } => {
+                channels.lock().unwrap().release(channel_id);
+                return None;
+            }
+            ControlMessage::Ping { ts } => ControlMessage::Pong { ts },
+            ControlMessage::Close { code, reason, digest } => {
+                if let (Some(sent), Some(read)) = (digest, read) {
+                    if sent != read {
+                        match &self.digest_mismatch_handler {
+                            Some(handler) => handler(sent, read),
+                            #[cfg(feature = "tracing")]
+                            None => tracing::error!(code, "stream digest mismatch on close"),
+                            #[cfg(not(feature = "tracing"))]
+                            None => {}
+                        }
+                    }
+                }
+                if let Some(handler) = &self.close_handler {
+                    handler(code, reason);
+                }
+                return None;
+            }
+            ControlMessage::ChannelOpened { .. } | ControlMessage::Pong { .. } => return None,
+        };
+
+        control_frame(&reply)
+    }
+}
+
+/// Spawns a task of the current connection, keeping it in the connection's
+/// `tracing` span when that feature is on.
+fn spawn_in_span(task: impl Future<Output = ()> + Send + 'static) {
+    #[cfg(feature = "tracing")]
+    let task = tracing::Instrument::in_current_span(task);
+    tokio::spawn(task);
+}
+
+/// Encodes `msg` as a control frame.
+fn control_frame(msg: &ControlMessage) -> Option<Vec<u8>> {
+    let body = rkyv::to_bytes::<_, 64>(msg).ok()?;
+    let head = FrameHeader {
+        flags: FrameFlags::CONTROL,
+        frame_type: FrameType::RkyvAligned,
+        channel_id: 0,
+        stream_id: 0,
+        length: body.len() as u64,
+    };
+    let mut frame = Vec::new();
+    head.encode_with_payload(&body, &mut frame).ok()?;
+    Some(frame)
+}
+
+fn peer_certificates(connection: &Connection) -> Option<Vec<rustls::Certificate>> {
+    connection.peer_identity()?.downcast::<Vec<rustls::Certificate>>().ok().map(|chain| *chain)
+}
+
+fn alpn_protocol(connection: &Connection) -> Option<Vec<u8>> {
+    connection.handshake_data()?.downcast::<quinn::crypto::rustls::HandshakeData>().ok()?.protocol
+}
+
+async fn write_auth(send: &mut quinn::SendStream, msg: &AuthMessage) -> Result<()> {
+    let resp = rkyv::to_bytes::<_, 256>(msg)
+        .map_err(|_| anyhow!("Failed to serialize auth response"))?;
+    let head = FrameHeader {
+        flags: FrameFlags::empty(),
+        frame_type: FrameType::RkyvAligned,
+        channel_id: 0,
+        stream_id: 0,
+        length: resp.len() as u64,
+    };
+    let mut frame = Vec::new();
+    head.encode_with_payload(&resp, &mut frame)?;
+    send.write_all(&frame).await?;
+    Ok(())
+}
+
+async fn read_auth(framer: &mut Framer, recv: &mut quinn::RecvStream) -> Result<Option<AuthMessage>> {
+    let Some((_header, payload)) = framer.read_frame_timeout(recv, AUTH_FRAME_TIMEOUT).await? else {
+        return Ok(None);
+    };
+    let mut aligned = rkyv::AlignedVec::new();
+    aligned.extend_from_slice(&payload);
+    let msg = rkyv::from_bytes(&aligned)
+        .map_err(|_| anyhow!("Failed to deserialize auth message"))?;
+    Ok(Some(msg))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dev_cert::generate_dev_cert;
+    use rcgen::{BasicConstraints, Certificate, CertificateParams, IsCa};
+
+    fn issue(ca: &Certificate, name: &str) -> (Vec<rustls::Certificate>, rustls::PrivateKey) {
+        let cert = Certificate::from_params(CertificateParams::new(vec![name.into()])).unwrap();
+        let der = cert.serialize_der_with_signer(ca).unwrap();
+        (vec![rustls::Certificate(der)], rustls::PrivateKey(cert.serialize_private_key_der()))
+    }
+
+    fn client(roots: rustls::RootCertStore, identity: Option<(Vec<rustls::Certificate>, rustls::PrivateKey)>) -> Endpoint {
+        let builder = rustls::ClientConfig::builder().with_safe_defaults().with_root_certificates(roots);
+        let mut crypto = match identity {
+            Some((chain, key)) => builder.with_client_auth_cert(chain, key).unwrap(),
+            None => builder.with_no_client_auth(),
+        };
+        crypto.alpn_protocols = vec![DEFAULT_ALPN.to_vec()];
+        let mut endpoint = Endpoint::client("127.0.0.1:0".parse().unwrap()).unwrap();
+        endpoint.set_default_client_config(quinn::ClientConfig::new(Arc::new(crypto)));
+        endpoint
+    }
+
+    #[tokio::test]
+    async fn test_client_certificate_required() {
+        let mut params = CertificateParams::new(vec![]);
+        params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        let ca = Certificate::from_params(params).unwrap();
+        let mut roots = rustls::RootCertStore::empty();
+        roots.add(&rustls::Certificate(ca.serialize_der().unwrap())).unwrap();
+
+        let (chain, key) = issue(&ca, "localhost");
+        let server = OrzattyServer::bind_with_client_auth("127.0.0.1:0".parse().unwrap(), chain, key, roots.clone()).unwrap();
+        let addr = server.local_addr().unwrap();
+
+        // Without a certificate the server refuses the handshake
+        let anonymous = client(roots.clone(), None);
+        let _attempt = anonymous.connect(addr, "localhost").unwrap();
+        assert!(server.accept().await.unwrap().connecting.await.is_err());
+
+        let (chain, key) = issue(&ca, "device-42");
+        let presented = chain.clone();
+        let device = client(roots, Some((chain, key)));
+        let connecting = device.connect(addr, "localhost").unwrap();
+        let (accepted, _connected) = tokio::join!(server.accept().await.unwrap().connecting, connecting);
+        assert_eq!(peer_certificates(&accepted.unwrap()), Some(presented));
+    }
+
+    /// A server with a fresh self-signed certificate and a client trusting it.
+    fn local_pair() -> (OrzattyServer, Endpoint) {
+        let (chain, key) = generate_dev_cert(vec!["localhost".into()], 1).unwrap();
+        let mut roots = rustls::RootCertStore::empty();
+        roots.add(&chain[0]).unwrap();
+        let server = OrzattyServer::bind("127.0.0.1:0".parse().unwrap(), chain, key).unwrap();
+        (server, client(roots, None))
+    }
+
+    /// Sends `hello` on a new connection and returns the server's verdict and reply.
+    async fn handshake(server: &OrzattyServer, peer: &Endpoint, hello: AuthMessage) -> (Result<ServerConnection>, Option<AuthMessage>) {
+        let connecting = peer.connect(server.local_addr().unwrap(), "localhost").unwrap();
+        let exchange = async {
+            let (mut send, mut recv) = connecting.await.unwrap().open_bi().await.unwrap();
+            write_auth(&mut send, &hello).await.unwrap();
+            read_auth(&mut Framer::new(), &mut recv).await.unwrap()
+        };
+        tokio::join!(server.accept().await.unwrap().authenticate(), exchange)
+    }
+
+    #[tokio::test]
+    async fn test_alpn_negotiated() {
+        let (chain, key) = generate_dev_cert(vec!["localhost".into()], 1).unwrap();
+        let mut roots = rustls::RootCertStore::empty();
+        roots.add(&chain[0]).unwrap();
+        let offering = |protocols: &[&[u8]]| {
+            let mut crypto = rustls::ClientConfig::builder().with_safe_defaults()
+                .with_root_certificates(roots.clone()).with_no_client_auth();
+            crypto.alpn_protocols = protocols.iter().map(|p| p.to_vec()).collect();
+            quinn::ClientConfig::new(Arc::new(crypto))
+        };
+        let peer = client(roots.clone(), None);
+
+        let server = OrzattyServer::bind("127.0.0.1:0".parse().unwrap(), chain.clone(), key.clone()).unwrap();
+        let addr = server.local_addr().unwrap();
+        let connecting = peer.connect_with(offering(&[b"h3", DEFAULT_ALPN]), addr, "localhost").unwrap();
+        let (accepted, connected) = tokio::join!(server.accept().await.unwrap().connecting, connecting);
+        assert_eq!(alpn_protocol(&accepted.unwrap()), Some(DEFAULT_ALPN.to_vec()));
+        assert_eq!(alpn_protocol(&connected.unwrap()), Some(DEFAULT_ALPN.to_vec()));
+
+        // Clients offering no ALPN, or only other protocols, are refused in
+        // the first flight, before the server would hand out a connection
+        assert!(peer.connect_with(offering(&[]), addr, "localhost").unwrap().await.is_err());
+        let custom = OrzattyServer::bind("127.0.0.1:0".parse().unwrap(), chain, key).unwrap()
+            .with_alpn_protocols(vec![b"orzatty-test".to_vec()]);
+        let addr = custom.local_addr().unwrap();
+        assert!(peer.connect_with(offering(&[DEFAULT_ALPN]), addr, "localhost").unwrap().await.is_err());
+        let connecting = peer.connect_with(offering(&[b"orzatty-test"]), addr, "localhost").unwrap();
+        let (accepted, _connected) = tokio::join!(custom.accept().await.unwrap().connecting, connecting);
+        assert_eq!(alpn_protocol(&accepted.unwrap()), Some(b"orzatty-test".to_vec()));
+    }
+
+    #[tokio::test]
+    async fn test_half_sent_frame_times_out() {
+        let (server, peer) = local_pair();
+        let connecting = peer.connect(server.local_addr().unwrap(), "localhost").unwrap();
+        let (accepted, connected) = tokio::join!(server.accept().await.unwrap().connecting, connecting);
+
+        // One byte of a header, then silence
+        let (mut send, _recv) = connected.unwrap().open_bi().await.unwrap();
+        send.write_all(&[FrameType::RkyvAligned as u8]).await.unwrap();
+        let (_send, mut recv) = accepted.unwrap().accept_bi().await.unwrap();
+
+        let mut framer = Framer::new();
+        let timeout = Duration::from_millis(100);
+        let err = framer.read_frame_timeout(&mut recv, timeout).await.unwrap_err();
+        assert_eq!(err.downcast_ref(), Some(&orzatty_core::Error::ReadTimeout(timeout)));
+        assert_eq!(framer.buffer_len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_protocol_version_mismatch_rejected() {
+        let (server, peer) = local_pair();
+        let server = server.with_authenticator(auth::Anonymous);
+        let hello = AuthMessage::Hello { token: "t".into(), protocol_version: PROTOCOL_VERSION + 1, capabilities: 0, metadata: Vec::new() };
+        let (verdict, reply) = handshake(&server, &peer, hello).await;
+        assert!(verdict.is_err());
+        let Some(AuthMessage::Fail { reason }) = reply else { panic!("expected Fail, got {:?}", reply) };
+        assert_eq!(reason, format!("unsupported protocol version {} (server speaks {})", PROTOCOL_VERSION + 1, PROTOCOL_VERSION));
+    }
+
+    #[tokio::test]
+    async fn test_capabilities_negotiated() {
+        let (server, peer) = local_pair();
+        let server = server
+            .with_authenticator(auth::Anonymous)
+            .with_capabilities(Capabilities::DATAGRAMS | Capabilities::CHECKSUM);
+        // Unknown bits from a newer client are ignored
+        let offered = (Capabilities::DATAGRAMS | Capabilities::COMPRESSION).bits() | 1 << 31;
+        let hello = AuthMessage::Hello { token: "t".into(), protocol_version: PROTOCOL_VERSION, capabilities: offered, metadata: Vec::new() };
+        let (verdict, reply) = handshake(&server, &peer, hello).await;
+        assert_eq!(verdict.unwrap().capabilities(), Capabilities::DATAGRAMS);
+        let Some(AuthMessage::Ok { capabilities, .. }) = reply else { panic!("expected Ok, got {:?}", reply) };
+        assert_eq!(capabilities, Capabilities::DATAGRAMS.bits());
+    }
+
+    #[tokio::test]
+    async fn test_session_metadata_exchanged() {
+        let (server, peer) = local_pair();
+        let server = server
+            .with_authenticator(auth::Anonymous)
+            .with_session_metadata(|identity, metadata| {
+                let mut reply = format!("{} from ", identity).into_bytes();
+                reply.extend_from_slice(metadata);
+                reply
+            });
+        let hello = AuthMessage::Hello { token: "t".into(), protocol_version: PROTOCOL_VERSION, capabilities: 0, metadata: b"eu-west".to_vec() };
+        let (verdict, reply) = handshake(&server, &peer, hello).await;
+        let conn = verdict.unwrap();
+        assert_eq!(conn.client_metadata(), b"eu-west");
+        let Some(AuthMessage::Ok { session, .. }) = reply else { panic!("expected Ok, got {:?}", reply) };
+        assert_eq!(session, SessionInfo {
+            identity: "anonymous".into(),
+            connection_id: conn.id(),
+            metadata: b"anonymous from eu-west".to_vec(),
+        });
+    }
+
+    #[tokio::test]
+    async fn test_connections_listed_and_disconnected() {
+        let (server, peer) = local_pair();
+        let server = server.with_authenticator(auth::Anonymous);
+        let connecting = peer.connect(server.local_addr().unwrap(), "localhost").unwrap();
+        let exchange = async {
+            let connection = connecting.await.unwrap();
+            let (mut send, mut recv) = connection.open_bi().await.unwrap();
+            let hello = AuthMessage::Hello { token: "t".into(), protocol_version: PROTOCOL_VERSION, capabilities: 0, metadata: Vec::new() };
+            write_auth(&mut send, &hello).await.unwrap();
+            read_auth(&mut Framer::new(), &mut recv).await.unwrap();
+            connection
+        };
+        let (conn, client) = tokio::join!(server.accept().await.unwrap().authenticate(), exchange);
+        let conn = conn.unwrap();
+
+        let [info] = &server.connections()[..] else { panic!("expected one connection") };
+        assert_eq!((info.id, info.identity.as_str(), info.channels), (conn.id(), "anonymous", 0));
+        assert_eq!(info.remote_address, peer.local_addr().unwrap());
+        assert!(info.bytes_received > 0 && info.bytes_sent > 0);
+
+        assert!(!server.disconnect(conn.id() + 1, "nobody"));
+        assert!(server.disconnect(conn.id(), "kicked"));
+        let quinn::ConnectionError::ApplicationClosed(close) = client.closed().await else { panic!("expected an application close") };
+        assert_eq!((close.error_code, &close.reason[..]), (quinn::VarInt::from_u32(DISCONNECTED_CODE), &b"kicked"[..]));
+        drop(conn);
+        assert!(server.connections().is_empty());
+    }
+}
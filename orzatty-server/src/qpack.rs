@@ -0,0 +1,350 @@
+//! The sliver of QPACK (RFC 9204) a WebTransport handshake needs.
+//!
+//! The server advertises no dynamic table, so field sections only ever
+//! reference the static table or carry literals. Literals from the client
+//! may be Huffman coded; the server's own are always sent plain.
+
+use anyhow::{anyhow, bail, Result};
+
+/// Decodes a HEADERS frame's field section into `(name, value)` pairs.
+pub(crate) fn decode(mut block: &[u8]) -> Result<Vec<(String, String)>> {
+    let required_insert_count = integer(&mut block, 8)?;
+    let _delta_base = integer(&mut block, 7)?;
+    if required_insert_count != 0 {
+        bail!("QPACK field section uses the dynamic table");
+    }
+
+    let mut fields = Vec::new();
+    while let Some(&first) = block.first() {
+        let field = if first & 0x80 != 0 {
+            // Indexed field line
+            if first & 0x40 == 0 {
+                bail!("QPACK field line references the dynamic table");
+            }
+            let (name, value) = static_entry(integer(&mut block, 6)?)?;
+            (name.to_string(), value.to_string())
+        } else if first & 0x40 != 0 {
+            // Literal field line with name reference
+            if first & 0x10 == 0 {
+                bail!("QPACK field line references the dynamic table");
+            }
+            let (name, _) = static_entry(integer(&mut block, 4)?)?;
+            (name.to_string(), string(&mut block, 7)?)
+        } else if first & 0x20 != 0 {
+            // Literal field line with literal name
+            let name = string(&mut block, 3)?;
+            (name, string(&mut block, 7)?)
+        } else {
+            bail!("QPACK field line references the dynamic table");
+        };
+        fields.push(field);
+    }
+    Ok(fields)
+}
+
+/// Encodes `fields` as a field section, referencing the static table where
+/// it has the field or its name.
+pub(crate) fn encode(fields: &[(&str, &str)]) -> Vec<u8> {
+    // Required Insert Count and Base: no dynamic table
+    let mut block = vec![0, 0];
+    for &(name, value) in fields {
+        if let Some(index) = STATIC_TABLE.iter().position(|&entry| entry == (name, value)) {
+            put_integer(&mut block, 0xC0, 6, index as u64);
+        } else if let Some(index) = STATIC_TABLE.iter().position(|&(n, _)| n == name) {
+            put_integer(&mut block, 0x50, 4, index as u64);
+            put_string(&mut block, 0x00, 7, value);
+        } else {
+            put_string(&mut block, 0x20, 3, name);
+            put_string(&mut block, 0x00, 7, value);
+        }
+    }
+    block
+}
+
+fn static_entry(index: u64) -> Result<(&'static str, &'static str)> {
+    usize::try_from(index).ok()
+        .and_then(|index| STATIC_TABLE.get(index).copied())
+        .ok_or_else(|| anyhow!("QPACK static table has no entry {}", index))
+}
+
+/// Reads a prefixed integer (RFC 7541 section 5.1) whose first byte keeps
+/// its low `prefix` bits.
+fn integer(block: &mut &[u8], prefix: u32) -> Result<u64> {
+    let truncated = || anyhow!("truncated QPACK field section");
+    let (&first, rest) = block.split_first().ok_or_else(truncated)?;
+    *block = rest;
+    let max = (1u64 << prefix) - 1;
+    let mut value = first as u64 & max;
+    if value < max {
+        return Ok(value);
+    }
+    for shift in (0..63).step_by(7) {
+        let (&byte, rest) = block.split_first().ok_or_else(truncated)?;
+        *block = rest;
+        value += ((byte & 0x7F) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    bail!("QPACK integer overflows")
+}
+
+/// Reads a string literal whose length has a `prefix`-bit prefix, right
+/// after its Huffman flag.
+fn string(block: &mut &[u8], prefix: u32) -> Result<String> {
+    let huffman = block.first().is_some_and(|&first| first & (1 << prefix) != 0);
+    let len = integer(block, prefix)?;
+    let len = usize::try_from(len).ok().filter(|&len| len <= block.len())
+        .ok_or_else(|| anyhow!("truncated QPACK field section"))?;
+    let (raw, rest) = block.split_at(len);
+    *block = rest;
+    let bytes = if huffman { huffman_decode(raw)? } else { raw.to_vec() };
+    String::from_utf8(bytes).map_err(|_| anyhow!("QPACK string is not UTF-8"))
+}
+
+/// Writes an integer with a `prefix`-bit prefix into a first byte starting
+/// as `pattern`.
+fn put_integer(block: &mut Vec<u8>, pattern: u8, prefix: u32, value: u64) {
+    let max = (1u64 << prefix) - 1;
+    if value < max {
+        block.push(pattern | value as u8);
+        return;
+    }
+    block.push(pattern | max as u8);
+    let mut rest = value - max;
+    while rest >= 0x80 {
+        block.push(rest as u8 | 0x80);
+        rest >>= 7;
+    }
+    block.push(rest as u8);
+}
+
+fn put_string(block: &mut Vec<u8>, pattern: u8, prefix: u32, value: &str) {
+    put_integer(block, pattern, prefix, value.len() as u64);
+    block.extend_from_slice(value.as_bytes());
+}
+
+/// Decodes a Huffman-coded string (RFC 7541 appendix B).
+fn huffman_decode(coded: &[u8]) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(coded.len() * 8 / 5);
+    let (mut code, mut len) = (0u32, 0u8);
+    for bit in coded.iter().flat_map(|byte| (0..8).rev().map(move |i| (byte >> i) & 1)) {
+        code = (code << 1) | bit as u32;
+        len += 1;
+        // Headers are short and only read once per session: a scan will do
+        if let Some(symbol) = HUFFMAN_CODES.iter().position(|&entry| entry == (code, len)) {
+            out.push(symbol as u8);
+            (code, len) = (0, 0);
+        } else if len >= 30 {
+            bail!("invalid Huffman code in QPACK string");
+        }
+    }
+    // Padding is the most significant bits of EOS: under a byte, all ones
+    if len > 7 || code != (1 << len) - 1 {
+        bail!("invalid Huffman padding in QPACK string");
+    }
+    Ok(out)
+}
+
+/// The QPACK static table (RFC 9204 appendix A).
+const STATIC_TABLE: [(&str, &str); 99] = [
+    (":authority", ""),
+    (":path", "/"),
+    ("age", "0"),
+    ("content-disposition", ""),
+    ("content-length", "0"),
+    ("cookie", ""),
+    ("date", ""),
+    ("etag", ""),
+    ("if-modified-since", ""),
+    ("if-none-match", ""),
+    ("last-modified", ""),
+    ("link", ""),
+    ("location", ""),
+    ("referer", ""),
+    ("set-cookie", ""),
+    (":method", "CONNECT"),
+    (":method", "DELETE"),
+    (":method", "GET"),
+    (":method", "HEAD"),
+    (":method", "OPTIONS"),
+    (":method", "POST"),
+    (":method", "PUT"),
+    (":scheme", "http"),
+    (":scheme", "https"),
+    (":status", "103"),
+    (":status", "200"),
+    (":status", "304"),
+    (":status", "404"),
+    (":status", "503"),
+    ("accept", "*/*"),
+    ("accept", "application/dns-message"),
+    ("accept-encoding", "gzip, deflate, br"),
+    ("accept-ranges", "bytes"),
+    ("access-control-allow-headers", "cache-control"),
+    ("access-control-allow-headers", "content-type"),
+    ("access-control-allow-origin", "*"),
+    ("cache-control", "max-age=0"),
+    ("cache-control", "max-age=2592000"),
+    ("cache-control", "max-age=604800"),
+    ("cache-control", "no-cache"),
+    ("cache-control", "no-store"),
+    ("cache-control", "public, max-age=31536000"),
+    ("content-encoding", "br"),
+    ("content-encoding", "gzip"),
+    ("content-type", "application/dns-message"),
+    ("content-type", "application/javascript"),
+    ("content-type", "application/json"),
+    ("content-type", "application/x-www-form-urlencoded"),
+    ("content-type", "image/gif"),
+    ("content-type", "image/jpeg"),
+    ("content-type", "image/png"),
+    ("content-type", "text/css"),
+    ("content-type", "text/html; charset=utf-8"),
+    ("content-type", "text/plain"),
+    ("content-type", "text/plain;charset=utf-8"),
+    ("range", "bytes=0-"),
+    ("strict-transport-security", "max-age=31536000"),
+    ("strict-transport-security", "max-age=31536000; includesubdomains"),
+    ("strict-transport-security", "max-age=31536000; includesubdomains; preload"),
+    ("vary", "accept-encoding"),
+    ("vary", "origin"),
+    ("x-content-type-options", "nosniff"),
+    ("x-xss-protection", "1; mode=block"),
+    (":status", "100"),
+    (":status", "204"),
+    (":status", "206"),
+    (":status", "302"),
+    (":status", "400"),
+    (":status", "403"),
+    (":status", "421"),
+    (":status", "425"),
+    (":status", "500"),
+    ("accept-language", ""),
+    ("access-control-allow-credentials", "FALSE"),
+    ("access-control-allow-credentials", "TRUE"),
+    ("access-control-allow-headers", "*"),
+    ("access-control-allow-methods", "get"),
+    ("access-control-allow-methods", "get, post, options"),
+    ("access-control-allow-methods", "options"),
+    ("access-control-expose-headers", "content-length"),
+    ("access-control-request-headers", "content-type"),
+    ("access-control-request-method", "get"),
+    ("access-control-request-method", "post"),
+    ("alt-svc", "clear"),
+    ("authorization", ""),
+    ("content-security-policy", "script-src 'none'; object-src 'none'; base-uri 'none'"),
+    ("early-data", "1"),
+    ("expect-ct", ""),
+    ("forwarded", ""),
+    ("if-range", ""),
+    ("origin", ""),
+    ("purpose", "prefetch"),
+    ("server", ""),
+    ("timing-allow-origin", "*"),
+    ("upgrade-insecure-requests", "1"),
+    ("user-agent", ""),
+    ("x-forwarded-for", ""),
+    ("x-frame-options", "deny"),
+    ("x-frame-options", "sameorigin"),
+];
+
+/// `(code, bit length)` of each byte value (RFC 7541 appendix B).
+const HUFFMAN_CODES: [(u32, u8); 256] = [
+    (0x1ff8, 13), (0x7fffd8, 23), (0xfffffe2, 28), (0xfffffe3, 28),
+    (0xfffffe4, 28), (0xfffffe5, 28), (0xfffffe6, 28), (0xfffffe7, 28),
+    (0xfffffe8, 28), (0xffffea, 24), (0x3ffffffc, 30), (0xfffffe9, 28),
+    (0xfffffea, 28), (0x3ffffffd, 30), (0xfffffeb, 28), (0xfffffec, 28),
+    (0xfffffed, 28), (0xfffffee, 28), (0xfffffef, 28), (0xffffff0, 28),
+    (0xffffff1, 28), (0xffffff2, 28), (0x3ffffffe, 30), (0xffffff3, 28),
+    (0xffffff4, 28), (0xffffff5, 28), (0xffffff6, 28), (0xffffff7, 28),
+    (0xffffff8, 28), (0xffffff9, 28), (0xffffffa, 28), (0xffffffb, 28),
+    (0x14, 6), (0x3f8, 10), (0x3f9, 10), (0xffa, 12),
+    (0x1ff9, 13), (0x15, 6), (0xf8, 8), (0x7fa, 11),
+    (0x3fa, 10), (0x3fb, 10), (0xf9, 8), (0x7fb, 11),
+    (0xfa, 8), (0x16, 6), (0x17, 6), (0x18, 6),
+    (0x0, 5), (0x1, 5), (0x2, 5), (0x19, 6),
+    (0x1a, 6), (0x1b, 6), (0x1c, 6), (0x1d, 6),
+    (0x1e, 6), (0x1f, 6), (0x5c, 7), (0xfb, 8),
+    (0x7ffc, 15), (0x20, 6), (0xffb, 12), (0x3fc, 10),
+    (0x1ffa, 13), (0x21, 6), (0x5d, 7), (0x5e, 7),
+    (0x5f, 7), (0x60, 7), (0x61, 7), (0x62, 7),
+    (0x63, 7), (0x64, 7), (0x65, 7), (0x66, 7),
+    (0x67, 7), (0x68, 7), (0x69, 7), (0x6a, 7),
+    (0x6b, 7), (0x6c, 7), (0x6d, 7), (0x6e, 7),
+    (0x6f, 7), (0x70, 7), (0x71, 7), (0x72, 7),
+    (0xfc, 8), (0x73, 7), (0xfd, 8), (0x1ffb, 13),
+    (0x7fff0, 19), (0x1ffc, 13), (0x3ffc, 14), (0x22, 6),
+    (0x7ffd, 15), (0x3, 5), (0x23, 6), (0x4, 5),
+    (0x24, 6), (0x5, 5), (0x25, 6), (0x26, 6),
+    (0x27, 6), (0x6, 5), (0x74, 7), (0x75, 7),
+    (0x28, 6), (0x29, 6), (0x2a, 6), (0x7, 5),
+    (0x2b, 6), (0x76, 7), (0x2c, 6), (0x8, 5),
+    (0x9, 5), (0x2d, 6), (0x77, 7), (0x78, 7),
+    (0x79, 7), (0x7a, 7), (0x7b, 7), (0x7ffe, 15),
+    (0x7fc, 11), (0x3ffd, 14), (0x1ffd, 13), (0xffffffc, 28),
+    (0xfffe6, 20), (0x3fffd2, 22), (0xfffe7, 20), (0xfffe8, 20),
+    (0x3fffd3, 22), (0x3fffd4, 22), (0x3fffd5, 22), (0x7fffd9, 23),
+    (0x3fffd6, 22), (0x7fffda, 23), (0x7fffdb, 23), (0x7fffdc, 23),
+    (0x7fffdd, 23), (0x7fffde, 23), (0xffffeb, 24), (0x7fffdf, 23),
+    (0xffffec, 24), (0xffffed, 24), (0x3fffd7, 22), (0x7fffe0, 23),
+    (0xffffee, 24), (0x7fffe1, 23), (0x7fffe2, 23), (0x7fffe3, 23),
+    (0x7fffe4, 23), (0x1fffdc, 21), (0x3fffd8, 22), (0x7fffe5, 23),
+    (0x3fffd9, 22), (0x7fffe6, 23), (0x7fffe7, 23), (0xffffef, 24),
+    (0x3fffda, 22), (0x1fffdd, 21), (0xfffe9, 20), (0x3fffdb, 22),
+    (0x3fffdc, 22), (0x7fffe8, 23), (0x7fffe9, 23), (0x1fffde, 21),
+    (0x7fffea, 23), (0x3fffdd, 22), (0x3fffde, 22), (0xfffff0, 24),
+    (0x1fffdf, 21), (0x3fffdf, 22), (0x7fffeb, 23), (0x7fffec, 23),
+    (0x1fffe0, 21), (0x1fffe1, 21), (0x3fffe0, 22), (0x1fffe2, 21),
+    (0x7fffed, 23), (0x3fffe1, 22), (0x7fffee, 23), (0x7fffef, 23),
+    (0xfffea, 20), (0x3fffe2, 22), (0x3fffe3, 22), (0x3fffe4, 22),
+    (0x7ffff0, 23), (0x3fffe5, 22), (0x3fffe6, 22), (0x7ffff1, 23),
+    (0x3ffffe0, 26), (0x3ffffe1, 26), (0xfffeb, 20), (0x7fff1, 19),
+    (0x3fffe7, 22), (0x7ffff2, 23), (0x3fffe8, 22), (0x1ffffec, 25),
+    (0x3ffffe2, 26), (0x3ffffe3, 26), (0x3ffffe4, 26), (0x7ffffde, 27),
+    (0x7ffffdf, 27), (0x3ffffe5, 26), (0xfffff1, 24), (0x1ffffed, 25),
+    (0x7fff2, 19), (0x1fffe3, 21), (0x3ffffe6, 26), (0x7ffffe0, 27),
+    (0x7ffffe1, 27), (0x3ffffe7, 26), (0x7ffffe2, 27), (0xfffff2, 24),
+    (0x1fffe4, 21), (0x1fffe5, 21), (0x3ffffe8, 26), (0x3ffffe9, 26),
+    (0xffffffd, 28), (0x7ffffe3, 27), (0x7ffffe4, 27), (0x7ffffe5, 27),
+    (0xfffec, 20), (0xfffff3, 24), (0xfffed, 20), (0x1fffe6, 21),
+    (0x3fffe9, 22), (0x1fffe7, 21), (0x1fffe8, 21), (0x7ffff3, 23),
+    (0x3fffea, 22), (0x3fffeb, 22), (0x1ffffee, 25), (0x1ffffef, 25),
+    (0xfffff4, 24), (0xfffff5, 24), (0x3ffffea, 26), (0x7ffff4, 23),
+    (0x3ffffeb, 26), (0x7ffffe6, 27), (0x3ffffec, 26), (0x3ffffed, 26),
+    (0x7ffffe7, 27), (0x7ffffe8, 27), (0x7ffffe9, 27), (0x7ffffea, 27),
+    (0x7ffffeb, 27), (0xffffffe, 28), (0x7ffffec, 27), (0x7ffffed, 27),
+    (0x7ffffee, 27), (0x7ffffef, 27), (0x7fffff0, 27), (0x3ffffee, 26),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_field_section_round_trip() {
+        let fields = [(":status", "200"), (":path", "/chat"), ("sec-webtransport-http3-draft", "draft02")];
+        let block = encode(&fields);
+        // `:status 200` is static entry 25
+        assert_eq!(&block[..3], &[0, 0, 0xC0 | 25]);
+        let decoded = decode(&block).unwrap();
+        assert_eq!(decoded, fields.map(|(n, v)| (n.to_string(), v.to_string())));
+    }
+
+    #[test]
+    fn test_huffman_literal() {
+        // RFC 7541 C.4.1: "www.example.com", Huffman coded
+        let coded = [0xf1, 0xe3, 0xc2, 0xe5, 0xf2, 0x3a, 0x6b, 0xa0, 0xab, 0x90, 0xf4, 0xff];
+        let mut block = vec![0, 0, 0x50, 0x80 | coded.len() as u8];
+        block.extend_from_slice(&coded);
+        assert_eq!(decode(&block).unwrap(), vec![(":authority".to_string(), "www.example.com".to_string())]);
+
+        // Padding must stay under a byte, and be all ones
+        assert!(huffman_decode(&[0xff]).is_err());
+        assert_eq!(huffman_decode(&[0x1f]).unwrap(), b"a");
+        assert!(huffman_decode(&[0x1e]).is_err());
+        // Dynamic table references are refused
+        assert!(decode(&[1, 0]).is_err());
+        assert!(decode(&[0, 0, 0x80]).is_err());
+    }
+}
@@ -0,0 +1,139 @@
+//! Per-connection limits on what a client may send.
+
+use std::time::{Duration, Instant};
+
+/// Caps on the frames and bytes a client sends, set with
+/// [`OrzattyServer::with_rate_limit`](crate::OrzattyServer::with_rate_limit).
+///
+/// Each cap allows a burst of one second's worth before it starts to bite.
+/// Every frame counts, control frames and datagrams included, against one
+/// budget shared by all streams of the connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+    /// Most frames per second; `None` for no cap.
+    pub frames_per_sec: Option<u32>,
+    /// Most payload bytes per second; `None` for no cap.
+    pub bytes_per_sec: Option<u64>,
+    /// What happens to a client over budget.
+    pub on_exceed: RateLimitAction,
+}
+
+/// How the server treats a client that exceeds its [`RateLimit`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateLimitAction {
+    /// Stop reading the stream until the client is back within budget, which
+    /// pushes back on it through QUIC flow control. Datagrams over budget are
+    /// dropped.
+    Throttle,
+    /// Send a `ControlMessage::Close` with code
+    /// [`RATE_LIMITED_CODE`](crate::RATE_LIMITED_CODE) and close the
+    /// connection.
+    Close,
+}
+
+impl RateLimit {
+    /// Caps frames per second, throttling a client over budget.
+    pub fn frames_per_sec(frames: u32) -> Self {
+        Self { frames_per_sec: Some(frames), bytes_per_sec: None, on_exceed: RateLimitAction::Throttle }
+    }
+
+    /// Caps payload bytes per second, throttling a client over budget.
+    pub fn bytes_per_sec(bytes: u64) -> Self {
+        Self { frames_per_sec: None, bytes_per_sec: Some(bytes), on_exceed: RateLimitAction::Throttle }
+    }
+
+    /// Adds a cap on payload bytes per second.
+    pub fn with_bytes_per_sec(mut self, bytes: u64) -> Self {
+        self.bytes_per_sec = Some(bytes);
+        self
+    }
+
+    /// Adds a cap on frames per second.
+    pub fn with_frames_per_sec(mut self, frames: u32) -> Self {
+        self.frames_per_sec = Some(frames);
+        self
+    }
+
+    /// Sets what happens to a client over budget.
+    pub fn on_exceed(mut self, action: RateLimitAction) -> Self {
+        self.on_exceed = action;
+        self
+    }
+}
+
+/// The budget of one connection under a [`RateLimit`].
+pub(crate) struct Limiter {
+    frames: Option<Bucket>,
+    bytes: Option<Bucket>,
+}
+
+impl Limiter {
+    pub(crate) fn new(limit: &RateLimit) -> Self {
+        let now = Instant::now();
+        Self {
+            frames: limit.frames_per_sec.map(|rate| Bucket::new(rate as f64, now)),
+            bytes: limit.bytes_per_sec.map(|rate| Bucket::new(rate as f64, now)),
+        }
+    }
+
+    /// Charges one frame of `len` payload bytes and returns how long the
+    /// client must stay unread to be back within budget; zero if it is.
+    pub(crate) fn charge(&mut self, len: usize) -> Duration {
+        self.charge_at(len, Instant::now())
+    }
+
+    fn charge_at(&mut self, len: usize, now: Instant) -> Duration {
+        let frames = self.frames.as_mut().map_or(Duration::ZERO, |b| b.reserve(1.0, now));
+        let bytes = self.bytes.as_mut().map_or(Duration::ZERO, |b| b.reserve(len as f64, now));
+        frames.max(bytes)
+    }
+}
+
+/// Token bucket whose balance goes negative on overdraft; the debt tells how
+/// long until it is repaid.
+struct Bucket {
+    rate: f64,
+    tokens: f64,
+    last: Instant,
+}
+
+impl Bucket {
+    fn new(rate: f64, now: Instant) -> Self {
+        let rate = rate.max(1.0);
+        Self { rate, tokens: rate, last: now }
+    }
+
+    fn reserve(&mut self, amount: f64, now: Instant) -> Duration {
+        let elapsed = now.saturating_duration_since(self.last).as_secs_f64();
+        self.last = now;
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.rate) - amount;
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / self.rate)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_limiter_charges_frames_and_bytes() {
+        let limit = RateLimit::frames_per_sec(10).with_bytes_per_sec(1000);
+        let mut limiter = Limiter::new(&limit);
+        let start = Instant::now();
+
+        // A second's worth of either is free
+        for _ in 0..10 {
+            assert_eq!(limiter.charge_at(10, start), Duration::ZERO);
+        }
+        // The frame cap bites first...
+        assert_eq!(limiter.charge_at(10, start), Duration::from_millis(100));
+
+        // ...and the byte cap once frames are small but payloads large
+        let later = start + Duration::from_secs(2);
+        assert_eq!(limiter.charge_at(1500, later), Duration::from_millis(500));
+    }
+}
@@ -0,0 +1,345 @@
+//! WebTransport sessions, for browser clients such as `orzatty-wasm`.
+//!
+//! Browsers cannot open raw QUIC connections: they speak HTTP/3 and ask for
+//! a WebTransport session with an extended CONNECT request
+//! (draft-ietf-webtrans-http3). The server answers just enough HTTP/3 for
+//! that: its SETTINGS, the CONNECT, and the prefixes that tie streams and
+//! datagrams to the session. Orzatty frames then flow over the session's
+//! streams and datagrams exactly as over a native connection.
+//!
+//! Enabled with [`OrzattyServer::with_webtransport`](crate::OrzattyServer::with_webtransport);
+//! connections that negotiate [`WEBTRANSPORT_ALPN`] take this path.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Result};
+use bytes::Bytes;
+use orzatty_core::frame::{decode_varint, encode_varint};
+use quinn::{Connection, ConnectionError, RecvStream, SendDatagramError, SendStream, VarInt};
+
+use crate::qpack;
+
+/// ALPN protocol id of HTTP/3, which browsers offer for WebTransport.
+pub const WEBTRANSPORT_ALPN: &[u8] = b"h3";
+
+// HTTP/3 stream and frame types, and settings (RFC 9114, RFC 9220,
+// RFC 9297 and the WebTransport draft)
+const CONTROL_STREAM: u64 = 0x00;
+const QPACK_ENCODER_STREAM: u64 = 0x02;
+const QPACK_DECODER_STREAM: u64 = 0x03;
+const HEADERS_FRAME: u64 = 0x01;
+const SETTINGS_FRAME: u64 = 0x04;
+const WEBTRANSPORT_STREAM: u64 = 0x41;
+const SETTINGS: [(u64, u64); 6] = [
+    (0x08, 1),       // ENABLE_CONNECT_PROTOCOL
+    (0x33, 1),       // H3_DATAGRAM
+    (0xffd277, 1),   // H3_DATAGRAM, as older Chrome versions spell it
+    (0x2b603742, 1), // ENABLE_WEBTRANSPORT
+    (0x2b603743, 1), // WEBTRANSPORT_MAX_SESSIONS, drafts 02 to 06
+    (0xc671706a, 1), // WEBTRANSPORT_MAX_SESSIONS, draft 07 on
+];
+const H3_NO_ERROR: u32 = 0x100;
+const H3_STREAM_CREATION_ERROR: u32 = 0x103;
+const H3_REQUEST_INCOMPLETE: u32 = 0x10d;
+
+/// Largest HEADERS frame accepted from a client.
+const MAX_HEADERS_LEN: u64 = 16 * 1024;
+
+/// How long a client has to send the headers of a request it opened.
+const HEADERS_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Where a client's streams and datagrams come from: the QUIC connection
+/// itself, or a WebTransport session inside it.
+#[derive(Clone)]
+pub(crate) enum Transport {
+    Quic(Connection),
+    WebTransport(Arc<Session>),
+}
+
+impl Transport {
+    pub(crate) fn connection(&self) -> &Connection {
+        match self {
+            Transport::Quic(connection) => connection,
+            Transport::WebTransport(session) => &session.connection,
+        }
+    }
+
+    /// Waits for the client's next bidirectional stream. Under WebTransport
+    /// it still has to be [claimed](Self::claim) before carrying frames.
+    ///
+    /// The client ending its session reads as it closing the connection.
+    pub(crate) async fn accept_bi(&self) -> Result<(SendStream, RecvStream), ConnectionError> {
+        let result = self.connection().accept_bi().await;
+        match (self, result) {
+            (Transport::WebTransport(session), Err(ConnectionError::LocallyClosed)) if session.ended.load(Ordering::Acquire) => {
+                Err(ConnectionError::ApplicationClosed(quinn::ApplicationClose {
+                    error_code: VarInt::from_u32(H3_NO_ERROR),
+                    reason: Bytes::new(),
+                }))
+            }
+            (_, result) => result,
+        }
+    }
+
+    /// Reads the prefix a WebTransport stream opens with and tells whether
+    /// it belongs to the session; the frames follow. Always true over QUIC.
+    pub(crate) async fn claim(&self, recv: &mut RecvStream) -> bool {
+        let Transport::WebTransport(session) = self else { return true };
+        matches!(
+            (read_varint(recv).await, read_varint(recv).await),
+            (Ok(WEBTRANSPORT_STREAM), Ok(id)) if id == session.id
+        )
+    }
+
+    pub(crate) async fn read_datagram(&self) -> Result<Bytes, ConnectionError> {
+        let Transport::WebTransport(session) = self else {
+            return self.connection().read_datagram().await;
+        };
+        // HTTP datagrams open with the session's quarter stream id
+        loop {
+            let datagram = session.connection.read_datagram().await?;
+            if let Ok((quarter_id, len)) = decode_varint(&datagram) {
+                if quarter_id == session.id / 4 {
+                    return Ok(datagram.slice(len..));
+                }
+            }
+        }
+    }
+
+    pub(crate) fn send_datagram(&self, data: Bytes) -> Result<(), SendDatagramError> {
+        let Transport::WebTransport(session) = self else {
+            return self.connection().send_datagram(data);
+        };
+        let mut datagram = Vec::with_capacity(8 + data.len());
+        put_varint(&mut datagram, session.id / 4);
+        datagram.extend_from_slice(&data);
+        session.connection.send_datagram(datagram.into())
+    }
+}
+
+/// A WebTransport session, established by the client's CONNECT request.
+pub(crate) struct Session {
+    connection: Connection,
+    // Id of the CONNECT stream, which names the session
+    id: u64,
+    // Set once the client ends the session
+    ended: Arc<AtomicBool>,
+    // Closing either ends the session, or HTTP/3 for the whole connection
+    _control: SendStream,
+    _response: SendStream,
+}
+
+/// Runs the HTTP/3 side of a connection that negotiated
+/// [`WEBTRANSPORT_ALPN`] until a CONNECT request opens a session.
+///
+/// Other requests get a `404`; the connection stays usable for a CONNECT.
+pub(crate) async fn accept(connection: Connection) -> Result<Session> {
+    let mut control = connection.open_uni().await?;
+    let mut settings = Vec::new();
+    for (id, value) in SETTINGS {
+        put_varint(&mut settings, id);
+        put_varint(&mut settings, value);
+    }
+    let mut stream = Vec::new();
+    put_varint(&mut stream, CONTROL_STREAM);
+    put_frame(&mut stream, SETTINGS_FRAME, &settings);
+    control.write_all(&stream).await?;
+
+    // The client's control and QPACK streams carry nothing the server needs,
+    // but must be read for as long as the connection lives. Sessions carry
+    // frames on bidirectional streams only, so other unidirectional streams,
+    // WebTransport's included, are refused rather than read
+    let incoming = connection.clone();
+    tokio::spawn(async move {
+        while let Ok(mut recv) = incoming.accept_uni().await {
+            tokio::spawn(async move {
+                match read_varint(&mut recv).await {
+                    Ok(CONTROL_STREAM | QPACK_ENCODER_STREAM | QPACK_DECODER_STREAM) => {
+                        while let Ok(Some(_)) = recv.read_chunk(usize::MAX, true).await {}
+                    }
+                    Ok(_) => {
+                        let _ = recv.stop(VarInt::from_u32(H3_STREAM_CREATION_ERROR));
+                    }
+                    Err(_) => {}
+                }
+            });
+        }
+    });
+
+    loop {
+        let (mut send, mut recv) = connection.accept_bi().await?;
+        let fields = match tokio::time::timeout(HEADERS_TIMEOUT, read_headers(&mut recv)).await {
+            Ok(Ok(fields)) => fields,
+            Ok(Err(_)) => continue,
+            Err(_) => {
+                connection.close(VarInt::from_u32(H3_REQUEST_INCOMPLETE), b"request headers timed out");
+                bail!("no request headers within {:?}", HEADERS_TIMEOUT);
+            }
+        };
+        let field = |name: &str| fields.iter().find(|(n, _)| n == name).map(|(_, v)| v.as_str());
+        if field(":method") != Some("CONNECT") || field(":protocol") != Some("webtransport") {
+            let mut response = Vec::new();
+            put_frame(&mut response, HEADERS_FRAME, &qpack::encode(&[(":status", "404")]));
+            let _ = send.write_all(&response).await;
+            let _ = send.finish().await;
+            continue;
+        }
+
+        let mut response = Vec::new();
+        let headers = qpack::encode(&[(":status", "200"), ("sec-webtransport-http3-draft", "draft02")]);
+        put_frame(&mut response, HEADERS_FRAME, &headers);
+        send.write_all(&response).await?;
+
+        // The session lasts until the client finishes or resets the CONNECT
+        // stream; capsules sent on it meanwhile are skipped
+        let ended = Arc::new(AtomicBool::new(false));
+        let watcher = (connection.clone(), ended.clone());
+        tokio::spawn(async move {
+            while let Ok(Some(_)) = recv.read_chunk(usize::MAX, true).await {}
+            let (connection, ended) = watcher;
+            ended.store(true, Ordering::Release);
+            connection.close(VarInt::from_u32(H3_NO_ERROR), b"session closed");
+        });
+
+        let id = VarInt::from(send.id()).into_inner();
+        return Ok(Session { connection, id, ended, _control: control, _response: send });
+    }
+}
+
+/// Reads HTTP/3 frames off a request stream until its HEADERS, skipping
+/// frames of other types (e.g. GREASE), and decodes them.
+async fn read_headers(recv: &mut RecvStream) -> Result<Vec<(String, String)>> {
+    loop {
+        let frame_type = read_varint(recv).await?;
+        let len = read_varint(recv).await?;
+        if len > MAX_HEADERS_LEN {
+            bail!("HTTP/3 frame of {} bytes on a request stream", len);
+        }
+        let mut payload = vec![0; len as usize];
+        recv.read_exact(&mut payload).await?;
+        if frame_type == HEADERS_FRAME {
+            return qpack::decode(&payload);
+        }
+    }
+}
+
+async fn read_varint(recv: &mut RecvStream) -> Result<u64> {
+    let mut buf = [0u8; 8];
+    recv.read_exact(&mut buf[..1]).await?;
+    let len = 1 << (buf[0] >> 6);
+    recv.read_exact(&mut buf[1..len]).await?;
+    let (value, _) = decode_varint(&buf[..len]).map_err(|e| anyhow!("{}", e))?;
+    Ok(value)
+}
+
+fn put_varint(buf: &mut Vec<u8>, value: u64) {
+    let mut bytes = [0u8; 8];
+    // Only ids and lengths well inside the varint range are written
+    let len = encode_varint(value, &mut bytes).unwrap_or(0);
+    buf.extend_from_slice(&bytes[..len]);
+}
+
+fn put_frame(buf: &mut Vec<u8>, frame_type: u64, payload: &[u8]) {
+    put_varint(buf, frame_type);
+    put_varint(buf, payload.len() as u64);
+    buf.extend_from_slice(payload);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use orzatty_core::auth::{AuthMessage, Capabilities};
+    use orzatty_core::frame::FrameBuilder;
+    use orzatty_core::{Framer, PROTOCOL_VERSION};
+
+    use crate::dev_cert::generate_dev_cert;
+    use crate::{auth, read_auth, write_auth, OrzattyServer};
+
+    /// What a browser does to open a session; returns its id and the
+    /// CONNECT stream, whose end ends the session.
+    async fn open_session(connection: &Connection) -> (u64, SendStream) {
+        let (mut send, mut recv) = connection.open_bi().await.unwrap();
+        let request = qpack::encode(&[
+            (":method", "CONNECT"),
+            (":protocol", "webtransport"),
+            (":scheme", "https"),
+            (":authority", "localhost"),
+            (":path", "/orzatty"),
+        ]);
+        let mut frame = Vec::new();
+        put_frame(&mut frame, HEADERS_FRAME, &request);
+        send.write_all(&frame).await.unwrap();
+        let response = read_headers(&mut recv).await.unwrap();
+        assert!(response.contains(&(":status".into(), "200".into())));
+        (VarInt::from(send.id()).into_inner(), send)
+    }
+
+    async fn open_stream(connection: &Connection, session_id: u64) -> (SendStream, RecvStream) {
+        let (mut send, recv) = connection.open_bi().await.unwrap();
+        let mut prefix = Vec::new();
+        put_varint(&mut prefix, WEBTRANSPORT_STREAM);
+        put_varint(&mut prefix, session_id);
+        send.write_all(&prefix).await.unwrap();
+        (send, recv)
+    }
+
+    #[tokio::test]
+    async fn test_frames_flow_over_session() {
+        let (chain, key) = generate_dev_cert(vec!["localhost".into()], 1).unwrap();
+        let mut roots = rustls::RootCertStore::empty();
+        roots.add(&chain[0]).unwrap();
+        let server = OrzattyServer::bind("127.0.0.1:0".parse().unwrap(), chain, key)
+            .unwrap()
+            .with_authenticator(auth::Anonymous)
+            .with_webtransport();
+        let addr = server.local_addr().unwrap();
+        let served = tokio::spawn(async move {
+            let mut conn = server.accept().await.unwrap().authenticate().await?;
+            assert!(conn.is_webtransport());
+            conn.on(1, |_header, payload| Some(payload));
+            conn.run().await
+        });
+
+        let mut crypto = rustls::ClientConfig::builder().with_safe_defaults()
+            .with_root_certificates(roots).with_no_client_auth();
+        crypto.alpn_protocols = vec![WEBTRANSPORT_ALPN.to_vec()];
+        let browser = quinn::Endpoint::client("127.0.0.1:0".parse().unwrap()).unwrap();
+        let connecting = browser.connect_with(quinn::ClientConfig::new(Arc::new(crypto)), addr, "localhost").unwrap();
+        let connection = connecting.await.unwrap();
+        let (session_id, mut connect_stream) = open_session(&connection).await;
+
+        // Sessions take no unidirectional streams of their own
+        let mut uni = connection.open_uni().await.unwrap();
+        let mut prefix = Vec::new();
+        put_varint(&mut prefix, 0x54);
+        put_varint(&mut prefix, session_id);
+        uni.write_all(&prefix).await.unwrap();
+        assert_eq!(uni.stopped().await.unwrap(), VarInt::from_u32(H3_STREAM_CREATION_ERROR));
+
+        let (mut send, mut recv) = open_stream(&connection, session_id).await;
+        let hello = AuthMessage::Hello { token: "t".into(), protocol_version: PROTOCOL_VERSION, capabilities: Capabilities::DATAGRAMS.bits(), metadata: Vec::new() };
+        write_auth(&mut send, &hello).await.unwrap();
+        let reply = read_auth(&mut Framer::new(), &mut recv).await.unwrap();
+        assert!(matches!(reply, Some(AuthMessage::Ok { capabilities, .. }) if capabilities == Capabilities::DATAGRAMS.bits()));
+
+        let (mut send, mut recv) = open_stream(&connection, session_id).await;
+        send.write_all(&FrameBuilder::new(b"ping").channel(1).to_bytes().unwrap()).await.unwrap();
+        let (header, payload) = Framer::new().read_frame(&mut recv).await.unwrap().unwrap();
+        assert_eq!((header.channel_id, &payload[..]), (1, &b"ping"[..]));
+
+        // Datagrams carry the session's quarter stream id, both ways
+        let mut datagram = Vec::new();
+        put_varint(&mut datagram, session_id / 4);
+        datagram.extend(FrameBuilder::new(b"pong").channel(1).datagram().to_bytes().unwrap());
+        connection.send_datagram(datagram.clone().into()).unwrap();
+        let echoed = tokio::time::timeout(Duration::from_secs(5), connection.read_datagram()).await.unwrap().unwrap();
+        assert_eq!(&echoed[..], &datagram[..]);
+
+        // Ending the session reads as the client closing on purpose
+        connect_stream.finish().await.unwrap();
+        assert!(served.await.unwrap().is_ok());
+    }
+}
@@ -1,18 +0,0 @@
-/* tslint:disable */
-/* eslint-disable */
-export const memory: WebAssembly.Memory;
-export const __wbg_orzattywasmclient_free: (a: number, b: number) => void;
-export const orzattywasmclient_connect: (a: number, b: number, c: number, d: number) => any;
-export const orzattywasmclient_on: (a: number, b: number, c: any) => void;
-export const orzattywasmclient_send: (a: number, b: number, c: number, d: number) => any;
-export const start: () => void;
-export const wasm_bindgen__closure__destroy__h74e6bd6f43229e6f: (a: number, b: number) => void;
-export const wasm_bindgen__convert__closures_____invoke__h0c9bc67126ae64e3: (a: number, b: number, c: any, d: any) => void;
-export const wasm_bindgen__convert__closures_____invoke__haa83310f2622f82c: (a: number, b: number, c: any) => void;
-export const __wbindgen_malloc: (a: number, b: number) => number;
-export const __wbindgen_realloc: (a: number, b: number, c: number, d: number) => number;
-export const __wbindgen_exn_store: (a: number) => void;
-export const __externref_table_alloc: () => number;
-export const __wbindgen_externrefs: WebAssembly.Table;
-export const __wbindgen_free: (a: number, b: number, c: number) => void;
-export const __wbindgen_start: () => void;
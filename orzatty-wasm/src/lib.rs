@@ -6,12 +6,19 @@ use web_sys::{
     console
 };
 use js_sys::{Uint8Array, Reflect};
-use orzatty_core::frame::{FrameHeader, FrameType, FrameFlags};
-// Removed Framer: use orzatty_core::Framer;
+use orzatty_core::auth::{AuthMessage, Capabilities};
+use orzatty_core::frame::{FrameBuilder, FrameFlags, FrameHeader, FrameType};
+use orzatty_core::{FrameDecoder, PROTOCOL_VERSION};
+use bytes::BytesMut;
 use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
+use std::rc::Rc;
 use std::cell::RefCell;
 
+/// Features the WASM client implements, offered in `AuthMessage::Hello`.
+const SUPPORTED_CAPABILITIES: Capabilities = Capabilities::DATAGRAMS
+    .union(Capabilities::FRAGMENTATION)
+    .union(Capabilities::CHECKSUM);
+
 // Need a panic hook for better debugging in browser console
 #[wasm_bindgen(start)]
 pub fn start() {
@@ -20,17 +27,31 @@ pub fn start() {
 
 /// The Orzatty Client for Web (WASM)
 /// 
-/// Bridges rust-core framing with browser WebTransport.
+/// Bridges rust-core framing with browser WebTransport. Two send paths:
+/// - `send`: reliable and ordered, over one long-lived session stream.
+/// - `send_datagram`: unreliable and unordered, one frame per datagram;
+///   it may be dropped, and must fit the path's datagram size.
+///
+/// Frames from either path reach the same `on` callbacks.
 #[wasm_bindgen]
 pub struct OrzattyWasmClient {
     transport: WebTransport,
-    writer: WritableStreamDefaultWriter,
-    callbacks: Arc<Mutex<HashMap<u32, js_sys::Function>>>,
+    datagram_writer: WritableStreamDefaultWriter,
+    // Features agreed with the server in the handshake
+    capabilities: Capabilities,
+    // Writer of the session bi-stream; `None` after a write error, so the
+    // next `send` opens a fresh stream
+    session: RefCell<Option<WritableStreamDefaultWriter>>,
+    callbacks: Rc<RefCell<HashMap<u32, js_sys::Function>>>,
+    // Called with a message when an incoming stream carries a malformed frame
+    error_callback: Rc<RefCell<Option<js_sys::Function>>>,
 }
 
 #[wasm_bindgen]
 impl OrzattyWasmClient {
-    /// Connects to a server url (e.g., "https://localhost:5000")
+    /// Connects to a server url (e.g., "https://localhost:5000") and authenticates.
+    ///
+    /// The promise rejects with the server's reason if the token is refused.
     // Changed from constructor to static method to avoid async constructor warning
     pub async fn connect(url: String, token: String) -> Result<OrzattyWasmClient, JsValue> {
         // ... (Log omitted for brevity)
@@ -45,84 +66,250 @@ impl OrzattyWasmClient {
         
         // Fix: Use generic JS wrapper or specific cast
         let bi_stream: web_sys::WebTransportBidirectionalStream = stream.into();
-        let writer = bi_stream.writable().get_writer()?;
+        let auth_writer = bi_stream.writable().get_writer()?;
         // Fix: Cast WebTransportReceiveStream to ReadableStream
         let readable: web_sys::ReadableStream = bi_stream.readable().into();
-        let reader_lock = readable.get_reader().unchecked_into::<ReadableStreamDefaultReader>();
+        let auth_reader = readable.get_reader().unchecked_into::<ReadableStreamDefaultReader>();
 
-        // ... (Skipping Auth logic for MVP speed)
+        let capabilities = Self::authenticate(&auth_writer, &auth_reader, token).await?;
+        console::log_1(&"Authenticated".into());
 
-        // Store transport writer for Datagrams (or Streams)
-        let datagrams_writable = transport.datagrams().writable();
-        let writer = datagrams_writable.get_writer()?;
+        let datagram_writer = transport.datagrams().writable().get_writer()?;
 
         let client = OrzattyWasmClient {
             transport: transport.clone(),
-            writer,
-            callbacks: Arc::new(Mutex::new(HashMap::new())),
+            datagram_writer,
+            capabilities,
+            session: RefCell::new(None),
+            callbacks: Rc::new(RefCell::new(HashMap::new())),
+            error_callback: Rc::new(RefCell::new(None)),
         };
+        let session = client.open_session().await?;
+        *client.session.borrow_mut() = Some(session);
 
         let callbacks_clone = client.callbacks.clone();
+        let error_callback = client.error_callback.clone();
         let incoming_uni = transport.incoming_unidirectional_streams();
         
         wasm_bindgen_futures::spawn_local(async move {
             let reader = incoming_uni.get_reader().unchecked_into::<ReadableStreamDefaultReader>();
-            loop {
-                 match JsFuture::from(reader.read()).await {
-                     Ok(chunk) => {
-                         let done = Reflect::get(&chunk, &"done".into()).unwrap().as_bool().unwrap();
-                         if done { break; }
-                         let value = Reflect::get(&chunk, &"value".into()).unwrap();
-                         let stream: web_sys::WebTransportReceiveStream = value.into();
-                         Self::handle_stream(stream, callbacks_clone.clone());
-                     }
-                     Err(_) => break,
-                 }
+            while let Ok(chunk) = JsFuture::from(reader.read()).await {
+                let done = Reflect::get(&chunk, &"done".into()).unwrap().as_bool().unwrap();
+                if done { break; }
+                let value = Reflect::get(&chunk, &"value".into()).unwrap();
+                let stream: web_sys::WebTransportReceiveStream = value.into();
+                Self::handle_stream(stream, callbacks_clone.clone(), error_callback.clone());
             }
         });
 
+        if capabilities.contains(Capabilities::DATAGRAMS) {
+            Self::handle_datagrams(&transport, client.callbacks.clone());
+        }
+
         Ok(client)
     }
     
-    // Fix: Prefix unused arg
-    fn handle_stream(stream: web_sys::WebTransportReceiveStream, _callbacks: Arc<Mutex<HashMap<u32, js_sys::Function>>>) {
+    /// Sends `AuthMessage::Hello` and waits for the server's verdict.
+    /// Returns the negotiated capabilities.
+    async fn authenticate(
+        writer: &WritableStreamDefaultWriter,
+        reader: &ReadableStreamDefaultReader,
+        token: String,
+    ) -> Result<Capabilities, JsValue> {
+        let hello = AuthMessage::Hello {
+            token,
+            protocol_version: PROTOCOL_VERSION,
+            capabilities: SUPPORTED_CAPABILITIES.bits(),
+            metadata: Vec::new(),
+        };
+        let hello = rkyv::to_bytes::<_, 256>(&hello)
+            .map_err(|e| JsValue::from_str(&format!("Failed to serialize auth hello: {:?}", e)))?;
+        let header = FrameHeader {
+            flags: FrameFlags::empty(),
+            frame_type: FrameType::RkyvAligned,
+            channel_id: 0,
+            stream_id: 0,
+            length: hello.len() as u64,
+        };
+        let mut frame = Vec::new();
+        header.encode_with_payload(&hello, &mut frame)
+            .map_err(|e| JsValue::from_str(&format!("Failed to encode auth frame: {}", e)))?;
+        JsFuture::from(writer.write_with_chunk(&Uint8Array::from(&frame[..]))).await?;
+
+        let (_header, payload) = read_frame(reader, &mut FrameDecoder::new()).await?
+            .ok_or_else(|| JsValue::from_str("Server closed auth stream before response"))?;
+        let mut aligned = rkyv::AlignedVec::new();
+        aligned.extend_from_slice(&payload);
+        let response: AuthMessage = rkyv::from_bytes(&aligned)
+            .map_err(|_| JsValue::from_str("Failed to deserialize auth response"))?;
+
+        match response {
+            AuthMessage::Ok { capabilities, .. } => {
+                Ok(SUPPORTED_CAPABILITIES & Capabilities::from_bits_truncate(capabilities))
+            }
+            AuthMessage::Fail { reason } => {
+                Err(JsValue::from_str(&format!("Authentication failed: {}", reason)))
+            }
+            _ => Err(JsValue::from_str("Unexpected auth response")),
+        }
+    }
+
+    /// Decodes frames from an incoming stream and fires the matching channel callback.
+    ///
+    /// Chunks are buffered until a frame is complete, so frames may span
+    /// chunks and one chunk may carry several. A malformed frame ends the
+    /// stream and is reported to the `on_error` callback.
+    fn handle_stream(
+        stream: web_sys::WebTransportReceiveStream,
+        callbacks: Rc<RefCell<HashMap<u32, js_sys::Function>>>,
+        error_callback: Rc<RefCell<Option<js_sys::Function>>>,
+    ) {
         wasm_bindgen_futures::spawn_local(async move {
              let readable: web_sys::ReadableStream = stream.into();
              let reader = readable.get_reader().unchecked_into::<ReadableStreamDefaultReader>();
-             
+             let mut decoder = FrameDecoder::new();
+
              loop {
-                 let res = JsFuture::from(reader.read()).await;
-                 if let Ok(chunk) = res {
-                     let done = Reflect::get(&chunk, &"done".into()).unwrap().as_bool().unwrap();
-                     if done { break; }
-                     
-                     let val = Reflect::get(&chunk, &"value".into()).unwrap();
-                     let data = Uint8Array::new(&val);
-                     let vec = data.to_vec();
-                     
-                     if vec.len() > 10 {
-                          // TODO: Callback logic
+                 match read_frame(&reader, &mut decoder).await {
+                     Ok(Some((header, payload))) => {
+                         dispatch(&callbacks, header.channel_id, &payload);
+                     }
+                     Ok(None) => break,
+                     Err(e) => {
+                         let callback = error_callback.borrow().clone();
+                         match callback {
+                             Some(callback) => { let _ = callback.call1(&JsValue::NULL, &e); }
+                             None => console::error_1(&e),
+                         }
+                         let _ = JsFuture::from(reader.cancel()).await;
+                         break;
                      }
-                 } else { break; }
+                 }
              }
         });
     }
 
+    /// Decodes incoming datagrams, one frame each, and fires the matching
+    /// channel callback. Malformed datagrams are dropped, as on the server.
+    fn handle_datagrams(transport: &WebTransport, callbacks: Rc<RefCell<HashMap<u32, js_sys::Function>>>) {
+        let readable = transport.datagrams().readable();
+        wasm_bindgen_futures::spawn_local(async move {
+            let reader = readable.get_reader().unchecked_into::<ReadableStreamDefaultReader>();
+            while let Ok(chunk) = JsFuture::from(reader.read()).await {
+                let done = Reflect::get(&chunk, &"done".into()).ok().and_then(|d| d.as_bool()).unwrap_or(true);
+                if done { break; }
+                let Ok(value) = Reflect::get(&chunk, &"value".into()) else { break };
+                let datagram = Uint8Array::new(&value).to_vec();
+
+                let Ok((header, head_len)) = FrameHeader::decode(&datagram) else { continue };
+                let body = &datagram[head_len..];
+                if header.length != body.len() as u64 {
+                    continue;
+                }
+                let Ok(payload) = header.strip_checksum(body) else { continue };
+                dispatch(&callbacks, header.channel_id, payload);
+            }
+        });
+    }
+
+    /// Bits of the `Capabilities` agreed with the server during the handshake.
+    pub fn capabilities(&self) -> u32 {
+        self.capabilities.bits()
+    }
+
     pub fn on(&self, channel_id: u32, callback: js_sys::Function) {
-        self.callbacks.lock().unwrap().insert(channel_id, callback);
+        self.callbacks.borrow_mut().insert(channel_id, callback);
     }
 
+    /// Sets the callback that receives an error message (a string) whenever
+    /// an incoming stream carries a malformed or truncated frame. Without
+    /// one, the message is logged to the console.
+    pub fn on_error(&self, callback: js_sys::Function) {
+        *self.error_callback.borrow_mut() = Some(callback);
+    }
+
+    /// Sends `data` on `channel_id` as a datagram: unreliable and unordered.
+    ///
+    /// Suited to state that is superseded quickly, such as positions. Rejects
+    /// if the frame does not fit in a datagram; use `send` for larger data.
+    pub async fn send_datagram(&self, channel_id: u32, data: &[u8]) -> Result<(), JsValue> {
+        if !self.capabilities.contains(Capabilities::DATAGRAMS) {
+            return Err(JsValue::from_str("Datagrams were not negotiated with the server"));
+        }
+        let frame = FrameBuilder::new(data).channel(channel_id).datagram().to_bytes()
+            .map_err(|e| JsValue::from_str(&format!("Failed to encode datagram: {}", e)))?;
+        JsFuture::from(self.datagram_writer.write_with_chunk(&Uint8Array::from(&frame[..]))).await?;
+        Ok(())
+    }
+
+    /// Sends `data` on `channel_id` reliably and in order.
+    ///
+    /// Every frame goes on the one session stream, like the native client's
+    /// writer actor; the server's replies come back on it. If a write fails,
+    /// the promise rejects and the next call opens a new session stream.
     pub async fn send(&self, channel_id: u32, data: &[u8]) -> Result<(), JsValue> {
-        let stream_promise = self.transport.create_unidirectional_stream();
-        let stream = JsFuture::from(stream_promise).await?;
-        let send_stream: web_sys::WebTransportSendStream = stream.into();
-        let writer = send_stream.get_writer()?;
-        
-        let arr = Uint8Array::from(data);
-        // Fix: Use write_with_chunk
-        JsFuture::from(writer.write_with_chunk(&arr)).await?;
-        JsFuture::from(writer.close()).await?;
-        
+        let frame = FrameBuilder::new(data).channel(channel_id).to_bytes()
+            .map_err(|e| JsValue::from_str(&format!("Failed to encode frame: {}", e)))?;
+
+        // Clone the handle out so no borrow is held across an await
+        let current = self.session.borrow().clone();
+        let writer = match current {
+            Some(writer) => writer,
+            None => {
+                let writer = self.open_session().await?;
+                *self.session.borrow_mut() = Some(writer.clone());
+                writer
+            }
+        };
+        if let Err(e) = JsFuture::from(writer.write_with_chunk(&Uint8Array::from(&frame[..]))).await {
+            self.session.borrow_mut().take();
+            return Err(e);
+        }
         Ok(())
     }
 }
+
+impl OrzattyWasmClient {
+    /// Opens a session bi-stream, starts reading replies from it and returns
+    /// its writer.
+    async fn open_session(&self) -> Result<WritableStreamDefaultWriter, JsValue> {
+        let stream = JsFuture::from(self.transport.create_bidirectional_stream()).await?;
+        let bi_stream: web_sys::WebTransportBidirectionalStream = stream.into();
+        Self::handle_stream(bi_stream.readable(), self.callbacks.clone(), self.error_callback.clone());
+        bi_stream.writable().get_writer()
+    }
+}
+
+/// Fires the callback registered for `channel_id`, if any.
+fn dispatch(callbacks: &RefCell<HashMap<u32, js_sys::Function>>, channel_id: u32, payload: &[u8]) {
+    // Clone out of the map so a callback may call `on` re-entrantly
+    let callback = callbacks.borrow().get(&channel_id).cloned();
+    if let Some(callback) = callback {
+        let _ = callback.call1(&JsValue::NULL, &Uint8Array::from(payload));
+    }
+}
+
+/// Feeds chunks from `reader` into `decoder` until one whole frame is available.
+///
+/// Bytes past the frame stay buffered for the next call. `Ok(None)` means the
+/// stream ended cleanly on a frame boundary.
+async fn read_frame(
+    reader: &ReadableStreamDefaultReader,
+    decoder: &mut FrameDecoder,
+) -> Result<Option<(FrameHeader, BytesMut)>, JsValue> {
+    loop {
+        if let Some(frame) = decoder.next_frame()
+            .map_err(|e| JsValue::from_str(&format!("Frame decode error: {}", e)))? {
+            return Ok(Some(frame));
+        }
+
+        let chunk = JsFuture::from(reader.read()).await?;
+        let done = Reflect::get(&chunk, &"done".into())?.as_bool().unwrap_or(true);
+        if done {
+            decoder.finish().map_err(|e| JsValue::from_str(&e.to_string()))?;
+            return Ok(None);
+        }
+        let value = Reflect::get(&chunk, &"value".into())?;
+        decoder.push(&Uint8Array::new(&value).to_vec());
+    }
+}
@@ -1,87 +1,92 @@
-use quinn::Endpoint;
-use orzatty_core::frame::{FrameHeader, FrameType, FrameFlags};
-use orzatty_core::Framer;
-use orzatty_core::auth::AuthMessage;
 use std::net::SocketAddr;
-use std::sync::Arc;
 use anyhow::Result;
+use orzatty_server::auth::{Anonymous, AuthDecision, Denylist, StaticTokens};
+use orzatty_server::dev_cert;
+use orzatty_server::{OrzattyServer, RateLimit, RateLimitAction};
+
+/// Lifetime of the certificate generated at startup.
+const DEV_CERT_VALID_DAYS: u32 = 14;
+
+/// Where the generated certificate (DER) is written for clients to trust,
+/// unless ORZATTY_CERT_OUT names another path.
+const DEV_CERT_FILE: &str = "orzatty-dev-cert.der";
+
+/// Frames on this channel are relayed to every connected client (see
+/// `examples/chat_client.rs`).
+const CHAT_CHANNEL: u32 = 10;
 
 #[tokio::main]
 async fn main() -> Result<()> {
     let addr: SocketAddr = "127.0.0.1:5000".parse()?;
     
-    // 💡 Generate a simple self-signed cert for the example
-    let cert = rcgen::generate_simple_self_signed(vec!["localhost".into()])?;
-    let cert_der = cert.serialize_der()?;
-    let priv_key = cert.serialize_private_key_der();
+    // 💡 Generate a dev-only self-signed cert for the example.
+    // SANs come from the command line (hostnames or IPs), e.g. `simple_server myhost 10.0.0.5`
+    let mut sans: Vec<String> = std::env::args().skip(1).collect();
+    if sans.is_empty() {
+        sans = vec!["localhost".into(), "127.0.0.1".into()];
+    }
+    println!("🔐 Dev certificate for {:?}, valid {} days", sans, DEV_CERT_VALID_DAYS);
+    let (cert_chain, priv_key) = dev_cert::generate_dev_cert(sans, DEV_CERT_VALID_DAYS)?;
+    // Clients verify the server against it (`add_root_cert`); a new one is made on every start
+    let cert_path = std::env::var("ORZATTY_CERT_OUT").unwrap_or_else(|_| DEV_CERT_FILE.into());
+    std::fs::write(&cert_path, &cert_chain[0].0)?;
+    println!("📄 Certificate written to {}", cert_path);
     
-    let server_config = quinn::ServerConfig::with_single_cert(
-        vec![rustls::Certificate(cert_der)],
-        rustls::PrivateKey(priv_key)
-    )?;
+    // Browsers (`orzatty-wasm`) connect over WebTransport on the same port
+    let mut server = OrzattyServer::bind(addr, cert_chain, priv_key)?.with_webtransport();
+    println!("🦅 Orzatty Reference Server listening on {} (QUIC and WebTransport)", addr);
 
-    let endpoint = Endpoint::server(server_config, addr)?;
-    println!("🦅 Orzatty Reference Server listening on {}", addr);
+    // Revoked tokens from ORZATTY_DENY and service tokens from ORZATTY_TOKENS
+    // (both comma separated); empty tokens are refused and anything else is
+    // let in as an anonymous session.
+    let env_list = |name: &str| -> Vec<String> {
+        std::env::var(name).unwrap_or_default()
+            .split(',').filter(|t| !t.is_empty()).map(String::from).collect()
+    };
+    server = server
+        .with_auth_fn(|token: &str| if token.is_empty() {
+            AuthDecision::Reject { reason: "empty token".into() }
+        } else {
+            AuthDecision::Pass
+        })
+        .with_authenticator(Denylist::new(env_list("ORZATTY_DENY")))
+        .with_authenticator(StaticTokens::new(env_list("ORZATTY_TOKENS")))
+        .with_authenticator(Anonymous);
 
-    while let Some(conn) = endpoint.accept().await {
-        tokio::spawn(async move {
-            if let Err(e) = handle_client(conn).await {
-                eprintln!("❌ Client error: {:?}", e);
-            }
-        });
+    // With ORZATTY_SECRET set, clients must also answer an HMAC challenge
+    if let Ok(secret) = std::env::var("ORZATTY_SECRET") {
+        server = server.with_auth_secret(secret);
     }
 
-    Ok(())
-}
-
-async fn handle_client(conn: quinn::Connecting) -> Result<()> {
-    let connection = conn.await?;
-    println!("🔗 New connection from {}", connection.remote_address());
-
-    // 1. Simple Auth Handshake
-    let (mut send, mut recv) = connection.accept_bi().await?;
-    let mut framer = Framer::new();
-    
-    if let Some((_header, payload)) = framer.read_frame(&mut recv).await? {
-        let auth: AuthMessage = rkyv::from_bytes(&payload)
-            .map_err(|_| anyhow::anyhow!("Failed to deserialize auth message"))?;
-            
-        match auth {
-            AuthMessage::Hello { token } => {
-                println!("🔑 Auth attempt with token: {}", token);
-                // In this basic version, we accept everything
-                let resp = rkyv::to_bytes::<_, 32>(&AuthMessage::Ok)
-                    .map_err(|_| anyhow::anyhow!("Failed to serialize auth response"))?;
-                let head = FrameHeader {
-                    flags: FrameFlags::empty(),
-                    frame_type: FrameType::RkyvAligned,
-                    channel_id: 0,
-                    stream_id: 0,
-                    length: resp.len() as u64,
-                };
-                let mut buf = [0u8; 32];
-                let n = head.encode(&mut buf)?;
-                send.write_all(&buf[..n]).await?;
-                send.write_all(&resp).await?;
-                println!("✅ Client Authenticated.");
-            }
-            _ => return Err(anyhow::anyhow!("Expected Auth Hello")),
-        }
+    // ORZATTY_MAX_FPS / ORZATTY_MAX_BPS cap what each client may send
+    // (frames and bytes per second); a client over either is disconnected
+    let max_fps: Option<u32> = std::env::var("ORZATTY_MAX_FPS").ok().and_then(|v| v.parse().ok());
+    let max_bps: Option<u64> = std::env::var("ORZATTY_MAX_BPS").ok().and_then(|v| v.parse().ok());
+    if max_fps.is_some() || max_bps.is_some() {
+        server = server.with_rate_limit(RateLimit {
+            frames_per_sec: max_fps,
+            bytes_per_sec: max_bps,
+            on_exceed: RateLimitAction::Close,
+        });
+        println!("🚦 Rate limit: {:?} frames/s, {:?} bytes/s", max_fps, max_bps);
     }
 
-    // 2. Simple Echo Loop
-    loop {
-        let (mut send, mut recv) = connection.accept_bi().await?;
-        tokio::spawn(async move {
-            let mut framer = Framer::new();
-            while let Some((header, payload)) = framer.read_frame(&mut recv).await.unwrap_or(None) {
-                println!("📥 Received {} bytes on channel {}", payload.len(), header.channel_id);
-                // Echo back
-                let mut buf = [0u8; 32];
-                let n = header.encode(&mut buf).unwrap();
-                let _ = send.write_all(&buf[..n]).await;
-                let _ = send.write_all(&payload).await;
-            }
+    server.serve(|mut conn| async move {
+        println!("✅ Client Authenticated as {}.", conn.identity());
+        let hub = conn.hub().clone();
+        let (id, identity) = (conn.id(), conn.identity().to_string());
+        hub.broadcast_except(id, CHAT_CHANNEL, format!("* client {} ({}) joined", id, identity).as_bytes());
+
+        conn.broadcast_channel(CHAT_CHANNEL);
+        // Echo every other application frame back unchanged, stream or datagram
+        conn.on_any(|header, payload| {
+            println!("📥 Received {} bytes on channel {}", payload.len(), header.channel_id);
+            Some(payload)
         });
-    }
+        conn.on_close(|code, reason| println!("👋 Client closing (code {}): {}", code, reason));
+        let result = conn.run().await;
+
+        hub.broadcast(CHAT_CHANNEL, format!("* client {} ({}) left", id, identity).as_bytes());
+        result
+    }).await
 }